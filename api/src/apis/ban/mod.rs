@@ -0,0 +1,288 @@
+use chrono::{TimeDelta, Utc};
+use dropshot::{endpoint, ClientErrorStatusCode, HttpError, HttpResponseOk, RequestContext};
+use dropshot::{EmptyScanParams, PaginationParams, Path, Query, ResultsPage, TypedBody, WhichPage};
+use harm_entity::ban::{self, Entity as BanEntity, Model as BanModel};
+use schemars::JsonSchema;
+use sea_orm::{prelude::*, QueryOrder, QuerySelect};
+use serde::Deserialize;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{bans, context::ServerCtx};
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+struct BanPage {
+    pub id: Uuid,
+}
+
+impl From<&BanModel> for BanPage {
+    fn from(value: &BanModel) -> Self {
+        Self { id: value.id }
+    }
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct GetBanPath {
+    /// The ID of the ban to fetch data for.
+    id: Uuid,
+}
+
+#[derive(JsonSchema, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BanBody {
+    /// The player's Reforger identity ID.
+    identity_id: String,
+
+    /// The player's name, for reference.
+    name: Option<String>,
+
+    reason: Option<String>,
+
+    /// Who issued the ban.
+    issuer: Option<String>,
+
+    /// How many seconds the ban lasts from now. Bans without a duration are
+    /// permanent.
+    duration_secs: Option<u64>,
+}
+
+impl BanBody {
+    /// Checks the ban, and returns when it expires if it isn't permanent.
+    fn check(&self) -> Result<Option<ChronoDateTimeUtc>, HttpError> {
+        if self.identity_id.is_empty() || self.identity_id.contains(char::is_whitespace) {
+            return Err(HttpError::for_client_error(
+                Some("INVALID_IDENTITY".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                "The identity ID must not be empty or contain spaces.".to_string(),
+            ));
+        }
+
+        let Some(secs) = self.duration_secs else {
+            return Ok(None);
+        };
+        if secs == 0 {
+            return Err(HttpError::for_client_error(
+                Some("INVALID_DURATION".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                "durationSecs must be above 0, or unset for a permanent ban.".to_string(),
+            ));
+        }
+        let expires_at = i64::try_from(secs)
+            .ok()
+            .and_then(TimeDelta::try_seconds)
+            .and_then(|duration| Utc::now().checked_add_signed(duration));
+        match expires_at {
+            Some(expires_at) => Ok(Some(expires_at)),
+            None => Err(HttpError::for_client_error(
+                Some("INVALID_DURATION".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                "durationSecs is too large; leave it unset for a permanent ban.".to_string(),
+            )),
+        }
+    }
+}
+
+/// Checks that no other ban is for the same player.
+async fn check_not_banned(
+    db: &DatabaseConnection,
+    identity_id: &str,
+    except: Option<Uuid>,
+) -> Result<(), HttpError> {
+    let existing = BanEntity::find()
+        .filter(ban::Column::IdentityId.eq(identity_id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    match existing {
+        Some(existing) if Some(existing.id) != except => Err(HttpError::for_client_error(
+            Some("ALREADY_BANNED".to_string()),
+            ClientErrorStatusCode::CONFLICT,
+            format!("That player is already banned, by ban {}.", existing.id),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[endpoint(
+    method = GET,
+    path = "/bans",
+)]
+pub async fn list_bans(
+    rqctx: RequestContext<ServerCtx>,
+    query: Query<PaginationParams<EmptyScanParams, BanPage>>,
+) -> Result<HttpResponseOk<ResultsPage<BanModel>>, HttpError> {
+    let pag_params = query.into_inner();
+    let limit = rqctx.page_limit(&pag_params)?.get() as u64;
+    let db = &rqctx.context().db;
+
+    let bans = match &pag_params.page {
+        WhichPage::First(..) => BanEntity::find()
+            .limit(limit)
+            .order_by_asc(ban::Column::Id)
+            .all(db)
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string())),
+
+        WhichPage::Next(BanPage { id }) => BanEntity::find()
+            .limit(limit)
+            .filter(ban::Column::Id.gt(*id))
+            .order_by_asc(ban::Column::Id)
+            .all(db)
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string())),
+    }?;
+
+    Ok(HttpResponseOk(ResultsPage::new(
+        bans,
+        &EmptyScanParams {},
+        |p: &BanModel, _| BanPage::from(p),
+    )?))
+}
+
+#[endpoint(
+    method = GET,
+    path = "/bans/{id}",
+)]
+pub async fn get_ban(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetBanPath>,
+) -> Result<HttpResponseOk<BanModel>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let ban = BanEntity::find_by_id(path.id)
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(ban) = ban {
+        Ok(HttpResponseOk(ban))
+    } else {
+        Err(HttpError::for_not_found(
+            Some("NO_SUCH_BAN".to_string()),
+            "No ban with that ID was found.".to_string(),
+        ))
+    }
+}
+
+/// Bans a player from every server, and pushes the ban to every running
+/// server.
+#[endpoint(
+    method = POST,
+    path = "/bans",
+)]
+pub async fn create_ban(
+    rqctx: RequestContext<ServerCtx>,
+    body: TypedBody<BanBody>,
+) -> Result<HttpResponseOk<BanModel>, HttpError> {
+    let db = &rqctx.context().db;
+    let pm = &rqctx.context().process_manager;
+    let body = body.into_inner();
+
+    let expires_at = body.check()?;
+    check_not_banned(db, &body.identity_id, None).await?;
+
+    let ban = ban::ActiveModel {
+        id: sea_orm::ActiveValue::Set(Uuid::new_v4()),
+        identity_id: sea_orm::ActiveValue::Set(body.identity_id.clone()),
+        name: sea_orm::ActiveValue::Set(body.name.clone()),
+        reason: sea_orm::ActiveValue::Set(body.reason.clone()),
+        issuer: sea_orm::ActiveValue::Set(body.issuer.clone()),
+        created_at: sea_orm::ActiveValue::Set(Utc::now()),
+        expires_at: sea_orm::ActiveValue::Set(expires_at),
+    }
+    .insert(db)
+    .await
+    .map_err(|error| HttpError::for_internal_error(format!("failed to insert ban: {}", error)))?;
+
+    bans::push(pm, &rqctx.log, &ban).await;
+
+    Ok(HttpResponseOk(ban))
+}
+
+/// Replaces a ban, and pushes it to every running server again.
+#[endpoint(
+    method = PUT,
+    path = "/bans/{id}",
+)]
+pub async fn update_ban(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetBanPath>,
+    body: TypedBody<BanBody>,
+) -> Result<HttpResponseOk<BanModel>, HttpError> {
+    let db = &rqctx.context().db;
+    let pm = &rqctx.context().process_manager;
+    let path = path.into_inner();
+    let body = body.into_inner();
+
+    let existing = BanEntity::find_by_id(path.id)
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(existing) = existing {
+        let expires_at = body.check()?;
+        check_not_banned(db, &body.identity_id, Some(existing.id)).await?;
+
+        let ban = ban::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(existing.id),
+            identity_id: sea_orm::ActiveValue::Set(body.identity_id.clone()),
+            name: sea_orm::ActiveValue::Set(body.name.clone()),
+            reason: sea_orm::ActiveValue::Set(body.reason.clone()),
+            issuer: sea_orm::ActiveValue::Set(body.issuer.clone()),
+            created_at: sea_orm::ActiveValue::Unchanged(existing.created_at),
+            expires_at: sea_orm::ActiveValue::Set(expires_at),
+        }
+        .update(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update ban: {}", e)))?;
+
+        // Servers keep the ban they were given first, so it is lifted before
+        // the new one is pushed.
+        bans::lift(pm, &rqctx.log, &existing.identity_id).await;
+        bans::push(pm, &rqctx.log, &ban).await;
+
+        return Ok(HttpResponseOk(ban));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_BAN".to_string()),
+        "No ban with that ID was found.".to_string(),
+    ))
+}
+
+/// Removes a ban, and lifts it on every running server.
+#[endpoint(
+    method = DELETE,
+    path = "/bans/{id}",
+)]
+pub async fn delete_ban(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetBanPath>,
+) -> Result<HttpResponseOk<BanModel>, HttpError> {
+    let db = &rqctx.context().db;
+    let pm = &rqctx.context().process_manager;
+    let path = path.into_inner();
+
+    let ban = BanEntity::find_by_id(path.id)
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(ban) = ban {
+        BanEntity::delete_by_id(ban.id)
+            .exec(db)
+            .await
+            .map_err(|e| HttpError::for_internal_error(format!("failed to delete ban: {}", e)))?;
+
+        bans::lift(pm, &rqctx.log, &ban.identity_id).await;
+
+        return Ok(HttpResponseOk(ban));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_BAN".to_string()),
+        "No ban with that ID was found.".to_string(),
+    ))
+}
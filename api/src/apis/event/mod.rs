@@ -0,0 +1,204 @@
+use std::{
+    collections::HashSet,
+    convert::Infallible,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use dropshot::{endpoint, Body, ClientErrorStatusCode, HttpError, Query, RequestContext};
+use harm_pm::events::{EventSubscription, ServerEvent};
+use http::{header, Response, StatusCode};
+use http_body::Frame;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+use uuid::Uuid;
+
+use crate::context::ServerCtx;
+
+/// How often a comment is sent on an idle stream, so proxies keep it open
+/// and a client which went away is noticed.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How many frames may be waiting to be sent to a slow client before events
+/// are held back.
+const FRAME_BUFFER: usize = 64;
+
+#[derive(JsonSchema, Deserialize)]
+struct EventsQuery {
+    /// Only stream events from this server.
+    server: Option<Uuid>,
+
+    /// Only stream events of these types, separated by commas, e.g.
+    /// `stateChanged,playerConnected,playerDisconnected`.
+    types: Option<String>,
+
+    /// Resume after the event with this sequence number. Takes precedence
+    /// over the `Last-Event-ID` header browsers send when reconnecting.
+    after: Option<u64>,
+}
+
+/// Which events a client asked for.
+struct EventFilter {
+    server: Option<Uuid>,
+    types: Option<HashSet<String>>,
+}
+
+impl EventFilter {
+    /// Whether an event of type `kind` passes the filter.
+    fn check(&self, event: &ServerEvent, kind: &str) -> bool {
+        self.server.is_none_or(|server| server == event.server_id)
+            && self.types.as_ref().is_none_or(|types| types.contains(kind))
+    }
+}
+
+/// Streams events from every server as server-sent events. Each event is
+/// sent with its sequence number as its ID and its type as its event name,
+/// and clients resume after the last event they saw with `after` or the
+/// `Last-Event-ID` header. If events were missed, e.g. because the client
+/// was away for too long, a `lagged` event says how many. Sequence numbers
+/// start again from 0 when HARM restarts, so a cursor HARM hasn't reached
+/// yet is unknown: the stream then starts from the oldest event HARM
+/// remembers, after a `lagged` event with `reset` set.
+#[endpoint(
+    method = GET,
+    path = "/events"
+)]
+pub async fn stream_events(
+    rqctx: RequestContext<ServerCtx>,
+    query: Query<EventsQuery>,
+) -> Result<Response<Body>, HttpError> {
+    let query = query.into_inner();
+
+    let last_event_id = match rqctx.request.headers().get("last-event-id") {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .ok_or_else(|| {
+                    HttpError::for_client_error(
+                        Some("INVALID_CURSOR".to_string()),
+                        ClientErrorStatusCode::BAD_REQUEST,
+                        "Last-Event-ID must be an event's sequence number.".to_string(),
+                    )
+                })?,
+        ),
+        None => None,
+    };
+
+    let filter = EventFilter {
+        server: query.server,
+        types: query.types.map(|types| {
+            types
+                .split(',')
+                .map(str::trim)
+                .filter(|kind| !kind.is_empty())
+                .map(String::from)
+                .collect()
+        }),
+    };
+
+    let subscription = rqctx
+        .context()
+        .process_manager
+        .events()
+        .subscribe_since(query.after.or(last_event_id));
+    let (frames, frames_rx) = mpsc::channel(FRAME_BUFFER);
+    tokio::spawn(forward(subscription, filter, frames));
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::wrap(EventStream { frames: frames_rx }))
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))
+}
+
+/// Sends the events a client asked for until it goes away.
+async fn forward(
+    subscription: EventSubscription,
+    filter: EventFilter,
+    frames: mpsc::Sender<Bytes>,
+) {
+    if (subscription.missed > 0 || subscription.reset)
+        && frames
+            .send(lagged(subscription.missed, subscription.reset))
+            .await
+            .is_err()
+    {
+        return;
+    }
+    for event in &subscription.backlog {
+        if let Some(frame) = frame(&filter, event) {
+            if frames.send(frame).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    let mut receiver = subscription.receiver;
+    let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
+    keepalive.reset();
+    loop {
+        let frame = tokio::select! {
+            event = receiver.recv() => match event {
+                Ok(event) => match frame(&filter, &event) {
+                    Some(frame) => frame,
+                    None => continue,
+                },
+                Err(RecvError::Lagged(missed)) => lagged(missed, false),
+                Err(RecvError::Closed) => return,
+            },
+            _ = keepalive.tick() => Bytes::from_static(b": keepalive\n\n"),
+        };
+        if frames.send(frame).await.is_err() {
+            return;
+        }
+        keepalive.reset();
+    }
+}
+
+/// Formats an event as a server-sent event, if it passes the filter.
+fn frame(filter: &EventFilter, event: &ServerEvent) -> Option<Bytes> {
+    let data = serde_json::to_value(event).ok()?;
+    let kind = data.get("type")?.as_str()?;
+    if !filter.check(event, kind) {
+        return None;
+    }
+    Some(Bytes::from(format!(
+        "id: {}\nevent: {}\ndata: {}\n\n",
+        event.seq, kind, data
+    )))
+}
+
+/// Says how many events a client missed, and whether its cursor was unknown
+/// so it should forget what it knew and start over.
+fn lagged(missed: u64, reset: bool) -> Bytes {
+    Bytes::from(format!(
+        "event: lagged\ndata: {}\n\n",
+        serde_json::json!({ "missed": missed, "reset": reset })
+    ))
+}
+
+/// The body of an event stream, fed by `forward`. Dropping it when the client
+/// goes away stops `forward`.
+struct EventStream {
+    frames: mpsc::Receiver<Bytes>,
+}
+
+impl http_body::Body for EventStream {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        self.frames
+            .poll_recv(cx)
+            .map(|frame| frame.map(|frame| Ok(Frame::data(frame))))
+    }
+}
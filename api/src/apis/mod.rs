@@ -1 +1,6 @@
+pub mod ban;
+pub mod event;
+pub mod player;
+pub mod preset;
 pub mod server;
+pub mod trash;
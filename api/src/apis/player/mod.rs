@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+
+use chrono::Utc;
+use dropshot::{endpoint, HttpError, HttpResponseOk, RequestContext};
+use dropshot::{PaginationParams, Path, Query, ResultsPage, WhichPage};
+use harm_entity::{
+    player::{self, Entity as PlayerEntity, Model as PlayerModel},
+    session::{self, Entity as SessionEntity, Model as SessionModel},
+};
+use schemars::JsonSchema;
+use sea_orm::{prelude::*, Condition, QueryOrder, QuerySelect};
+use serde::Deserialize;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::context::ServerCtx;
+
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
+struct PlayerSearch {
+    /// Only list players whose identity ID, BattlEye GUID or any name they
+    /// have been seen with contains this.
+    q: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+struct PlayerPage {
+    pub id: Uuid,
+    pub search: PlayerSearch,
+}
+
+#[endpoint(
+    method = GET,
+    path = "/players",
+)]
+pub async fn list_players(
+    rqctx: RequestContext<ServerCtx>,
+    query: Query<PaginationParams<PlayerSearch, PlayerPage>>,
+) -> Result<HttpResponseOk<ResultsPage<PlayerModel>>, HttpError> {
+    let pag_params = query.into_inner();
+    let limit = rqctx.page_limit(&pag_params)?.get() as u64;
+    let db = &rqctx.context().db;
+
+    let (search, after) = match &pag_params.page {
+        WhichPage::First(search) => (search.clone(), None),
+        WhichPage::Next(PlayerPage { id, search }) => (search.clone(), Some(*id)),
+    };
+
+    let mut find = PlayerEntity::find()
+        .limit(limit)
+        .order_by_asc(player::Column::Id);
+    if let Some(after) = after {
+        find = find.filter(player::Column::Id.gt(after));
+    }
+    if let Some(q) = search.q.as_deref().filter(|q| !q.is_empty()) {
+        let pattern = format!("%{}%", q);
+        find = find.filter(
+            Condition::any()
+                .add(player::Column::IdentityId.like(&pattern))
+                .add(player::Column::Guid.like(&pattern))
+                .add(player::Column::Names.like(&pattern)),
+        );
+    }
+
+    let players = find
+        .all(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    Ok(HttpResponseOk(ResultsPage::new(
+        players,
+        &search,
+        |p: &PlayerModel, search| PlayerPage {
+            id: p.id,
+            search: search.clone(),
+        },
+    )?))
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct GetPlayerPath {
+    /// The ID of the player to fetch data for.
+    id: Uuid,
+}
+
+async fn find_player(
+    rqctx: &RequestContext<ServerCtx>,
+    id: Uuid,
+) -> Result<PlayerModel, HttpError> {
+    PlayerEntity::find_by_id(id)
+        .one(&rqctx.context().db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?
+        .ok_or_else(|| {
+            HttpError::for_not_found(
+                Some("NO_SUCH_PLAYER".to_string()),
+                "No player with that ID was found.".to_string(),
+            )
+        })
+}
+
+#[endpoint(
+    method = GET,
+    path = "/players/{id}",
+)]
+pub async fn get_player(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetPlayerPath>,
+) -> Result<HttpResponseOk<PlayerModel>, HttpError> {
+    let path = path.into_inner();
+    Ok(HttpResponseOk(find_player(&rqctx, path.id).await?))
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct SessionsQuery {
+    /// Only list sessions on this server.
+    server: Option<Uuid>,
+
+    /// How many of the most recent sessions to list. Defaults to 100, and is
+    /// capped at 1000.
+    limit: Option<u64>,
+}
+
+#[derive(JsonSchema, Serialize)]
+struct ListSessionsResponse {
+    /// The player's sessions, most recent first.
+    sessions: Vec<SessionModel>,
+}
+
+/// Lists a player's sessions across every server.
+#[endpoint(
+    method = GET,
+    path = "/players/{id}/sessions",
+)]
+pub async fn list_sessions(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetPlayerPath>,
+    query: Query<SessionsQuery>,
+) -> Result<HttpResponseOk<ListSessionsResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+    let query = query.into_inner();
+
+    let player = find_player(&rqctx, path.id).await?;
+    let mut find = SessionEntity::find()
+        .filter(session::Column::PlayerId.eq(player.id))
+        .order_by_desc(session::Column::JoinedAt)
+        .limit(query.limit.unwrap_or(100).min(1000));
+    if let Some(server) = query.server {
+        find = find.filter(session::Column::ServerId.eq(server));
+    }
+
+    let sessions = find
+        .all(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    Ok(HttpResponseOk(ListSessionsResponse { sessions }))
+}
+
+#[derive(Default, JsonSchema, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Playtime {
+    /// How many seconds the player has spent on the server, including their
+    /// current session.
+    total_secs: i64,
+    sessions: u64,
+}
+
+#[derive(JsonSchema, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlaytimeResponse {
+    #[serde(flatten)]
+    total: Playtime,
+    /// The player's playtime on each server they have played on.
+    servers: BTreeMap<Uuid, Playtime>,
+}
+
+/// Totals how long a player has played, overall and on each server.
+#[endpoint(
+    method = GET,
+    path = "/players/{id}/playtime",
+)]
+pub async fn get_playtime(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetPlayerPath>,
+) -> Result<HttpResponseOk<PlaytimeResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let player = find_player(&rqctx, path.id).await?;
+    let sessions = SessionEntity::find()
+        .filter(session::Column::PlayerId.eq(player.id))
+        .all(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    let now = Utc::now();
+    let mut response = PlaytimeResponse {
+        total: Playtime::default(),
+        servers: BTreeMap::new(),
+    };
+    for session in sessions {
+        let secs = (session.left_at.unwrap_or(now) - session.joined_at)
+            .num_seconds()
+            .max(0);
+        let server = response.servers.entry(session.server_id).or_default();
+        for playtime in [&mut response.total, server] {
+            playtime.total_secs += secs;
+            playtime.sessions += 1;
+        }
+    }
+
+    Ok(HttpResponseOk(response))
+}
@@ -0,0 +1,281 @@
+use chrono::Utc;
+use dropshot::{endpoint, ClientErrorStatusCode, HttpError, HttpResponseOk, RequestContext};
+use dropshot::{EmptyScanParams, PaginationParams, Path, Query, ResultsPage, TypedBody, WhichPage};
+use harm_entity::config::{Entity as ConfigEntity, Model as ConfigModel};
+use harm_entity::mod_preset::{self, Entity as ModPresetEntity, Model as ModPresetModel};
+use harm_entity::trash::Entity as TrashEntity;
+use harm_schemas::{GameConfig, ModConfig, ModList};
+use schemars::JsonSchema;
+use sea_orm::{prelude::*, QueryOrder, QuerySelect};
+use serde::Deserialize;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{apis::server::check_mods, context::ServerCtx};
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+struct ModPresetPage {
+    pub id: Uuid,
+}
+
+impl From<&ModPresetModel> for ModPresetPage {
+    fn from(value: &ModPresetModel) -> Self {
+        Self { id: value.id }
+    }
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct GetModPresetPath {
+    /// The ID of the preset to fetch data for.
+    id: Uuid,
+}
+
+#[derive(JsonSchema, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModPresetBody {
+    name: String,
+
+    description: Option<String>,
+
+    /// The preset's mods, in load order.
+    mods: Vec<ModConfig>,
+}
+
+impl ModPresetBody {
+    fn check(&self) -> Result<(), HttpError> {
+        if self.name.trim().is_empty() {
+            return Err(HttpError::for_client_error(
+                Some("INVALID_NAME".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                "The preset's name must not be empty.".to_string(),
+            ));
+        }
+        check_mods(&GameConfig {
+            mods: self.mods.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Checks that no other preset has the same name.
+async fn check_name_free(
+    db: &DatabaseConnection,
+    name: &str,
+    except: Option<Uuid>,
+) -> Result<(), HttpError> {
+    let existing = ModPresetEntity::find()
+        .filter(mod_preset::Column::Name.eq(name))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    match existing {
+        Some(existing) if Some(existing.id) != except => Err(HttpError::for_client_error(
+            Some("PRESET_EXISTS".to_string()),
+            ClientErrorStatusCode::CONFLICT,
+            format!("Preset {} already has that name.", existing.id),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[endpoint(
+    method = GET,
+    path = "/presets",
+)]
+pub async fn list_presets(
+    rqctx: RequestContext<ServerCtx>,
+    query: Query<PaginationParams<EmptyScanParams, ModPresetPage>>,
+) -> Result<HttpResponseOk<ResultsPage<ModPresetModel>>, HttpError> {
+    let pag_params = query.into_inner();
+    let limit = rqctx.page_limit(&pag_params)?.get() as u64;
+    let db = &rqctx.context().db;
+
+    let presets = match &pag_params.page {
+        WhichPage::First(..) => ModPresetEntity::find()
+            .limit(limit)
+            .order_by_asc(mod_preset::Column::Id)
+            .all(db)
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string())),
+
+        WhichPage::Next(ModPresetPage { id }) => ModPresetEntity::find()
+            .limit(limit)
+            .filter(mod_preset::Column::Id.gt(*id))
+            .order_by_asc(mod_preset::Column::Id)
+            .all(db)
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string())),
+    }?;
+
+    Ok(HttpResponseOk(ResultsPage::new(
+        presets,
+        &EmptyScanParams {},
+        |p: &ModPresetModel, _| ModPresetPage::from(p),
+    )?))
+}
+
+#[endpoint(
+    method = GET,
+    path = "/presets/{id}",
+)]
+pub async fn get_preset(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetModPresetPath>,
+) -> Result<HttpResponseOk<ModPresetModel>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let preset = ModPresetEntity::find_by_id(path.id)
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(preset) = preset {
+        Ok(HttpResponseOk(preset))
+    } else {
+        Err(HttpError::for_not_found(
+            Some("NO_SUCH_PRESET".to_string()),
+            "No preset with that ID was found.".to_string(),
+        ))
+    }
+}
+
+#[endpoint(
+    method = POST,
+    path = "/presets",
+)]
+pub async fn create_preset(
+    rqctx: RequestContext<ServerCtx>,
+    body: TypedBody<ModPresetBody>,
+) -> Result<HttpResponseOk<ModPresetModel>, HttpError> {
+    let db = &rqctx.context().db;
+    let body = body.into_inner();
+
+    body.check()?;
+    check_name_free(db, &body.name, None).await?;
+
+    let now = Utc::now();
+    let preset = mod_preset::ActiveModel {
+        id: sea_orm::ActiveValue::Set(Uuid::new_v4()),
+        name: sea_orm::ActiveValue::Set(body.name.clone()),
+        description: sea_orm::ActiveValue::Set(body.description.clone()),
+        mods: sea_orm::ActiveValue::Set(ModList(body.mods)),
+        created_at: sea_orm::ActiveValue::Set(now),
+        updated_at: sea_orm::ActiveValue::Set(now),
+    }
+    .insert(db)
+    .await
+    .map_err(|e| HttpError::for_internal_error(format!("failed to insert preset: {}", e)))?;
+
+    Ok(HttpResponseOk(preset))
+}
+
+/// Replaces a preset. Every server linked to it loads the new mods from the
+/// next time it is started.
+#[endpoint(
+    method = PUT,
+    path = "/presets/{id}",
+)]
+pub async fn update_preset(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetModPresetPath>,
+    body: TypedBody<ModPresetBody>,
+) -> Result<HttpResponseOk<ModPresetModel>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+    let body = body.into_inner();
+
+    let existing = ModPresetEntity::find_by_id(path.id)
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(existing) = existing {
+        body.check()?;
+        check_name_free(db, &body.name, Some(existing.id)).await?;
+
+        let preset = mod_preset::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(existing.id),
+            name: sea_orm::ActiveValue::Set(body.name.clone()),
+            description: sea_orm::ActiveValue::Set(body.description.clone()),
+            mods: sea_orm::ActiveValue::Set(ModList(body.mods)),
+            created_at: sea_orm::ActiveValue::Unchanged(existing.created_at),
+            updated_at: sea_orm::ActiveValue::Set(Utc::now()),
+        }
+        .update(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update preset: {}", e)))?;
+
+        return Ok(HttpResponseOk(preset));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_PRESET".to_string()),
+        "No preset with that ID was found.".to_string(),
+    ))
+}
+
+/// Deletes a preset, unless a server is still linked to it. Servers in the
+/// trash count too, so that they can still be restored.
+#[endpoint(
+    method = DELETE,
+    path = "/presets/{id}",
+)]
+pub async fn delete_preset(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetModPresetPath>,
+) -> Result<HttpResponseOk<ModPresetModel>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let preset = ModPresetEntity::find_by_id(path.id)
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(preset) = preset {
+        // Links live in each server's JSON, so they can't be queried for.
+        let mut linked = ConfigEntity::find()
+            .all(db)
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string()))?
+            .into_iter()
+            .filter(|cfg| cfg.mod_presets.presets.contains(&preset.id))
+            .map(|cfg| cfg.id.to_string())
+            .collect::<Vec<_>>();
+        let trashed = TrashEntity::find()
+            .all(db)
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+        for entry in trashed {
+            let cfg: ConfigModel = serde_json::from_value(entry.server).map_err(|e| {
+                HttpError::for_internal_error(format!("failed to read deleted server: {}", e))
+            })?;
+            if cfg.mod_presets.presets.contains(&preset.id) {
+                linked.push(format!("{} (in the trash)", cfg.id));
+            }
+        }
+        if !linked.is_empty() {
+            return Err(HttpError::for_client_error(
+                Some("PRESET_IN_USE".to_string()),
+                ClientErrorStatusCode::CONFLICT,
+                format!("The preset is used by servers {}.", linked.join(", ")),
+            ));
+        }
+
+        ModPresetEntity::delete_by_id(preset.id)
+            .exec(db)
+            .await
+            .map_err(|e| {
+                HttpError::for_internal_error(format!("failed to delete preset: {}", e))
+            })?;
+
+        return Ok(HttpResponseOk(preset));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_PRESET".to_string()),
+        "No preset with that ID was found.".to_string(),
+    ))
+}
@@ -1,14 +1,55 @@
-use dropshot::{endpoint, ClientErrorStatusCode, HttpError, HttpResponseOk, RequestContext};
-use dropshot::{EmptyScanParams, PaginationParams, Path, Query, ResultsPage, TypedBody, WhichPage};
+use std::time::Duration;
+
+use chrono::{TimeDelta, Utc};
+use dropshot::{
+    endpoint, ClientErrorStatusCode, ErrorStatusCode, HttpError, HttpResponseError, HttpResponseOk,
+    RequestContext,
+};
+use dropshot::{
+    EmptyScanParams, PaginationParams, Path, Query, ResultsPage, TypedBody, UntypedBody, WhichPage,
+};
 use harm_entity::config::{self, Entity as ConfigEntity, Model as ConfigModel};
-use harm_schemas::{GameConfig, ModConfig, ServerConfig};
+use harm_entity::mod_preset::{self, Entity as ModPresetEntity};
+use harm_entity::trash::{Entity as TrashEntity, Model as TrashModel};
+use harm_pm::{
+    launch, limits,
+    logs::LogLine,
+    manager::{ServerSpec, ServerState, ServerStatus, StopOptions, StopOutcome, StopSignal},
+    ports::{self, ServerPorts},
+    preflight::{PreflightFailed, PreflightReport},
+    rcon::{self, RconPlayer},
+    readiness,
+    resources::ResourceUsage,
+    runtime, supervisor,
+};
+use harm_schemas::{
+    GameConfig, LaunchOptions, ModConfig, ModPresetLinks, ReadinessConfig, ResourceLimits,
+    RestartPolicy, RuntimeConfig, ServerConfig, Severity, ValidationIssue, ValidationReport,
+};
 use schemars::JsonSchema;
-use sea_orm::{prelude::*, QueryOrder, QuerySelect};
+use sea_orm::{prelude::*, QueryOrder, QuerySelect, TransactionTrait};
 use serde::Deserialize;
 use serde::Serialize;
 use uuid::Uuid;
 
-use crate::context::ServerCtx;
+use crate::{context::ServerCtx, presets, trash};
+
+/// Builds the spec the process manager needs to run a server from its stored
+/// configuration, with the mods from its presets merged in.
+pub async fn server_spec(db: &DatabaseConnection, cfg: &ConfigModel) -> Result<ServerSpec, DbErr> {
+    let presets = presets::linked_presets(db, cfg).await?;
+    let mut config = cfg.config.clone();
+    config.game.mods = presets::effective_mods(cfg, &presets);
+
+    Ok(ServerSpec {
+        config,
+        restart_policy: cfg.restart_policy.clone(),
+        launch_options: cfg.launch_options.clone(),
+        runtime: cfg.runtime.clone(),
+        resource_limits: cfg.resource_limits.clone(),
+        readiness: cfg.readiness.clone(),
+    })
+}
 
 #[derive(Deserialize, Serialize, JsonSchema)]
 struct ListServersResponse {
@@ -109,17 +150,49 @@ pub async fn create_server(
 ) -> Result<HttpResponseOk<ConfigModel>, HttpError> {
     let body = rqbody.into_inner();
     let db = &rqctx.context().db;
+    let pm = &rqctx.context().process_manager;
 
-    let insert = ConfigEntity::insert(config::ActiveModel {
-        id: sea_orm::ActiveValue::Set(Uuid::new_v4()),
-        title: sea_orm::ActiveValue::Set(body.title.clone()),
-        config: sea_orm::ActiveValue::Set(ServerConfig {
-            game: GameConfig {
-                name: body.title,
-                ..Default::default()
-            },
+    let mut config = ServerConfig {
+        game: GameConfig {
+            name: body.title.clone(),
             ..Default::default()
-        }),
+        },
+        ..Default::default()
+    };
+    let _ports = rqctx.context().ports_lock.lock().await;
+    let used: Vec<_> = used_ports(db, None)
+        .await?
+        .into_iter()
+        .map(|(_, ports)| ports)
+        .collect();
+    let ports = rqctx
+        .context()
+        .port_pool
+        .allocate(&config.bind_address, &used)
+        .map_err(|error| {
+            HttpError::for_client_error(
+                Some("NO_FREE_PORTS".to_string()),
+                ClientErrorStatusCode::CONFLICT,
+                error.to_string(),
+            )
+        })?;
+    ports.apply(&mut config);
+
+    let id = Uuid::new_v4();
+    pm.create_data_dir(id).await.map_err(|error| {
+        HttpError::for_internal_error(format!("failed to create data directory: {}", error))
+    })?;
+
+    let insert = ConfigEntity::insert(config::ActiveModel {
+        id: sea_orm::ActiveValue::Set(id),
+        title: sea_orm::ActiveValue::Set(body.title),
+        config: sea_orm::ActiveValue::Set(config),
+        restart_policy: sea_orm::ActiveValue::Set(RestartPolicy::default()),
+        launch_options: sea_orm::ActiveValue::Set(LaunchOptions::default()),
+        runtime: sea_orm::ActiveValue::Set(RuntimeConfig::default()),
+        resource_limits: sea_orm::ActiveValue::Set(ResourceLimits::default()),
+        readiness: sea_orm::ActiveValue::Set(ReadinessConfig::default()),
+        mod_presets: sea_orm::ActiveValue::Set(ModPresetLinks::default()),
     })
     .exec_with_returning(db)
     .await
@@ -132,15 +205,17 @@ pub async fn create_server(
 
 #[endpoint(
     method = POST,
-    path = "/servers/{id}/start"
+    path = "/servers/{id}/clone",
 )]
-pub async fn start_server(
+pub async fn clone_server(
     rqctx: RequestContext<ServerCtx>,
     path: Path<GetServerPath>,
-) -> Result<HttpResponseOk<AddModResponse>, HttpError> {
+    rqbody: TypedBody<CreateServerBody>,
+) -> Result<HttpResponseOk<ConfigModel>, HttpError> {
+    let body = rqbody.into_inner();
     let db = &rqctx.context().db;
-    let path = path.into_inner();
     let pm = &rqctx.context().process_manager;
+    let path = path.into_inner();
 
     let config = ConfigEntity::find()
         .filter(Expr::col(config::Column::Id).eq(path.id))
@@ -149,11 +224,53 @@ pub async fn start_server(
         .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
 
     if let Some(cfg) = config {
-        pm.start_server(cfg.id, cfg.config).await.map_err(|e| {
-            HttpError::for_internal_error(format!("Could not spawn Reforger process: {}", e))
+        let mut config = cfg.config;
+        config.game.name = body.title.clone();
+
+        let _ports = rqctx.context().ports_lock.lock().await;
+        let used: Vec<_> = used_ports(db, None)
+            .await?
+            .into_iter()
+            .map(|(_, ports)| ports)
+            .collect();
+        let ports = rqctx
+            .context()
+            .port_pool
+            .allocate(&config.bind_address, &used)
+            .map_err(|error| {
+                HttpError::for_client_error(
+                    Some("NO_FREE_PORTS".to_string()),
+                    ClientErrorStatusCode::CONFLICT,
+                    error.to_string(),
+                )
+            })?;
+        ports.apply(&mut config);
+
+        let id = Uuid::new_v4();
+        pm.create_data_dir(id).await.map_err(|error| {
+            HttpError::for_internal_error(format!("failed to create data directory: {}", error))
         })?;
 
-        return Ok(HttpResponseOk(AddModResponse { success: true }));
+        // Only the record is cloned; the new server starts with an empty data
+        // directory.
+        let insert = ConfigEntity::insert(config::ActiveModel {
+            id: sea_orm::ActiveValue::Set(id),
+            title: sea_orm::ActiveValue::Set(body.title),
+            config: sea_orm::ActiveValue::Set(config),
+            restart_policy: sea_orm::ActiveValue::Set(cfg.restart_policy),
+            launch_options: sea_orm::ActiveValue::Set(cfg.launch_options),
+            runtime: sea_orm::ActiveValue::Set(cfg.runtime),
+            resource_limits: sea_orm::ActiveValue::Set(cfg.resource_limits),
+            readiness: sea_orm::ActiveValue::Set(cfg.readiness),
+            mod_presets: sea_orm::ActiveValue::Set(cfg.mod_presets),
+        })
+        .exec_with_returning(db)
+        .await
+        .map_err(|error| {
+            HttpError::for_internal_error(format!("failed to insert server: {}", error))
+        })?;
+
+        return Ok(HttpResponseOk(insert));
     }
 
     Err(HttpError::for_not_found(
@@ -162,17 +279,55 @@ pub async fn start_server(
     ))
 }
 
+#[derive(JsonSchema, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeleteServerQuery {
+    /// Stop the server if it is running, instead of refusing to delete it.
+    force: Option<bool>,
+
+    /// Delete the server immediately, instead of moving it to the trash.
+    permanent: Option<bool>,
+
+    /// Move the server's files, including its logs and saves, under
+    /// `<data dir>/archive` instead of deleting them.
+    archive: Option<bool>,
+
+    /// How many seconds the server stays in the trash before it is purged.
+    /// Defaults to 7 days.
+    retention_secs: Option<u64>,
+}
+
+#[derive(JsonSchema, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeleteServerResponse {
+    id: Uuid,
+
+    /// How the server was stopped, if it was running.
+    stopped: Option<StopOutcome>,
+
+    /// The server's trash entry, unless it was deleted permanently.
+    trash: Option<TrashModel>,
+
+    /// Where the server's files were archived to, if they were.
+    archived_to: Option<String>,
+}
+
+/// Deletes a server. By default it is moved to the trash, from which it can
+/// be restored until its retention window passes; its files are only
+/// removed once it is purged.
 #[endpoint(
-    method = POST,
-    path = "/servers/{id}/stop"
+    method = DELETE,
+    path = "/servers/{id}"
 )]
-pub async fn stop_server(
+pub async fn delete_server(
     rqctx: RequestContext<ServerCtx>,
     path: Path<GetServerPath>,
-) -> Result<HttpResponseOk<AddModResponse>, HttpError> {
+    query: Query<DeleteServerQuery>,
+) -> Result<HttpResponseOk<DeleteServerResponse>, HttpError> {
     let db = &rqctx.context().db;
     let pm = &rqctx.context().process_manager;
     let path = path.into_inner();
+    let query = query.into_inner();
 
     let config = ConfigEntity::find()
         .filter(Expr::col(config::Column::Id).eq(path.id))
@@ -181,11 +336,98 @@ pub async fn stop_server(
         .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
 
     if let Some(cfg) = config {
-        pm.stop_server(cfg.id).await.map_err(|e| {
-            HttpError::for_internal_error(format!("Could not stop Reforger process: {}", e))
+        let archive = query.archive.unwrap_or(false);
+        // Checked before anything is stopped, so a bad request changes nothing.
+        let now = Utc::now();
+        let purge_at = match query.retention_secs {
+            Some(secs) => i64::try_from(secs).ok().and_then(TimeDelta::try_seconds),
+            None => TimeDelta::from_std(trash::DEFAULT_RETENTION).ok(),
+        }
+        .and_then(|retention| now.checked_add_signed(retention))
+        .ok_or_else(|| {
+            HttpError::for_client_error(
+                Some("INVALID_RETENTION".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                "retentionSecs is too large.".to_string(),
+            )
         })?;
 
-        return Ok(HttpResponseOk(AddModResponse { success: true }));
+        let mut stopped = None;
+        if pm.status(cfg.id).await.state.is_active() {
+            if !query.force.unwrap_or(false) {
+                return Err(HttpError::for_client_error(
+                    Some("SERVER_RUNNING".to_string()),
+                    ClientErrorStatusCode::CONFLICT,
+                    "The server is running. Stop it first, or delete it with force=true."
+                        .to_string(),
+                ));
+            }
+            let outcome = pm
+                .stop_server(cfg.id, StopOptions::default())
+                .await
+                .map_err(|e| {
+                    HttpError::for_internal_error(format!("Could not stop Reforger process: {}", e))
+                })?;
+            stopped = Some(outcome);
+        }
+        pm.forget(cfg.id).await.map_err(|e| {
+            HttpError::for_client_error(
+                Some("SERVER_RUNNING".to_string()),
+                ClientErrorStatusCode::CONFLICT,
+                e.to_string(),
+            )
+        })?;
+
+        let mut response = DeleteServerResponse {
+            id: cfg.id,
+            stopped,
+            trash: None,
+            archived_to: None,
+        };
+
+        if query.permanent.unwrap_or(false) {
+            // The files go first, so a failure leaves the server to retry.
+            let archived = pm.remove_data_dir(cfg.id, archive).await.map_err(|e| {
+                HttpError::for_internal_error(format!("failed to remove server files: {}", e))
+            })?;
+            ConfigEntity::delete_by_id(cfg.id)
+                .exec(db)
+                .await
+                .map_err(|e| {
+                    HttpError::for_internal_error(format!("failed to delete server: {}", e))
+                })?;
+            response.archived_to = archived.map(|path| path.display().to_string());
+        } else {
+            let server = serde_json::to_value(&cfg)
+                .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+            let txn = db
+                .begin()
+                .await
+                .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+            let entry = TrashEntity::insert(harm_entity::trash::ActiveModel {
+                id: sea_orm::ActiveValue::Set(cfg.id),
+                title: sea_orm::ActiveValue::Set(cfg.title.clone()),
+                server: sea_orm::ActiveValue::Set(server),
+                archive: sea_orm::ActiveValue::Set(archive),
+                deleted_at: sea_orm::ActiveValue::Set(now),
+                purge_at: sea_orm::ActiveValue::Set(purge_at),
+            })
+            .exec_with_returning(&txn)
+            .await
+            .map_err(|e| HttpError::for_internal_error(format!("failed to trash server: {}", e)))?;
+            ConfigEntity::delete_by_id(cfg.id)
+                .exec(&txn)
+                .await
+                .map_err(|e| {
+                    HttpError::for_internal_error(format!("failed to delete server: {}", e))
+                })?;
+            txn.commit()
+                .await
+                .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+            response.trash = Some(entry);
+        }
+
+        return Ok(HttpResponseOk(response));
     }
 
     Err(HttpError::for_not_found(
@@ -194,29 +436,269 @@ pub async fn stop_server(
     ))
 }
 
-#[derive(JsonSchema, Deserialize, Serialize)]
-struct AddModResponse {
-    success: bool,
+/// Returns the ports of every server, other than `except` if it is given.
+/// Servers in the trash keep their ports, so that they can be restored.
+async fn used_ports(
+    db: &DatabaseConnection,
+    except: Option<Uuid>,
+) -> Result<Vec<(Uuid, ServerPorts)>, HttpError> {
+    let configs = ConfigEntity::find()
+        .all(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+    let trashed = TrashEntity::find()
+        .all(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    let mut used: Vec<_> = configs
+        .iter()
+        .map(|cfg| (cfg.id, ServerPorts::of(&cfg.config)))
+        .collect();
+    for entry in trashed {
+        let cfg: ConfigModel = serde_json::from_value(entry.server).map_err(|e| {
+            HttpError::for_internal_error(format!("failed to read deleted server: {}", e))
+        })?;
+        used.push((cfg.id, ServerPorts::of(&cfg.config)));
+    }
+    used.retain(|(id, _)| Some(*id) != except);
+
+    Ok(used)
 }
 
-#[derive(JsonSchema, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-struct AddModRequest {
-    mod_id: String,
-    name: Option<String>,
+/// Rejects `ports` if another server already listens on any of them.
+pub(crate) async fn check_ports(
+    db: &DatabaseConnection,
+    id: Uuid,
+    ports: &ServerPorts,
+) -> Result<(), HttpError> {
+    let others = used_ports(db, Some(id)).await?;
+    let conflicts = ports::conflicts(ports, &others);
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+
+    let message = conflicts
+        .iter()
+        .map(|conflict| match conflict.server_id {
+            Some(other) => format!(
+                "{} port {} is used by server {}",
+                conflict.field, conflict.port, other
+            ),
+            None => format!("{} port {} is used twice", conflict.field, conflict.port),
+        })
+        .collect::<Vec<_>>()
+        .join("; ");
+    Err(HttpError::for_client_error(
+        Some("PORT_CONFLICT".to_string()),
+        ClientErrorStatusCode::CONFLICT,
+        message,
+    ))
+}
+
+/// The error returned when a server's config can't be saved. An invalid
+/// config is answered with 422 and every problem found in it, so that
+/// clients can show each one next to its field.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ConfigError {
+    Invalid {
+        /// Always `INVALID_CONFIG`.
+        error_code: String,
+        message: String,
+        /// Every error and warning found, by field.
+        issues: Vec<ValidationIssue>,
+    },
+    Other {
+        error_code: Option<String>,
+        message: String,
+        #[serde(skip)]
+        internal_message: String,
+        #[serde(skip)]
+        status: ErrorStatusCode,
+    },
+}
+
+impl ConfigError {
+    fn invalid(issues: Vec<ValidationIssue>) -> Self {
+        Self::Invalid {
+            error_code: "INVALID_CONFIG".to_string(),
+            message: "The config is invalid.".to_string(),
+            issues,
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid { issues, .. } => {
+                let errors = issues
+                    .iter()
+                    .filter(|issue| issue.severity == Severity::Error)
+                    .map(|issue| format!("{}: {}", issue.pointer, issue.message))
+                    .collect::<Vec<_>>();
+                write!(f, "The config is invalid: {}", errors.join("; "))
+            }
+            Self::Other {
+                internal_message, ..
+            } => f.write_str(internal_message),
+        }
+    }
+}
+
+impl From<HttpError> for ConfigError {
+    fn from(error: HttpError) -> Self {
+        Self::Other {
+            error_code: error.error_code,
+            message: error.external_message,
+            internal_message: error.internal_message,
+            status: error.status_code,
+        }
+    }
+}
+
+impl HttpResponseError for ConfigError {
+    fn status_code(&self) -> ErrorStatusCode {
+        match self {
+            Self::Invalid { .. } => ErrorStatusCode::UNPROCESSABLE_ENTITY,
+            Self::Other { status, .. } => *status,
+        }
+    }
+}
+
+/// Validates a config for server `id`, also reporting any of its ports which
+/// another server already listens on.
+async fn config_report(
+    db: &DatabaseConnection,
+    id: Uuid,
+    config: &ServerConfig,
+) -> Result<ValidationReport, HttpError> {
+    let mut report = config.validate();
+
+    let others = used_ports(db, Some(id)).await?;
+    for conflict in ports::conflicts(&ServerPorts::of(config), &others) {
+        let Some(other) = conflict.server_id else {
+            // Already reported by the config's own validation.
+            continue;
+        };
+        let pointer = match conflict.field.as_str() {
+            "a2s" => "/a2s/port",
+            "rcon" => "/rcon/port",
+            _ if config.bind_port.is_some() => "/bindPort",
+            _ => "/publicPort",
+        };
+        report.issues.push(ValidationIssue {
+            pointer: pointer.to_string(),
+            severity: Severity::Error,
+            message: format!("port {} is used by server {}", conflict.port, other),
+        });
+    }
+
+    Ok(report)
+}
+
+/// Rejects a config for server `id` with every invalid field, including any
+/// port another server already listens on. Warnings don't stop a config
+/// from being saved.
+async fn check_config(
+    db: &DatabaseConnection,
+    id: Uuid,
+    config: &ServerConfig,
+) -> Result<(), ConfigError> {
+    let report = config_report(db, id, config).await?;
+    if !report.is_valid() {
+        return Err(ConfigError::invalid(report.issues));
+    }
+    Ok(())
+}
+
+/// Escapes a key for use in a JSON pointer (RFC 6901).
+fn escape_pointer_key(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Finds the keys a merge patch removes which a `T`, e.g. a server config,
+/// can't do without. Each key is removed from the `current` value on its
+/// own, so that fields which are optional or have a default can still be
+/// removed.
+fn removed_required_keys<T: serde::de::DeserializeOwned>(
+    current: &serde_json::Value,
+    patch: &serde_json::Value,
+) -> Vec<ValidationIssue> {
+    let mut removed = Vec::new();
+    removed_keys(patch, String::new(), &mut removed);
+
+    removed
+        .into_iter()
+        .filter(|(parent, key)| {
+            let mut probe = current.clone();
+            let removed = match probe.pointer_mut(parent) {
+                Some(serde_json::Value::Object(object)) => object.remove(key).is_some(),
+                _ => false,
+            };
+            removed && serde_json::from_value::<T>(probe).is_err()
+        })
+        .map(|(parent, key)| ValidationIssue {
+            pointer: format!("{}/{}", parent, escape_pointer_key(&key)),
+            severity: Severity::Error,
+            message: "is required, so it can't be removed".to_string(),
+        })
+        .collect()
+}
+
+/// Collects the keys a merge patch sets to `null`, as the JSON pointer of
+/// the object holding each one and the key itself.
+fn removed_keys(patch: &serde_json::Value, pointer: String, removed: &mut Vec<(String, String)>) {
+    let serde_json::Value::Object(patch) = patch else {
+        return;
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            removed.push((pointer.clone(), key.clone()));
+        } else {
+            let child = format!("{}/{}", pointer, escape_pointer_key(key));
+            removed_keys(value, child, removed);
+        }
+    }
+}
+
+/// Applies a JSON Merge Patch (RFC 7396) to `target`: objects are merged
+/// key by key, `null` removes a key, and anything else replaces the value.
+fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let serde_json::Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+    let Some(target) = target.as_object_mut() else {
+        return;
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(
+                target.entry(key.clone()).or_insert(serde_json::Value::Null),
+                value,
+            );
+        }
+    }
 }
 
 #[endpoint(
     method = POST,
-    path = "/servers/{id}/mods"
+    path = "/servers/{id}/start"
 )]
-pub async fn add_mod(
+pub async fn start_server(
     rqctx: RequestContext<ServerCtx>,
     path: Path<GetServerPath>,
-    body: TypedBody<AddModRequest>,
 ) -> Result<HttpResponseOk<AddModResponse>, HttpError> {
     let db = &rqctx.context().db;
     let path = path.into_inner();
+    let pm = &rqctx.context().process_manager;
 
     let config = ConfigEntity::find()
         .filter(Expr::col(config::Column::Id).eq(path.id))
@@ -224,42 +706,58 @@ pub async fn add_mod(
         .await
         .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
 
-    if let Some(mut cfg) = config {
-        let mod_body = body.into_inner();
+    if let Some(cfg) = config {
+        let spec = server_spec(db, &cfg)
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+        pm.start_server(cfg.id, spec).await.map_err(|e| {
+            match e.downcast_ref::<PreflightFailed>() {
+                Some(failed) => HttpError::for_client_error(
+                    Some("PREFLIGHT_FAILED".to_string()),
+                    ClientErrorStatusCode::CONFLICT,
+                    failed.to_string(),
+                ),
+                None => HttpError::for_internal_error(format!(
+                    "Could not spawn Reforger process: {}",
+                    e
+                )),
+            }
+        })?;
 
-        let existing_ids: Vec<String> = cfg
-            .config
-            .game
-            .mods
-            .iter()
-            .map(|e| e.mod_id.clone())
-            .collect();
-        if existing_ids.contains(&mod_body.mod_id.clone()) {
-            return Err(HttpError::for_client_error(
-                Some("MOD_ALREADY_ADDED".to_string()),
-                ClientErrorStatusCode::BAD_REQUEST,
-                "A mod with that ID already exists on this server's configuration!".to_string(),
-            ));
-        }
+        return Ok(HttpResponseOk(AddModResponse { success: true }));
+    }
 
-        let mod_block = ModConfig {
-            mod_id: mod_body.mod_id,
-            name: mod_body.name.unwrap_or_default(),
-            required: true,
-        };
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
 
-        cfg.config.game.mods.push(mod_block);
+/// Runs the checks done before a server is started, without starting it.
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/preflight"
+)]
+pub async fn get_preflight(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+) -> Result<HttpResponseOk<PreflightReport>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+    let pm = &rqctx.context().process_manager;
 
-        ConfigEntity::update(config::ActiveModel {
-            id: sea_orm::ActiveValue::Unchanged(cfg.id),
-            title: sea_orm::ActiveValue::Unchanged(cfg.title),
-            config: sea_orm::ActiveValue::Set(cfg.config),
-        })
-        .exec(db)
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
         .await
-        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
 
-        return Ok(HttpResponseOk(AddModResponse { success: true }));
+    if let Some(cfg) = config {
+        let spec = server_spec(db, &cfg)
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+        let report = pm.preflight(cfg.id, &spec).await;
+        return Ok(HttpResponseOk(report));
     }
 
     Err(HttpError::for_not_found(
@@ -268,21 +766,32 @@ pub async fn add_mod(
     ))
 }
 
-#[derive(JsonSchema, Deserialize, Serialize)]
-struct ListModsResponse {
-    mods: Vec<ModConfig>,
+#[derive(JsonSchema, Deserialize)]
+struct StopServerQuery {
+    /// How many seconds the server has to shut down cleanly before it is
+    /// killed. Defaults to 30.
+    timeout: Option<u64>,
+
+    /// Kill the server immediately instead of asking it to shut down.
+    force: Option<bool>,
+
+    /// The signal used to ask the server to shut down. Defaults to TERM.
+    signal: Option<StopSignal>,
 }
 
 #[endpoint(
-    method = GET,
-    path = "/servers/{id}/mods"
+    method = POST,
+    path = "/servers/{id}/stop"
 )]
-pub async fn list_mods(
+pub async fn stop_server(
     rqctx: RequestContext<ServerCtx>,
     path: Path<GetServerPath>,
-) -> Result<HttpResponseOk<ListModsResponse>, HttpError> {
+    query: Query<StopServerQuery>,
+) -> Result<HttpResponseOk<StopOutcome>, HttpError> {
     let db = &rqctx.context().db;
+    let pm = &rqctx.context().process_manager;
     let path = path.into_inner();
+    let query = query.into_inner();
 
     let config = ConfigEntity::find()
         .filter(Expr::col(config::Column::Id).eq(path.id))
@@ -291,9 +800,21 @@ pub async fn list_mods(
         .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
 
     if let Some(cfg) = config {
-        return Ok(HttpResponseOk(ListModsResponse {
-            mods: cfg.config.game.mods,
-        }));
+        let defaults = StopOptions::default();
+        let options = StopOptions {
+            signal: query.signal.unwrap_or(defaults.signal),
+            grace_period: query
+                .timeout
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.grace_period),
+            force: query.force.unwrap_or(defaults.force),
+        };
+
+        let outcome = pm.stop_server(cfg.id, options).await.map_err(|e| {
+            HttpError::for_internal_error(format!("Could not stop Reforger process: {}", e))
+        })?;
+
+        return Ok(HttpResponseOk(outcome));
     }
 
     Err(HttpError::for_not_found(
@@ -302,23 +823,15 @@ pub async fn list_mods(
     ))
 }
 
-#[derive(JsonSchema, Deserialize)]
-struct ModPath {
-    /// The ID of the server to fetch data for.
-    id: Uuid,
-
-    /// The ID of the mod to fetch data for.
-    mod_id: String,
-}
-
 #[endpoint(
-    method = DELETE,
-    path = "/servers/{id}/mods/{mod_id}"
+    method = PUT,
+    path = "/servers/{id}/restart-policy"
 )]
-pub async fn delete_mod(
+pub async fn update_restart_policy(
     rqctx: RequestContext<ServerCtx>,
-    path: Path<ModPath>,
-) -> Result<HttpResponseOk<AddModResponse>, HttpError> {
+    path: Path<GetServerPath>,
+    body: TypedBody<RestartPolicy>,
+) -> Result<HttpResponseOk<RestartPolicy>, HttpError> {
     let db = &rqctx.context().db;
     let path = path.into_inner();
 
@@ -328,41 +841,54 @@ pub async fn delete_mod(
         .await
         .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
 
-    if let Some(mut cfg) = config {
-        let existing_ids: Vec<String> = cfg
-            .config
-            .game
-            .mods
-            .iter()
-            .map(|e| e.mod_id.clone())
-            .collect();
-        if !existing_ids.contains(&path.mod_id.clone()) {
-            return Err(HttpError::for_client_error(
-                Some("MOD_NOT_ADDED".to_string()),
+    if let Some(cfg) = config {
+        let policy = body.into_inner();
+        supervisor::check(&policy).map_err(|message| {
+            HttpError::for_client_error(
+                Some("INVALID_RESTART_POLICY".to_string()),
                 ClientErrorStatusCode::BAD_REQUEST,
-                "No mod with that ID exists on this server's configuration!".to_string(),
-            ));
-        }
-
-        let idx = cfg
-            .config
-            .game
-            .mods
-            .iter()
-            .position(|x| x.mod_id == path.mod_id.clone())
-            .unwrap();
-        cfg.config.game.mods.remove(idx);
+                message,
+            )
+        })?;
 
         ConfigEntity::update(config::ActiveModel {
             id: sea_orm::ActiveValue::Unchanged(cfg.id),
-            title: sea_orm::ActiveValue::Unchanged(cfg.title),
-            config: sea_orm::ActiveValue::Set(cfg.config),
+            restart_policy: sea_orm::ActiveValue::Set(policy.clone()),
+            ..Default::default()
         })
         .exec(db)
         .await
         .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
 
-        return Ok(HttpResponseOk(AddModResponse { success: true }));
+        // The new policy applies from the next time the server is started.
+        return Ok(HttpResponseOk(policy));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/launch-options"
+)]
+pub async fn get_launch_options(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+) -> Result<HttpResponseOk<LaunchOptions>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        return Ok(HttpResponseOk(cfg.launch_options));
     }
 
     Err(HttpError::for_not_found(
@@ -370,3 +896,1704 @@ pub async fn delete_mod(
         "No server with that ID was found.".to_string(),
     ))
 }
+
+#[endpoint(
+    method = PUT,
+    path = "/servers/{id}/launch-options"
+)]
+pub async fn update_launch_options(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<LaunchOptions>,
+) -> Result<HttpResponseOk<LaunchOptions>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let options = body.into_inner();
+        launch::check(&options).map_err(|message| {
+            HttpError::for_client_error(
+                Some("INVALID_LAUNCH_OPTIONS".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                message,
+            )
+        })?;
+
+        ConfigEntity::update(config::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(cfg.id),
+            launch_options: sea_orm::ActiveValue::Set(options.clone()),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+
+        // The new options apply from the next time the server is started.
+        return Ok(HttpResponseOk(options));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/runtime"
+)]
+pub async fn get_runtime(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+) -> Result<HttpResponseOk<RuntimeConfig>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        return Ok(HttpResponseOk(cfg.runtime));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[endpoint(
+    method = PUT,
+    path = "/servers/{id}/runtime"
+)]
+pub async fn update_runtime(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<RuntimeConfig>,
+) -> Result<HttpResponseOk<RuntimeConfig>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let runtime = body.into_inner();
+        runtime::check(&runtime)
+            .and_then(|_| limits::check(&cfg.resource_limits, runtime.kind))
+            .map_err(|message| {
+                HttpError::for_client_error(
+                    Some("INVALID_RUNTIME".to_string()),
+                    ClientErrorStatusCode::BAD_REQUEST,
+                    message,
+                )
+            })?;
+
+        ConfigEntity::update(config::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(cfg.id),
+            runtime: sea_orm::ActiveValue::Set(runtime.clone()),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+
+        // A running server keeps its current runtime until it is restarted.
+        return Ok(HttpResponseOk(runtime));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/resource-limits"
+)]
+pub async fn get_resource_limits(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+) -> Result<HttpResponseOk<ResourceLimits>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        return Ok(HttpResponseOk(cfg.resource_limits));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[endpoint(
+    method = PUT,
+    path = "/servers/{id}/resource-limits"
+)]
+pub async fn update_resource_limits(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<ResourceLimits>,
+) -> Result<HttpResponseOk<ResourceLimits>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let resource_limits = body.into_inner();
+        limits::check(&resource_limits, cfg.runtime.kind).map_err(|message| {
+            HttpError::for_client_error(
+                Some("INVALID_RESOURCE_LIMITS".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                message,
+            )
+        })?;
+
+        ConfigEntity::update(config::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(cfg.id),
+            resource_limits: sea_orm::ActiveValue::Set(resource_limits.clone()),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+
+        // The new limits apply from the next time the server is started.
+        return Ok(HttpResponseOk(resource_limits));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/readiness"
+)]
+pub async fn get_readiness(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+) -> Result<HttpResponseOk<ReadinessConfig>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        return Ok(HttpResponseOk(cfg.readiness));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[endpoint(
+    method = PUT,
+    path = "/servers/{id}/readiness"
+)]
+pub async fn update_readiness(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<ReadinessConfig>,
+) -> Result<HttpResponseOk<ReadinessConfig>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let readiness = body.into_inner();
+        readiness::check(&readiness).map_err(|message| {
+            HttpError::for_client_error(
+                Some("INVALID_READINESS".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                message,
+            )
+        })?;
+
+        ConfigEntity::update(config::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(cfg.id),
+            readiness: sea_orm::ActiveValue::Set(readiness.clone()),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+
+        // The new probes apply from the next time the server is started.
+        return Ok(HttpResponseOk(readiness));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct WaitReadyQuery {
+    /// How many seconds to wait for the server to become ready. Defaults to
+    /// 30, and is capped at 300.
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct WaitReadyResponse {
+    /// Whether the server is ready.
+    ready: bool,
+    status: ServerStatus,
+}
+
+/// Waits for a starting server to become ready. Returns as soon as the
+/// server is ready, is no longer starting, or the timeout passes.
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/ready"
+)]
+pub async fn wait_ready(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    query: Query<WaitReadyQuery>,
+) -> Result<HttpResponseOk<WaitReadyResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let pm = &rqctx.context().process_manager;
+    let path = path.into_inner();
+    let query = query.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let timeout = Duration::from_secs(query.timeout.unwrap_or(30).min(300));
+        let status = pm.wait_ready(cfg.id, timeout).await;
+        return Ok(HttpResponseOk(WaitReadyResponse {
+            ready: status.state == ServerState::Ready,
+            status,
+        }));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/ports"
+)]
+pub async fn get_ports(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+) -> Result<HttpResponseOk<ServerPorts>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        return Ok(HttpResponseOk(ServerPorts::of(&cfg.config)));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[endpoint(
+    method = PUT,
+    path = "/servers/{id}/ports"
+)]
+pub async fn update_ports(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<ServerPorts>,
+) -> Result<HttpResponseOk<ServerPorts>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(mut cfg) = config {
+        let ports = body.into_inner();
+        let _ports = rqctx.context().ports_lock.lock().await;
+        check_ports(db, cfg.id, &ports).await?;
+        ports.apply(&mut cfg.config);
+
+        ConfigEntity::update(config::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(cfg.id),
+            config: sea_orm::ActiveValue::Set(cfg.config),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+
+        // The new ports apply from the next time the server is started.
+        return Ok(HttpResponseOk(ports));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/status"
+)]
+pub async fn get_status(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+) -> Result<HttpResponseOk<ServerStatus>, HttpError> {
+    let db = &rqctx.context().db;
+    let pm = &rqctx.context().process_manager;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        return Ok(HttpResponseOk(pm.status(cfg.id).await));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct GetLogsQuery {
+    /// The maximum number of lines to return. Defaults to 100.
+    lines: Option<usize>,
+
+    /// Only return lines with a sequence number greater than this one. Pass
+    /// the `seq` of the last line received to follow a server's output.
+    after: Option<u64>,
+}
+
+#[derive(JsonSchema, Deserialize, Serialize)]
+struct GetLogsResponse {
+    lines: Vec<LogLine>,
+}
+
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/logs"
+)]
+pub async fn get_logs(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    query: Query<GetLogsQuery>,
+) -> Result<HttpResponseOk<GetLogsResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let pm = &rqctx.context().process_manager;
+    let path = path.into_inner();
+    let query = query.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let limit = query.lines.unwrap_or(100);
+        let logs = match query.after {
+            Some(seq) => pm.get_logs_since(cfg.id, seq, limit).await,
+            None => pm.get_logs(cfg.id, limit).await,
+        };
+
+        // A server which has never been started has no captured output yet.
+        return Ok(HttpResponseOk(GetLogsResponse {
+            lines: logs.unwrap_or_default(),
+        }));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct GetResourcesQuery {
+    /// The maximum number of historical samples to return. Defaults to 60.
+    samples: Option<usize>,
+}
+
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/resources"
+)]
+pub async fn get_resources(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    query: Query<GetResourcesQuery>,
+) -> Result<HttpResponseOk<ResourceUsage>, HttpError> {
+    let db = &rqctx.context().db;
+    let pm = &rqctx.context().process_manager;
+    let path = path.into_inner();
+    let query = query.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let usage = pm
+            .resources(cfg.id, query.samples.unwrap_or(60))
+            .await
+            // A server which has never been started has not been sampled.
+            .unwrap_or(ResourceUsage {
+                current: None,
+                history: Vec::new(),
+            });
+        return Ok(HttpResponseOk(usage));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[derive(JsonSchema, Deserialize, Serialize)]
+struct AddModResponse {
+    success: bool,
+}
+
+#[derive(JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AddModRequest {
+    mod_id: String,
+    name: Option<String>,
+
+    /// The version to pin the mod to. The latest version is loaded if unset.
+    version: Option<String>,
+
+    /// Whether players must have the mod to join. Defaults to the server's
+    /// `modsRequiredByDefault`.
+    required: Option<bool>,
+}
+
+impl AddModRequest {
+    fn into_mod(self, game: &GameConfig) -> ModConfig {
+        ModConfig {
+            mod_id: self.mod_id.trim().to_string(),
+            name: self.name.unwrap_or_default(),
+            version: self.version,
+            required: self.required.unwrap_or(game.mods_required_by_default),
+        }
+    }
+}
+
+/// Rejects a server's mod list if any mod in it is invalid or listed twice.
+pub(crate) fn check_mods(game: &GameConfig) -> Result<(), HttpError> {
+    let config = ServerConfig {
+        game: game.clone(),
+        ..Default::default()
+    };
+    let report = config.validate();
+    let errors = report
+        .errors()
+        .filter(|error| error.pointer.starts_with("/game/mods/"))
+        .map(|error| format!("{}: {}", error.pointer, error.message))
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    Err(HttpError::for_client_error(
+        Some("INVALID_MODS".to_string()),
+        ClientErrorStatusCode::BAD_REQUEST,
+        format!("The mod list is invalid: {}", errors.join("; ")),
+    ))
+}
+
+#[endpoint(
+    method = POST,
+    path = "/servers/{id}/mods"
+)]
+pub async fn add_mod(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<AddModRequest>,
+) -> Result<HttpResponseOk<AddModResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(mut cfg) = config {
+        let mod_body = body.into_inner();
+
+        if cfg.config.game.mod_position(&mod_body.mod_id).is_some() {
+            return Err(HttpError::for_client_error(
+                Some("MOD_ALREADY_ADDED".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                "A mod with that ID already exists on this server's configuration!".to_string(),
+            ));
+        }
+
+        let mod_block = mod_body.into_mod(&cfg.config.game);
+        cfg.config.game.mods.push(mod_block);
+        check_mods(&cfg.config.game)?;
+
+        ConfigEntity::update(config::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(cfg.id),
+            config: sea_orm::ActiveValue::Set(cfg.config),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+
+        return Ok(HttpResponseOk(AddModResponse { success: true }));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[derive(JsonSchema, Deserialize, Serialize)]
+struct ListModsResponse {
+    mods: Vec<ModConfig>,
+}
+
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/mods"
+)]
+pub async fn list_mods(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+) -> Result<HttpResponseOk<ListModsResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        return Ok(HttpResponseOk(ListModsResponse {
+            mods: cfg.config.game.mods,
+        }));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[derive(JsonSchema, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReplaceModsRequest {
+    /// Every mod, in load order.
+    mods: Vec<AddModRequest>,
+}
+
+/// Replaces a server's whole mod list at once. Nothing is saved if any mod
+/// in the new list is invalid or listed twice.
+#[endpoint(
+    method = PUT,
+    path = "/servers/{id}/mods"
+)]
+pub async fn replace_mods(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<ReplaceModsRequest>,
+) -> Result<HttpResponseOk<ListModsResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(mut cfg) = config {
+        let game = &cfg.config.game;
+        let mods = body
+            .into_inner()
+            .mods
+            .into_iter()
+            .map(|m| m.into_mod(game))
+            .collect::<Vec<_>>();
+        cfg.config.game.mods = mods.clone();
+        check_mods(&cfg.config.game)?;
+
+        ConfigEntity::update(config::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(cfg.id),
+            config: sea_orm::ActiveValue::Set(cfg.config),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+
+        return Ok(HttpResponseOk(ListModsResponse { mods }));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[derive(JsonSchema, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModOrderRequest {
+    /// The ID of every mod on the server, in the order they should load.
+    mod_ids: Vec<String>,
+}
+
+/// Changes the order a server's mods are loaded in.
+#[endpoint(
+    method = PUT,
+    path = "/servers/{id}/mod-order"
+)]
+pub async fn reorder_mods(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<ModOrderRequest>,
+) -> Result<HttpResponseOk<ListModsResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(mut cfg) = config {
+        let mod_ids = body.into_inner().mod_ids;
+        let game = &cfg.config.game;
+
+        let mut positions = mod_ids
+            .iter()
+            .filter_map(|mod_id| game.mod_position(mod_id))
+            .collect::<Vec<_>>();
+        positions.sort_unstable();
+        positions.dedup();
+        if mod_ids.len() != game.mods.len() || positions.len() != game.mods.len() {
+            return Err(HttpError::for_client_error(
+                Some("INVALID_ORDER".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                "modIds must list every mod on the server exactly once.".to_string(),
+            ));
+        }
+
+        let mods = mod_ids
+            .iter()
+            .filter_map(|mod_id| game.mod_position(mod_id))
+            .map(|i| game.mods[i].clone())
+            .collect::<Vec<_>>();
+        cfg.config.game.mods = mods.clone();
+
+        ConfigEntity::update(config::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(cfg.id),
+            config: sea_orm::ActiveValue::Set(cfg.config),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+
+        return Ok(HttpResponseOk(ListModsResponse { mods }));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct ModPath {
+    /// The ID of the server to fetch data for.
+    id: Uuid,
+
+    /// The ID of the mod to fetch data for.
+    mod_id: String,
+}
+
+#[derive(JsonSchema, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateModRequest {
+    /// The mod's new name. The name is kept if unset.
+    name: Option<String>,
+
+    /// The version to pin the mod to. The latest version is loaded if unset.
+    version: Option<String>,
+
+    /// Whether players must have the mod to join. The flag is kept if unset.
+    required: Option<bool>,
+}
+
+/// Updates a mod's name, version and required flag. The name and flag are
+/// only changed if given, and the mod keeps its place in the load order.
+#[endpoint(
+    method = PUT,
+    path = "/servers/{id}/mods/{mod_id}"
+)]
+pub async fn update_mod(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<ModPath>,
+    body: TypedBody<UpdateModRequest>,
+) -> Result<HttpResponseOk<ModConfig>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(mut cfg) = config {
+        let Some(idx) = cfg.config.game.mod_position(&path.mod_id) else {
+            return Err(HttpError::for_client_error(
+                Some("MOD_NOT_ADDED".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                "No mod with that ID exists on this server's configuration!".to_string(),
+            ));
+        };
+
+        let body = body.into_inner();
+        let mod_block = &mut cfg.config.game.mods[idx];
+        if let Some(name) = body.name {
+            mod_block.name = name;
+        }
+        mod_block.version = body.version;
+        if let Some(required) = body.required {
+            mod_block.required = required;
+        }
+        let mod_block = mod_block.clone();
+        check_mods(&cfg.config.game)?;
+
+        ConfigEntity::update(config::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(cfg.id),
+            config: sea_orm::ActiveValue::Set(cfg.config),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+
+        return Ok(HttpResponseOk(mod_block));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[endpoint(
+    method = DELETE,
+    path = "/servers/{id}/mods/{mod_id}"
+)]
+pub async fn delete_mod(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<ModPath>,
+) -> Result<HttpResponseOk<AddModResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(mut cfg) = config {
+        let Some(idx) = cfg.config.game.mod_position(&path.mod_id) else {
+            return Err(HttpError::for_client_error(
+                Some("MOD_NOT_ADDED".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                "No mod with that ID exists on this server's configuration!".to_string(),
+            ));
+        };
+        cfg.config.game.mods.remove(idx);
+
+        ConfigEntity::update(config::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(cfg.id),
+            config: sea_orm::ActiveValue::Set(cfg.config),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+
+        return Ok(HttpResponseOk(AddModResponse { success: true }));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/mod-presets"
+)]
+pub async fn get_mod_presets(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+) -> Result<HttpResponseOk<ModPresetLinks>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        return Ok(HttpResponseOk(cfg.mod_presets));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+/// Sets the presets a server loads mods from, and which of their mods it
+/// leaves out; only mods in one of the presets can be left out. The
+/// server's own mods load after the presets'.
+#[endpoint(
+    method = PUT,
+    path = "/servers/{id}/mod-presets"
+)]
+pub async fn update_mod_presets(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<ModPresetLinks>,
+) -> Result<HttpResponseOk<ModPresetLinks>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let links = body.into_inner();
+
+        let found = ModPresetEntity::find()
+            .filter(mod_preset::Column::Id.is_in(links.presets.clone()))
+            .all(db)
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+        if let Some(missing) = links
+            .presets
+            .iter()
+            .find(|id| !found.iter().any(|preset| preset.id == **id))
+        {
+            return Err(HttpError::for_client_error(
+                Some("NO_SUCH_PRESET".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                format!("No preset with ID {} was found.", missing),
+            ));
+        }
+        if let Some(unknown) = links.excluded_mods.iter().find(|mod_id| {
+            !found
+                .iter()
+                .any(|preset| preset.mods.0.iter().any(|m| m.is(mod_id)))
+        }) {
+            return Err(HttpError::for_client_error(
+                Some("MOD_NOT_IN_PRESETS".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                format!("Mod {} isn't in any of the server's presets.", unknown),
+            ));
+        }
+
+        ConfigEntity::update(config::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(cfg.id),
+            mod_presets: sea_orm::ActiveValue::Set(links.clone()),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+
+        return Ok(HttpResponseOk(links));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+/// Lists the mods a server loads once its presets, exclusions and own mods
+/// are combined.
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/effective-mods"
+)]
+pub async fn get_effective_mods(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+) -> Result<HttpResponseOk<ListModsResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let presets = presets::linked_presets(db, &cfg)
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+        return Ok(HttpResponseOk(ListModsResponse {
+            mods: presets::effective_mods(&cfg, &presets),
+        }));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+/// Runs an RCON command on a running server. The command may only be run if
+/// the permission in the server's RCON config allows it, as that is the
+/// permission HARM logs in with: commands which change the server's state
+/// need admin.
+async fn run_rcon(
+    rqctx: &RequestContext<ServerCtx>,
+    cfg: &ConfigModel,
+    command: &str,
+) -> Result<RconCommandResponse, HttpError> {
+    let required = rcon::required_permission(command);
+    if !rcon::permits(&cfg.config.rcon.permission, &required) {
+        return Err(HttpError::for_client_error(
+            Some("RCON_FORBIDDEN".to_string()),
+            ClientErrorStatusCode::FORBIDDEN,
+            "That command needs the admin RCON permission, but this server's RCON is configured as a monitor.".to_string(),
+        ));
+    }
+
+    let response = rqctx
+        .context()
+        .process_manager
+        .rcon_command(cfg.id, command)
+        .await
+        .map_err(|e| {
+            HttpError::for_client_error(
+                Some("RCON_UNAVAILABLE".to_string()),
+                ClientErrorStatusCode::CONFLICT,
+                e.to_string(),
+            )
+        })?;
+
+    Ok(RconCommandResponse {
+        command: command.to_string(),
+        lines: response
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect(),
+        response,
+    })
+}
+
+/// Checks that a player given to a ban command is a single word, e.g. a
+/// session ID or an identity ID.
+fn check_player(player: &str) -> Result<(), HttpError> {
+    if player.is_empty() || player.contains(char::is_whitespace) {
+        return Err(HttpError::for_client_error(
+            Some("INVALID_PLAYER".to_string()),
+            ClientErrorStatusCode::BAD_REQUEST,
+            "The player must be a session ID or an identity ID.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct RconCommandBody {
+    /// The command to run, e.g. `#players`. Commands which change the
+    /// server's state are only allowed if its RCON permission is admin.
+    command: String,
+}
+
+#[derive(JsonSchema, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RconCommandResponse {
+    command: String,
+    /// The server's answer, as sent.
+    response: String,
+    /// The non-empty lines of the server's answer.
+    lines: Vec<String>,
+}
+
+#[endpoint(
+    method = POST,
+    path = "/servers/{id}/rcon"
+)]
+pub async fn run_rcon_command(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<RconCommandBody>,
+) -> Result<HttpResponseOk<RconCommandResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+    let body = body.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let command = body.command.trim();
+        if command.is_empty() || command.contains(['\r', '\n']) {
+            return Err(HttpError::for_client_error(
+                Some("INVALID_COMMAND".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                "The command must be a single, non-empty line.".to_string(),
+            ));
+        }
+
+        let response = run_rcon(&rqctx, &cfg, command).await?;
+        return Ok(HttpResponseOk(response));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[derive(JsonSchema, Serialize)]
+struct ListPlayersResponse {
+    players: Vec<RconPlayer>,
+}
+
+/// Lists the players connected to a running server, as reported over RCON.
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/players"
+)]
+pub async fn list_players(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+) -> Result<HttpResponseOk<ListPlayersResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let response = run_rcon(&rqctx, &cfg, "#players").await?;
+        return Ok(HttpResponseOk(ListPlayersResponse {
+            players: rcon::parse_players(&response.response),
+        }));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct PlayerPath {
+    /// The ID of the server to fetch data for.
+    id: Uuid,
+
+    /// The player's ID for this session of the server.
+    player_id: u32,
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct KickPlayerBody {
+    /// Why the player is kicked, shown to them.
+    reason: Option<String>,
+}
+
+#[endpoint(
+    method = POST,
+    path = "/servers/{id}/players/{player_id}/kick"
+)]
+pub async fn kick_player(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<PlayerPath>,
+    body: TypedBody<KickPlayerBody>,
+) -> Result<HttpResponseOk<RconCommandResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+    let body = body.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let command = rcon::kick_command(path.player_id, &body.reason.unwrap_or_default());
+        let response = run_rcon(&rqctx, &cfg, &command).await?;
+        return Ok(HttpResponseOk(response));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[derive(JsonSchema, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BanPlayerBody {
+    /// The player's ID for this session of the server, or their identity ID.
+    player: String,
+
+    /// How many seconds the ban lasts. Defaults to 0, which bans the player
+    /// for good.
+    duration_secs: Option<u64>,
+
+    /// Why the player is banned.
+    reason: Option<String>,
+}
+
+#[endpoint(
+    method = POST,
+    path = "/servers/{id}/bans"
+)]
+pub async fn ban_player(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<BanPlayerBody>,
+) -> Result<HttpResponseOk<RconCommandResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+    let body = body.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        check_player(&body.player)?;
+        let command = rcon::ban_command(
+            &body.player,
+            body.duration_secs.unwrap_or(0),
+            &body.reason.unwrap_or_default(),
+        );
+        let response = run_rcon(&rqctx, &cfg, &command).await?;
+        return Ok(HttpResponseOk(response));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct BanPath {
+    /// The ID of the server to fetch data for.
+    id: Uuid,
+
+    /// The banned player's identity ID.
+    player: String,
+}
+
+#[endpoint(
+    method = DELETE,
+    path = "/servers/{id}/bans/{player}"
+)]
+pub async fn unban_player(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<BanPath>,
+) -> Result<HttpResponseOk<RconCommandResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        check_player(&path.player)?;
+        let command = rcon::unban_command(&path.player);
+        let response = run_rcon(&rqctx, &cfg, &command).await?;
+        return Ok(HttpResponseOk(response));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct BroadcastBody {
+    /// The message to send to every player.
+    message: String,
+}
+
+#[endpoint(
+    method = POST,
+    path = "/servers/{id}/broadcast"
+)]
+pub async fn broadcast_message(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<BroadcastBody>,
+) -> Result<HttpResponseOk<RconCommandResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+    let body = body.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        if body.message.trim().is_empty() {
+            return Err(HttpError::for_client_error(
+                Some("INVALID_MESSAGE".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                "The message must not be empty.".to_string(),
+            ));
+        }
+
+        let command = rcon::say_command(&body.message);
+        let response = run_rcon(&rqctx, &cfg, &command).await?;
+        return Ok(HttpResponseOk(response));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[endpoint(
+    method = GET,
+    path = "/servers/{id}/config"
+)]
+pub async fn get_config(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+) -> Result<HttpResponseOk<ServerConfig>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        return Ok(HttpResponseOk(cfg.config));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+/// Replaces a server's config. The new config applies from the next time
+/// the server is started.
+#[endpoint(
+    method = PUT,
+    path = "/servers/{id}/config"
+)]
+pub async fn update_config(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<ServerConfig>,
+) -> Result<HttpResponseOk<ServerConfig>, ConfigError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let new_config = body.into_inner();
+        let _ports = rqctx.context().ports_lock.lock().await;
+        check_config(db, cfg.id, &new_config).await?;
+
+        ConfigEntity::update(config::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(cfg.id),
+            config: sea_orm::ActiveValue::Set(new_config.clone()),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+
+        return Ok(HttpResponseOk(new_config));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    )
+    .into())
+}
+
+/// Changes part of a server's config with a JSON Merge Patch (RFC 7396),
+/// e.g. `{"game": {"maxPlayers": 32}}`. The new config applies from the next
+/// time the server is started.
+#[endpoint(
+    method = PATCH,
+    path = "/servers/{id}/config"
+)]
+pub async fn patch_config(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: UntypedBody,
+) -> Result<HttpResponseOk<ServerConfig>, ConfigError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let patch: serde_json::Value = serde_json::from_slice(body.as_bytes()).map_err(|e| {
+            HttpError::for_client_error(
+                Some("INVALID_PATCH".to_string()),
+                ClientErrorStatusCode::BAD_REQUEST,
+                format!("The patch is not valid JSON: {}", e),
+            )
+        })?;
+
+        let current = serde_json::to_value(&cfg.config)
+            .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+        let removed = removed_required_keys::<ServerConfig>(&current, &patch);
+        if !removed.is_empty() {
+            return Err(ConfigError::invalid(removed));
+        }
+        let mut merged = current;
+        merge_patch(&mut merged, &patch);
+        let new_config: ServerConfig = serde_json::from_value(merged).map_err(|e| {
+            ConfigError::invalid(vec![ValidationIssue {
+                pointer: String::new(),
+                severity: Severity::Error,
+                message: format!("the patched config can't be read: {}", e),
+            }])
+        })?;
+        let _ports = rqctx.context().ports_lock.lock().await;
+        check_config(db, cfg.id, &new_config).await?;
+
+        ConfigEntity::update(config::ActiveModel {
+            id: sea_orm::ActiveValue::Unchanged(cfg.id),
+            config: sea_orm::ActiveValue::Set(new_config.clone()),
+            ..Default::default()
+        })
+        .exec(db)
+        .await
+        .map_err(|e| HttpError::for_internal_error(format!("failed to update config: {}", e)))?;
+
+        return Ok(HttpResponseOk(new_config));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    )
+    .into())
+}
+
+/// Checks a config for server `id` without saving it, returning every error
+/// and warning. Ports used by other servers are reported as errors too.
+#[endpoint(
+    method = POST,
+    path = "/servers/{id}/config/validate"
+)]
+pub async fn validate_config(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetServerPath>,
+    body: TypedBody<ServerConfig>,
+) -> Result<HttpResponseOk<ValidationReport>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let config = ConfigEntity::find()
+        .filter(Expr::col(config::Column::Id).eq(path.id))
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(cfg) = config {
+        let report = config_report(db, cfg.id, &body.into_inner()).await?;
+
+        return Ok(HttpResponseOk(report));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No server with that ID was found.".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn patched(target: serde_json::Value, patch: serde_json::Value) -> serde_json::Value {
+        let mut target = target;
+        merge_patch(&mut target, &patch);
+        target
+    }
+
+    #[test]
+    fn merges_patches_as_rfc_7396_does() {
+        // The examples in appendix A of RFC 7396.
+        let examples = [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (
+                json!({"a": "b"}),
+                json!({"b": "c"}),
+                json!({"a": "b", "b": "c"}),
+            ),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (
+                json!({"a": "b", "b": "c"}),
+                json!({"a": null}),
+                json!({"b": "c"}),
+            ),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "c"}), json!({"a": ["b"]}), json!({"a": ["b"]})),
+            (
+                json!({"a": {"b": "c"}}),
+                json!({"a": {"b": "d", "c": null}}),
+                json!({"a": {"b": "d"}}),
+            ),
+            (
+                json!({"a": [{"b": "c"}]}),
+                json!({"a": [1]}),
+                json!({"a": [1]}),
+            ),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "b"}), json!(["c"]), json!(["c"])),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (
+                json!({"e": null}),
+                json!({"a": 1}),
+                json!({"e": null, "a": 1}),
+            ),
+            (
+                json!([1, 2]),
+                json!({"a": "b", "c": null}),
+                json!({"a": "b"}),
+            ),
+            (
+                json!({}),
+                json!({"a": {"bb": {"ccc": null}}}),
+                json!({"a": {"bb": {}}}),
+            ),
+        ];
+        for (target, patch, expected) in examples {
+            assert_eq!(
+                patched(target.clone(), patch.clone()),
+                expected,
+                "{} patched with {}",
+                target,
+                patch
+            );
+        }
+    }
+
+    #[test]
+    fn escapes_pointer_keys() {
+        assert_eq!(escape_pointer_key("maxPlayers"), "maxPlayers");
+        assert_eq!(escape_pointer_key("a/b"), "a~1b");
+        assert_eq!(escape_pointer_key("m~n"), "m~0n");
+        // `~` is escaped first, so `~1` isn't read back as `/`.
+        assert_eq!(escape_pointer_key("~1/"), "~01~1");
+    }
+
+    #[test]
+    fn finds_removed_keys() {
+        let mut removed = Vec::new();
+        removed_keys(
+            &json!({"a/b": null, "m~n": {"x": null, "y": 1}, "z": [null]}),
+            String::new(),
+            &mut removed,
+        );
+        removed.sort();
+        assert_eq!(
+            removed,
+            [
+                (String::new(), String::from("a/b")),
+                (String::from("/m~0n"), String::from("x")),
+            ]
+        );
+    }
+
+    fn pointers(issues: Vec<ValidationIssue>) -> Vec<String> {
+        issues.into_iter().map(|issue| issue.pointer).collect()
+    }
+
+    #[test]
+    fn rejects_removing_required_config_fields() {
+        let current = serde_json::to_value(ServerConfig::default()).unwrap();
+        let removed = |patch| pointers(removed_required_keys::<ServerConfig>(&current, &patch));
+
+        assert_eq!(removed(json!({"game": null})), ["/game"]);
+        assert_eq!(
+            removed(json!({"game": {"scenarioId": null, "password": null}})),
+            ["/game/scenarioId"]
+        );
+        assert_eq!(
+            removed(json!({"rcon": {"port": null}, "publicAddress": null})),
+            ["/rcon/port"]
+        );
+        // Optional fields can be removed, and removing a key which isn't
+        // there is a no-op.
+        assert_eq!(
+            removed(json!({"game": {"password": null}})),
+            Vec::<String>::new()
+        );
+        assert_eq!(removed(json!({"unknown": null})), Vec::<String>::new());
+        assert_eq!(
+            removed(json!({"game": {"maxPlayers": 32}})),
+            Vec::<String>::new()
+        );
+
+        let issues = removed_required_keys::<ServerConfig>(&current, &json!({"game": null}));
+        assert_eq!(issues[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn escapes_removed_required_keys() {
+        #[derive(Deserialize, Serialize)]
+        struct Inner {
+            #[serde(rename = "m~n")]
+            required: u32,
+            optional: Option<u32>,
+        }
+
+        #[derive(Deserialize, Serialize)]
+        struct Outer {
+            #[serde(rename = "a/b")]
+            inner: Inner,
+        }
+
+        let current = serde_json::to_value(Outer {
+            inner: Inner {
+                required: 1,
+                optional: Some(2),
+            },
+        })
+        .unwrap();
+        let removed = |patch| pointers(removed_required_keys::<Outer>(&current, &patch));
+
+        assert_eq!(removed(json!({"a/b": null})), ["/a~1b"]);
+        assert_eq!(
+            removed(json!({"a/b": {"m~n": null, "optional": null}})),
+            ["/a~1b/m~0n"]
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use dropshot::{endpoint, HttpError, HttpResponseOk, RequestContext};
+use dropshot::{EmptyScanParams, PaginationParams, Path, Query, ResultsPage, WhichPage};
+use harm_entity::config::{Entity as ConfigEntity, Model as ConfigModel};
+use harm_entity::trash::{self, Entity as TrashEntity, Model as TrashModel};
+use harm_pm::ports::ServerPorts;
+use schemars::JsonSchema;
+use sea_orm::{prelude::*, QueryOrder, QuerySelect, TransactionTrait};
+use serde::Deserialize;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{apis::server::check_ports, context::ServerCtx, trash as purger};
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+struct TrashPage {
+    pub id: Uuid,
+}
+
+impl From<&TrashModel> for TrashPage {
+    fn from(value: &TrashModel) -> Self {
+        Self { id: value.id }
+    }
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct GetTrashPath {
+    /// The ID of the deleted server.
+    id: Uuid,
+}
+
+#[derive(JsonSchema, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PurgeResponse {
+    id: Uuid,
+
+    /// Where the server's files were archived to, if they were.
+    archived_to: Option<String>,
+}
+
+/// Lists the deleted servers which can still be restored.
+#[endpoint(
+    method = GET,
+    path = "/trash",
+)]
+pub async fn list_trash(
+    rqctx: RequestContext<ServerCtx>,
+    query: Query<PaginationParams<EmptyScanParams, TrashPage>>,
+) -> Result<HttpResponseOk<ResultsPage<TrashModel>>, HttpError> {
+    let pag_params = query.into_inner();
+    let limit = rqctx.page_limit(&pag_params)?.get() as u64;
+    let db = &rqctx.context().db;
+
+    let entries = match &pag_params.page {
+        WhichPage::First(..) => TrashEntity::find()
+            .limit(limit)
+            .order_by_asc(trash::Column::Id)
+            .all(db)
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string())),
+
+        WhichPage::Next(TrashPage { id }) => TrashEntity::find()
+            .limit(limit)
+            .filter(trash::Column::Id.gt(*id))
+            .order_by_asc(trash::Column::Id)
+            .all(db)
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string())),
+    }?;
+
+    Ok(HttpResponseOk(ResultsPage::new(
+        entries,
+        &EmptyScanParams {},
+        |p: &TrashModel, _| TrashPage::from(p),
+    )?))
+}
+
+/// Restores a deleted server from the trash, as long as no other server has
+/// taken its ports in the meantime.
+#[endpoint(
+    method = POST,
+    path = "/trash/{id}/restore",
+)]
+pub async fn restore_server(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetTrashPath>,
+) -> Result<HttpResponseOk<ConfigModel>, HttpError> {
+    let db = &rqctx.context().db;
+    let path = path.into_inner();
+
+    let entry = TrashEntity::find_by_id(path.id)
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(entry) = entry {
+        let cfg: ConfigModel = serde_json::from_value(entry.server).map_err(|e| {
+            HttpError::for_internal_error(format!("failed to read deleted server: {}", e))
+        })?;
+        let _ports = rqctx.context().ports_lock.lock().await;
+        check_ports(db, cfg.id, &ServerPorts::of(&cfg.config)).await?;
+
+        let txn = db
+            .begin()
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+        // Every column is set, as the row no longer exists.
+        let model = harm_entity::config::ActiveModel::from(cfg).reset_all();
+        let restored = ConfigEntity::insert(model)
+            .exec_with_returning(&txn)
+            .await
+            .map_err(|e| {
+                HttpError::for_internal_error(format!("failed to restore server: {}", e))
+            })?;
+        TrashEntity::delete_by_id(entry.id)
+            .exec(&txn)
+            .await
+            .map_err(|e| {
+                HttpError::for_internal_error(format!("failed to restore server: {}", e))
+            })?;
+        txn.commit()
+            .await
+            .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+        return Ok(HttpResponseOk(restored));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No deleted server with that ID was found.".to_string(),
+    ))
+}
+
+/// Deletes a server in the trash for good, without waiting for its retention
+/// window to pass. Its files are deleted, or archived if it was deleted with
+/// `archive=true`.
+#[endpoint(
+    method = DELETE,
+    path = "/trash/{id}",
+)]
+pub async fn purge_server(
+    rqctx: RequestContext<ServerCtx>,
+    path: Path<GetTrashPath>,
+) -> Result<HttpResponseOk<PurgeResponse>, HttpError> {
+    let db = &rqctx.context().db;
+    let pm = &rqctx.context().process_manager;
+    let path = path.into_inner();
+
+    let entry = TrashEntity::find_by_id(path.id)
+        .one(db)
+        .await
+        .map_err(|error| HttpError::for_internal_error(error.to_string()))?;
+
+    if let Some(entry) = entry {
+        let archived = purger::purge(db, pm, &entry)
+            .await
+            .map_err(|e| HttpError::for_internal_error(format!("failed to purge server: {}", e)))?;
+
+        return Ok(HttpResponseOk(PurgeResponse {
+            id: entry.id,
+            archived_to: archived.map(|path| path.display().to_string()),
+        }));
+    }
+
+    Err(HttpError::for_not_found(
+        Some("NO_SUCH_SERVER".to_string()),
+        "No deleted server with that ID was found.".to_string(),
+    ))
+}
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use harm_entity::ban::{self, Entity as BanEntity, Model as BanModel};
+use harm_pm::{events::EventKind, manager::ProcessManager, rcon};
+use sea_orm::{prelude::*, DatabaseConnection};
+use slog::{warn, Logger};
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+use uuid::Uuid;
+
+/// How often expired bans are removed and lifted.
+pub const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a ban has left from `now`, in seconds, as given to `#ban create`:
+/// 0 for a permanent ban, or None once it has expired.
+pub fn remaining_secs(ban: &BanModel, now: DateTime<Utc>) -> Option<u64> {
+    match ban.expires_at {
+        None => Some(0),
+        Some(expires_at) if expires_at > now => {
+            let millis = (expires_at - now).num_milliseconds().max(1) as u64;
+            Some(millis.div_ceil(1000))
+        }
+        Some(_) => None,
+    }
+}
+
+/// Bans a player on one running server, unless the ban has expired.
+async fn apply(pm: &ProcessManager, server_id: Uuid, ban: &BanModel) -> anyhow::Result<()> {
+    let Some(secs) = remaining_secs(ban, Utc::now()) else {
+        return Ok(());
+    };
+    let command = rcon::ban_command(
+        &ban.identity_id,
+        secs,
+        ban.reason.as_deref().unwrap_or_default(),
+    );
+    pm.rcon_command(server_id, &command).await?;
+    Ok(())
+}
+
+/// The servers HARM is logged in to over RCON.
+async fn connected_servers(pm: &ProcessManager) -> Vec<Uuid> {
+    pm.list_status()
+        .await
+        .into_iter()
+        .filter(|status| status.rcon_connected)
+        .map(|status| status.id)
+        .collect()
+}
+
+/// Bans a player on every running server.
+pub async fn push(pm: &ProcessManager, log: &Logger, ban: &BanModel) {
+    for server_id in connected_servers(pm).await {
+        if let Err(e) = apply(pm, server_id, ban).await {
+            warn!(
+                log,
+                "Could not ban {} on server {}: {}", ban.identity_id, server_id, e
+            );
+        }
+    }
+}
+
+/// Lifts a player's ban on every running server.
+pub async fn lift(pm: &ProcessManager, log: &Logger, identity_id: &str) {
+    for server_id in connected_servers(pm).await {
+        if let Err(e) = pm
+            .rcon_command(server_id, &rcon::unban_command(identity_id))
+            .await
+        {
+            warn!(
+                log,
+                "Could not lift the ban on {} on server {}: {}", identity_id, server_id, e
+            );
+        }
+    }
+}
+
+/// Spawns the task which enforces the ban list. Every ban is applied to a
+/// server whenever HARM logs in to its RCON, so servers which start or
+/// restart pick up bans issued while they were down, and to every server if
+/// events were missed. Expired bans are removed and lifted every `interval`.
+pub fn spawn_enforcer(
+    db: DatabaseConnection,
+    pm: ProcessManager,
+    log: Logger,
+    interval: Duration,
+) -> JoinHandle<()> {
+    let mut events = pm.events().subscribe();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) if event.kind == EventKind::RconConnected => {
+                        if let Err(e) = enforce(&db, &pm, &log, &[event.server_id]).await {
+                            warn!(log, "Could not load bans: {}", e);
+                        }
+                    }
+                    Ok(_) => continue,
+                    // A login may have been among the missed events, so
+                    // every server is given the bans again.
+                    Err(RecvError::Lagged(_)) => {
+                        let servers = connected_servers(&pm).await;
+                        if let Err(e) = enforce(&db, &pm, &log, &servers).await {
+                            warn!(log, "Could not load bans: {}", e);
+                        }
+                    }
+                    Err(RecvError::Closed) => return,
+                },
+                _ = ticker.tick() => {
+                    if let Err(e) = expire(&db, &pm, &log).await {
+                        warn!(log, "Could not expire bans: {}", e);
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Applies every ban to each of `servers`.
+async fn enforce(
+    db: &DatabaseConnection,
+    pm: &ProcessManager,
+    log: &Logger,
+    servers: &[Uuid],
+) -> Result<(), DbErr> {
+    let bans = BanEntity::find().all(db).await?;
+    for server_id in servers {
+        for ban in &bans {
+            if let Err(e) = apply(pm, *server_id, ban).await {
+                warn!(
+                    log,
+                    "Could not ban {} on server {}: {}", ban.identity_id, server_id, e
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes bans which have expired, and lifts them on every running server.
+async fn expire(db: &DatabaseConnection, pm: &ProcessManager, log: &Logger) -> Result<(), DbErr> {
+    let expired = BanEntity::find()
+        .filter(ban::Column::ExpiresAt.lte(Utc::now()))
+        .all(db)
+        .await?;
+    for ban in expired {
+        BanEntity::delete_by_id(ban.id).exec(db).await?;
+        lift(pm, log, &ban.identity_id).await;
+    }
+    Ok(())
+}
@@ -1,7 +1,13 @@
-use harm_pm::manager::ProcessManager;
+use harm_pm::{manager::ProcessManager, ports::PortPool};
 use sea_orm::DatabaseConnection;
+use tokio::sync::Mutex;
 
 pub struct ServerCtx {
     pub process_manager: ProcessManager,
     pub db: DatabaseConnection,
+    /// The ports new servers are given.
+    pub port_pool: PortPool,
+    /// Held from when a server's ports are checked or allocated until they
+    /// are saved, so that two servers are never given the same ports.
+    pub ports_lock: Mutex<()>,
 }
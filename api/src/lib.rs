@@ -1,17 +1,52 @@
-use std::net::{Ipv4Addr, SocketAddr};
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
 use context::ServerCtx;
+use directories::ProjectDirs;
 use dropshot::{ApiDescription, ConfigDropshot, ConfigLogging, ServerBuilder};
+use harm_entity::config::Entity as ConfigEntity;
 use harm_migration::MigratorTrait;
-use harm_pm::manager::ProcessManager;
+use harm_pm::{a2s, manager::ProcessManager, ports::PortPool};
+use sea_orm::EntityTrait;
+use slog::info;
+use store::DbProcessStore;
+use tokio::sync::Mutex;
 
 mod apis;
+mod bans;
 mod context;
 mod db;
+mod players;
+mod presets;
+mod store;
+mod trash;
 
-pub async fn start(port: u16, database_url: String, reforger_path: String) -> Result<(), String> {
+/// The largest request body the API accepts, e.g. a server config with a
+/// long mod list.
+const REQUEST_BODY_MAX_BYTES: usize = 1024 * 1024;
+
+/// The directory HARM keeps server data in when none is given to `start`.
+pub fn default_data_dir() -> Option<PathBuf> {
+    ProjectDirs::from("dev", "hbjy", "harm").map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+pub async fn start(
+    port: u16,
+    database_url: String,
+    reforger_path: String,
+    data_dir: Option<PathBuf>,
+    sample_interval: Duration,
+    cgroup_root: Option<PathBuf>,
+    port_pool: PortPool,
+) -> Result<(), String> {
     let config_dropshot = ConfigDropshot {
         bind_address: SocketAddr::from((Ipv4Addr::new(0, 0, 0, 0), port)),
+        default_request_body_max_bytes: REQUEST_BODY_MAX_BYTES,
         ..Default::default()
     };
 
@@ -30,22 +65,124 @@ pub async fn start(port: u16, database_url: String, reforger_path: String) -> Re
         .await
         .map_err(|error| format!("failed to migrate db: {}", error))?;
 
-    let process_manager = ProcessManager::new(reforger_path, log.clone());
+    let data_dir = data_dir
+        .or_else(default_data_dir)
+        .ok_or_else(|| String::from("failed to find a data directory, please set one"))?;
+
+    let mut process_manager = ProcessManager::new(reforger_path, data_dir, log.clone())
+        .with_store(Arc::new(DbProcessStore::new(db_conn.clone())));
+    if let Some(cgroup_root) = cgroup_root {
+        process_manager = process_manager.with_cgroup_root(cgroup_root);
+    }
+
+    let configs = ConfigEntity::find()
+        .all(&db_conn)
+        .await
+        .map_err(|error| format!("failed to load servers: {}", error))?;
+    let mut specs = HashMap::new();
+    for cfg in &configs {
+        let spec = apis::server::server_spec(&db_conn, cfg)
+            .await
+            .map_err(|error| format!("failed to load servers: {}", error))?;
+        specs.insert(cfg.id, spec);
+    }
+    let adopted = process_manager
+        .reattach(specs)
+        .await
+        .map_err(|error| format!("failed to reattach servers: {}", error))?;
+    if !adopted.is_empty() {
+        info!(log, "Re-adopted {} running server(s)", adopted.len());
+    }
+
+    process_manager.spawn_sampler(sample_interval);
+    process_manager.spawn_query_poller(a2s::DEFAULT_POLL_INTERVAL);
+    bans::spawn_enforcer(
+        db_conn.clone(),
+        process_manager.clone(),
+        log.clone(),
+        bans::EXPIRY_INTERVAL,
+    );
+    players::spawn_tracker(
+        db_conn.clone(),
+        process_manager.clone(),
+        log.clone(),
+        players::RECONCILE_INTERVAL,
+    );
+    trash::spawn_purger(
+        db_conn.clone(),
+        process_manager.clone(),
+        log.clone(),
+        trash::PURGE_INTERVAL,
+    );
 
     let ctx = ServerCtx {
         db: db_conn,
         process_manager,
+        port_pool,
+        ports_lock: Mutex::new(()),
     };
 
     let mut api = ApiDescription::<ServerCtx>::new();
     api.register(apis::server::list_servers).unwrap();
     api.register(apis::server::get_server).unwrap();
     api.register(apis::server::create_server).unwrap();
+    api.register(apis::server::clone_server).unwrap();
+    api.register(apis::server::delete_server).unwrap();
     api.register(apis::server::start_server).unwrap();
+    api.register(apis::server::get_preflight).unwrap();
     api.register(apis::server::stop_server).unwrap();
+    api.register(apis::server::get_status).unwrap();
+    api.register(apis::server::get_config).unwrap();
+    api.register(apis::server::update_config).unwrap();
+    api.register(apis::server::patch_config).unwrap();
+    api.register(apis::server::validate_config).unwrap();
+    api.register(apis::server::update_restart_policy).unwrap();
+    api.register(apis::server::get_launch_options).unwrap();
+    api.register(apis::server::update_launch_options).unwrap();
+    api.register(apis::server::get_runtime).unwrap();
+    api.register(apis::server::update_runtime).unwrap();
+    api.register(apis::server::get_resource_limits).unwrap();
+    api.register(apis::server::update_resource_limits).unwrap();
+    api.register(apis::server::get_readiness).unwrap();
+    api.register(apis::server::update_readiness).unwrap();
+    api.register(apis::server::wait_ready).unwrap();
+    api.register(apis::server::get_ports).unwrap();
+    api.register(apis::server::update_ports).unwrap();
+    api.register(apis::server::get_logs).unwrap();
+    api.register(apis::server::get_resources).unwrap();
     api.register(apis::server::add_mod).unwrap();
     api.register(apis::server::list_mods).unwrap();
+    api.register(apis::server::replace_mods).unwrap();
+    api.register(apis::server::reorder_mods).unwrap();
+    api.register(apis::server::update_mod).unwrap();
     api.register(apis::server::delete_mod).unwrap();
+    api.register(apis::server::get_mod_presets).unwrap();
+    api.register(apis::server::update_mod_presets).unwrap();
+    api.register(apis::server::get_effective_mods).unwrap();
+    api.register(apis::server::run_rcon_command).unwrap();
+    api.register(apis::server::list_players).unwrap();
+    api.register(apis::server::kick_player).unwrap();
+    api.register(apis::server::ban_player).unwrap();
+    api.register(apis::server::unban_player).unwrap();
+    api.register(apis::server::broadcast_message).unwrap();
+    api.register(apis::ban::list_bans).unwrap();
+    api.register(apis::ban::get_ban).unwrap();
+    api.register(apis::ban::create_ban).unwrap();
+    api.register(apis::ban::update_ban).unwrap();
+    api.register(apis::ban::delete_ban).unwrap();
+    api.register(apis::player::list_players).unwrap();
+    api.register(apis::player::get_player).unwrap();
+    api.register(apis::player::list_sessions).unwrap();
+    api.register(apis::player::get_playtime).unwrap();
+    api.register(apis::event::stream_events).unwrap();
+    api.register(apis::preset::list_presets).unwrap();
+    api.register(apis::preset::get_preset).unwrap();
+    api.register(apis::preset::create_preset).unwrap();
+    api.register(apis::preset::update_preset).unwrap();
+    api.register(apis::preset::delete_preset).unwrap();
+    api.register(apis::trash::list_trash).unwrap();
+    api.register(apis::trash::restore_server).unwrap();
+    api.register(apis::trash::purge_server).unwrap();
 
     let server = ServerBuilder::new(api, ctx, log)
         .config(config_dropshot)
@@ -0,0 +1,748 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use harm_entity::{
+    player::{self, Entity as PlayerEntity, Model as PlayerModel},
+    session::{self, Entity as SessionEntity},
+};
+use harm_pm::{
+    events::{EventKind, ServerEvent},
+    manager::{ProcessManager, ServerState},
+    rcon::{self, RconPlayer},
+};
+use sea_orm::{
+    prelude::*,
+    ActiveValue::{Set, Unchanged},
+    DatabaseConnection,
+};
+use slog::{warn, Logger};
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+use uuid::Uuid;
+
+/// How often the players on every running server are listed over RCON, to
+/// catch joins and leaves the logs didn't show.
+pub const RECONCILE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A player connected to a server.
+struct Connected {
+    name: String,
+    address: Option<String>,
+    guid: Option<String>,
+    joined_at: DateTime<Utc>,
+    /// The player's row, once their identity is known.
+    player: Option<Uuid>,
+    /// The player's open session, once their identity is known.
+    session: Option<Uuid>,
+}
+
+impl Connected {
+    fn new(name: String, joined_at: DateTime<Utc>) -> Self {
+        Self {
+            name,
+            address: None,
+            guid: None,
+            joined_at,
+            player: None,
+            session: None,
+        }
+    }
+}
+
+/// Records players and their sessions from the events servers publish.
+struct Tracker {
+    db: DatabaseConnection,
+    pm: ProcessManager,
+    /// Connected players by server and their ID for the server's session.
+    connected: HashMap<(Uuid, u32), Connected>,
+}
+
+impl Tracker {
+    async fn handle(&mut self, event: ServerEvent) -> Result<(), DbErr> {
+        let server_id = event.server_id;
+        let at = event.timestamp;
+        match event.kind {
+            EventKind::PlayerConnected {
+                player_id,
+                name,
+                address,
+            } => {
+                // Session IDs are reused once a player leaves, so a player
+                // still connected with this ID left without it being logged.
+                self.leave(server_id, player_id, at).await?;
+                let mut connected = Connected::new(name, at);
+                connected.address = address;
+                self.connected.insert((server_id, player_id), connected);
+            }
+            EventKind::PlayerGuid {
+                player_id,
+                name,
+                guid,
+            } => {
+                let connected = self
+                    .connected
+                    .entry((server_id, player_id))
+                    .or_insert_with(|| Connected::new(name, at));
+                connected.guid = Some(guid.clone());
+                if let Some(player) = connected.player {
+                    PlayerEntity::update(player::ActiveModel {
+                        id: Unchanged(player),
+                        guid: Set(Some(guid)),
+                        ..Default::default()
+                    })
+                    .exec(&self.db)
+                    .await?;
+                }
+            }
+            EventKind::PlayerIdentity {
+                player_id,
+                name,
+                identity_id,
+                platform,
+            } => {
+                let connected = self
+                    .connected
+                    .entry((server_id, player_id))
+                    .or_insert_with(|| Connected::new(name.clone(), at));
+                if connected.session.is_some() {
+                    return Ok(());
+                }
+
+                let player = upsert_player(
+                    &self.db,
+                    &identity_id,
+                    &name,
+                    connected.guid.clone(),
+                    platform,
+                    at,
+                )
+                .await?;
+                connected.player = Some(player.id);
+                connected.session =
+                    Some(open_session(&self.db, player.id, server_id, connected).await?);
+            }
+            EventKind::PlayerDisconnected { player_id, .. }
+            | EventKind::PlayerKicked { player_id, .. } => {
+                self.leave(server_id, player_id, at).await?;
+            }
+            // Players can only be connected to a server while it is ready.
+            EventKind::StateChanged { state } if state != ServerState::Ready => {
+                let players = self
+                    .connected
+                    .keys()
+                    .filter(|(server, _)| *server == server_id)
+                    .map(|(_, player_id)| *player_id)
+                    .collect::<Vec<_>>();
+                for player_id in players {
+                    self.leave(server_id, player_id, at).await?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Closes the session of a player who left a server.
+    async fn leave(
+        &mut self,
+        server_id: Uuid,
+        player_id: u32,
+        at: DateTime<Utc>,
+    ) -> Result<(), DbErr> {
+        let Some(connected) = self.connected.remove(&(server_id, player_id)) else {
+            return Ok(());
+        };
+        if let Some(session) = connected.session {
+            SessionEntity::update(session::ActiveModel {
+                id: Unchanged(session),
+                left_at: Set(Some(at)),
+                ..Default::default()
+            })
+            .exec(&self.db)
+            .await?;
+        }
+        if let Some(player) = connected.player {
+            seen(&self.db, player, at).await?;
+        }
+        Ok(())
+    }
+
+    /// Lists the players on every server HARM is logged in to over RCON, and
+    /// records joins and leaves the logs missed, e.g. while HARM was down.
+    async fn reconcile(&mut self) -> Result<(), DbErr> {
+        let now = Utc::now();
+        for status in self.pm.list_status().await {
+            if !status.rcon_connected {
+                continue;
+            }
+            let Ok(answer) = self.pm.rcon_command(status.id, "#players").await else {
+                continue;
+            };
+            self.reconcile_server(status.id, rcon::parse_players(&answer), now)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Records joins and leaves missing from the players listed on a server.
+    async fn reconcile_server(
+        &mut self,
+        server_id: Uuid,
+        listed: Vec<RconPlayer>,
+        at: DateTime<Utc>,
+    ) -> Result<(), DbErr> {
+        let ids = listed.iter().map(|p| p.id).collect::<HashSet<_>>();
+        let left = self
+            .connected
+            .keys()
+            .filter(|(server, player_id)| *server == server_id && !ids.contains(player_id))
+            .map(|(_, player_id)| *player_id)
+            .collect::<Vec<_>>();
+        for player_id in left {
+            self.leave(server_id, player_id, at).await?;
+        }
+
+        for listed in listed {
+            match self.connected.get(&(server_id, listed.id)) {
+                Some(connected) => {
+                    if let Some(player) = connected.player {
+                        seen(&self.db, player, at).await?;
+                    }
+                }
+                None => self.rejoin(server_id, listed, at).await?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Tracks a player listed over RCON whose join wasn't seen. The list
+    /// doesn't include identities, so the player is recognised by their
+    /// BattlEye GUID, if they have been seen before.
+    async fn rejoin(
+        &mut self,
+        server_id: Uuid,
+        listed: RconPlayer,
+        at: DateTime<Utc>,
+    ) -> Result<(), DbErr> {
+        let mut connected = Connected::new(listed.name, at);
+        connected.address = listed.address;
+        connected.guid = listed.guid;
+
+        let known = match &connected.guid {
+            Some(guid) => {
+                PlayerEntity::find()
+                    .filter(player::Column::Guid.eq(guid))
+                    .one(&self.db)
+                    .await?
+            }
+            None => None,
+        };
+        if let Some(player) = known {
+            connected.player = Some(player.id);
+            connected.session =
+                Some(open_session(&self.db, player.id, server_id, &connected).await?);
+            seen(&self.db, player.id, at).await?;
+        }
+
+        self.connected.insert((server_id, listed.id), connected);
+        Ok(())
+    }
+}
+
+/// Creates or updates the player with an identity, remembering every name
+/// they are seen with.
+async fn upsert_player(
+    db: &DatabaseConnection,
+    identity_id: &str,
+    name: &str,
+    guid: Option<String>,
+    platform: Option<String>,
+    at: DateTime<Utc>,
+) -> Result<PlayerModel, DbErr> {
+    let existing = PlayerEntity::find()
+        .filter(player::Column::IdentityId.eq(identity_id))
+        .one(db)
+        .await?;
+
+    match existing {
+        Some(existing) => {
+            let mut names: Vec<String> =
+                serde_json::from_value(existing.names.clone()).unwrap_or_default();
+            if !names.iter().any(|seen| seen == name) {
+                names.push(name.to_string());
+            }
+            player::ActiveModel {
+                id: Unchanged(existing.id),
+                name: Set(name.to_string()),
+                names: Set(serde_json::json!(names)),
+                guid: Set(guid.or(existing.guid)),
+                platform: Set(platform.or(existing.platform)),
+                last_seen: Set(at),
+                ..Default::default()
+            }
+            .update(db)
+            .await
+        }
+        None => {
+            player::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                identity_id: Set(identity_id.to_string()),
+                name: Set(name.to_string()),
+                names: Set(serde_json::json!([name])),
+                guid: Set(guid),
+                platform: Set(platform),
+                first_seen: Set(at),
+                last_seen: Set(at),
+            }
+            .insert(db)
+            .await
+        }
+    }
+}
+
+async fn open_session(
+    db: &DatabaseConnection,
+    player_id: Uuid,
+    server_id: Uuid,
+    connected: &Connected,
+) -> Result<Uuid, DbErr> {
+    let session = session::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        player_id: Set(player_id),
+        server_id: Set(server_id),
+        name: Set(connected.name.clone()),
+        address: Set(connected.address.clone()),
+        joined_at: Set(connected.joined_at),
+        left_at: Set(None),
+    }
+    .insert(db)
+    .await?;
+    Ok(session.id)
+}
+
+async fn seen(db: &DatabaseConnection, player_id: Uuid, at: DateTime<Utc>) -> Result<(), DbErr> {
+    PlayerEntity::update(player::ActiveModel {
+        id: Unchanged(player_id),
+        last_seen: Set(at),
+        ..Default::default()
+    })
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+/// Closes sessions left open when HARM last stopped. Each ends when its
+/// player was last seen, and sessions of players still connected are opened
+/// again once the players are listed over RCON.
+async fn close_stale_sessions(db: &DatabaseConnection) -> Result<(), DbErr> {
+    let stale = SessionEntity::find()
+        .filter(session::Column::LeftAt.is_null())
+        .find_also_related(PlayerEntity)
+        .all(db)
+        .await?;
+    for (session, player) in stale {
+        let left_at = player
+            .map(|player| player.last_seen)
+            .unwrap_or(session.joined_at)
+            .max(session.joined_at);
+        SessionEntity::update(session::ActiveModel {
+            id: Unchanged(session.id),
+            left_at: Set(Some(left_at)),
+            ..Default::default()
+        })
+        .exec(db)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Spawns the task which records players and their sessions from every
+/// server's events, and lists players over RCON every `interval` to catch
+/// anything the logs missed.
+pub fn spawn_tracker(
+    db: DatabaseConnection,
+    pm: ProcessManager,
+    log: Logger,
+    interval: Duration,
+) -> JoinHandle<()> {
+    let mut events = pm.events().subscribe();
+    tokio::spawn(async move {
+        if let Err(e) = close_stale_sessions(&db).await {
+            warn!(log, "Could not close stale sessions: {}", e);
+        }
+
+        let mut tracker = Tracker {
+            db,
+            pm,
+            connected: HashMap::new(),
+        };
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        if let Err(e) = tracker.handle(event).await {
+                            warn!(log, "Could not record player event: {}", e);
+                        }
+                    }
+                    // Joins and leaves may have been among the missed
+                    // events, so the players are listed straight away.
+                    Err(RecvError::Lagged(_)) => {
+                        if let Err(e) = tracker.reconcile().await {
+                            warn!(log, "Could not reconcile players: {}", e);
+                        }
+                    }
+                    Err(RecvError::Closed) => return,
+                },
+                _ = ticker.tick() => {
+                    if let Err(e) = tracker.reconcile().await {
+                        warn!(log, "Could not reconcile players: {}", e);
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use harm_migration::MigratorTrait;
+    use sea_orm::{Database, QueryOrder};
+    use slog::{o, Discard};
+
+    use super::*;
+
+    async fn tracker() -> Tracker {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        harm_migration::Migrator::up(&db, None).await.unwrap();
+        let pm = ProcessManager::new(
+            String::from("/nonexistent/ArmaReforgerServer"),
+            std::env::temp_dir().join(format!("harm-test-{}", Uuid::new_v4())),
+            Logger::root(Discard, o!()),
+        );
+        Tracker {
+            db,
+            pm,
+            connected: HashMap::new(),
+        }
+    }
+
+    /// The time `secs` seconds into a test.
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::UNIX_EPOCH + TimeDelta::seconds(secs)
+    }
+
+    async fn handle(tracker: &mut Tracker, server_id: Uuid, secs: i64, kind: EventKind) {
+        tracker
+            .handle(ServerEvent {
+                seq: 0,
+                server_id,
+                timestamp: at(secs),
+                kind,
+            })
+            .await
+            .unwrap();
+    }
+
+    /// A player joining with their identity, as a server logs it.
+    async fn join(tracker: &mut Tracker, server_id: Uuid, secs: i64, player_id: u32, name: &str) {
+        handle(
+            tracker,
+            server_id,
+            secs,
+            EventKind::PlayerConnected {
+                player_id,
+                name: name.to_string(),
+                address: Some(String::from("10.0.0.2:2304")),
+            },
+        )
+        .await;
+        handle(
+            tracker,
+            server_id,
+            secs,
+            EventKind::PlayerIdentity {
+                player_id,
+                name: name.to_string(),
+                identity_id: format!("identity-{}", name),
+                platform: Some(String::from("PLATFORM_PC")),
+            },
+        )
+        .await;
+    }
+
+    async fn sessions(tracker: &Tracker) -> Vec<session::Model> {
+        SessionEntity::find()
+            .order_by_asc(session::Column::JoinedAt)
+            .order_by_asc(session::Column::Name)
+            .all(&tracker.db)
+            .await
+            .unwrap()
+    }
+
+    async fn player(tracker: &Tracker, name: &str) -> PlayerModel {
+        PlayerEntity::find()
+            .filter(player::Column::IdentityId.eq(format!("identity-{}", name)))
+            .one(&tracker.db)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn records_sessions_from_events() {
+        let mut tracker = tracker().await;
+        let server = Uuid::new_v4();
+
+        handle(
+            &mut tracker,
+            server,
+            10,
+            EventKind::PlayerConnected {
+                player_id: 0,
+                name: String::from("John"),
+                address: Some(String::from("10.0.0.2:2304")),
+            },
+        )
+        .await;
+        handle(
+            &mut tracker,
+            server,
+            11,
+            EventKind::PlayerGuid {
+                player_id: 0,
+                name: String::from("John"),
+                guid: String::from("0123456789abcdef0123456789abcdef"),
+            },
+        )
+        .await;
+        assert!(sessions(&tracker).await.is_empty());
+
+        handle(
+            &mut tracker,
+            server,
+            12,
+            EventKind::PlayerIdentity {
+                player_id: 0,
+                name: String::from("John"),
+                identity_id: String::from("identity-John"),
+                platform: Some(String::from("PLATFORM_PC")),
+            },
+        )
+        .await;
+        let john = player(&tracker, "John").await;
+        assert_eq!(
+            john.guid.as_deref(),
+            Some("0123456789abcdef0123456789abcdef")
+        );
+        assert_eq!(john.platform.as_deref(), Some("PLATFORM_PC"));
+        let opened = sessions(&tracker).await;
+        assert_eq!(opened.len(), 1);
+        assert_eq!(opened[0].player_id, john.id);
+        assert_eq!(opened[0].server_id, server);
+        assert_eq!(opened[0].address.as_deref(), Some("10.0.0.2:2304"));
+        assert_eq!(opened[0].joined_at, at(10));
+        assert_eq!(opened[0].left_at, None);
+
+        handle(
+            &mut tracker,
+            server,
+            20,
+            EventKind::PlayerDisconnected {
+                player_id: 0,
+                name: String::from("John"),
+            },
+        )
+        .await;
+        assert_eq!(sessions(&tracker).await[0].left_at, Some(at(20)));
+        assert_eq!(player(&tracker, "John").await.last_seen, at(20));
+
+        // The ID is given to the next player to join, and John comes back
+        // with a new name.
+        join(&mut tracker, server, 30, 0, "Bob").await;
+        handle(
+            &mut tracker,
+            server,
+            40,
+            EventKind::PlayerConnected {
+                player_id: 1,
+                name: String::from("Johnny"),
+                address: None,
+            },
+        )
+        .await;
+        handle(
+            &mut tracker,
+            server,
+            40,
+            EventKind::PlayerIdentity {
+                player_id: 1,
+                name: String::from("Johnny"),
+                identity_id: String::from("identity-John"),
+                platform: None,
+            },
+        )
+        .await;
+        let john = player(&tracker, "John").await;
+        assert_eq!(john.name, "Johnny");
+        assert_eq!(john.names, serde_json::json!(["John", "Johnny"]));
+        assert_eq!(john.platform.as_deref(), Some("PLATFORM_PC"));
+
+        // Bob's leave wasn't logged before a new player got his ID.
+        join(&mut tracker, server, 50, 0, "Charlie").await;
+        handle(
+            &mut tracker,
+            server,
+            60,
+            EventKind::PlayerKicked {
+                player_id: 1,
+                name: String::from("Johnny"),
+                reason: String::from("Admin kick"),
+            },
+        )
+        .await;
+        let sessions = sessions(&tracker).await;
+        let left = sessions
+            .iter()
+            .map(|session| (session.name.as_str(), session.left_at))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            left,
+            [
+                ("John", Some(at(20))),
+                ("Bob", Some(at(50))),
+                ("Johnny", Some(at(60))),
+                ("Charlie", None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn closes_every_session_when_a_server_stops() {
+        let mut tracker = tracker().await;
+        let stopping = Uuid::new_v4();
+        let running = Uuid::new_v4();
+        join(&mut tracker, stopping, 10, 0, "John").await;
+        join(&mut tracker, stopping, 10, 1, "Bob").await;
+        join(&mut tracker, running, 10, 0, "Charlie").await;
+
+        handle(
+            &mut tracker,
+            stopping,
+            15,
+            EventKind::StateChanged {
+                state: ServerState::Ready,
+            },
+        )
+        .await;
+        assert!(sessions(&tracker)
+            .await
+            .iter()
+            .all(|session| session.left_at.is_none()));
+
+        handle(
+            &mut tracker,
+            stopping,
+            20,
+            EventKind::StateChanged {
+                state: ServerState::Crashed,
+            },
+        )
+        .await;
+        let left = sessions(&tracker)
+            .await
+            .into_iter()
+            .map(|session| (session.name, session.left_at))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            left,
+            [
+                (String::from("Bob"), Some(at(20))),
+                (String::from("Charlie"), None),
+                (String::from("John"), Some(at(20))),
+            ]
+        );
+        assert!(!tracker.connected.contains_key(&(stopping, 0)));
+        assert!(tracker.connected.contains_key(&(running, 0)));
+    }
+
+    #[tokio::test]
+    async fn reconciles_players_listed_over_rcon() {
+        let mut tracker = tracker().await;
+        let server = Uuid::new_v4();
+        join(&mut tracker, server, 10, 0, "John").await;
+        handle(
+            &mut tracker,
+            server,
+            10,
+            EventKind::PlayerGuid {
+                player_id: 0,
+                name: String::from("John"),
+                guid: String::from("0123456789abcdef0123456789abcdef"),
+            },
+        )
+        .await;
+        join(&mut tracker, server, 10, 1, "Bob").await;
+
+        // HARM was down while John left and came back with another ID, Bob
+        // left, and a player HARM never saw joined.
+        tracker.connected.clear();
+        close_stale_sessions(&tracker.db).await.unwrap();
+        let answer = "Players on server:
+[#] [IP Address]:[Port] [Ping] [GUID] [Name]
+--------------------------------------------------
+3   10.0.0.2:2304     47   0123456789abcdef0123456789abcdef(OK) John
+4   10.0.0.3:2304     65   fedcba9876543210fedcba9876543210(OK) Delta
+(2 players in total)";
+        tracker
+            .reconcile_server(server, rcon::parse_players(answer), at(30))
+            .await
+            .unwrap();
+
+        let john = player(&tracker, "John").await;
+        assert_eq!(john.last_seen, at(30));
+        let sessions_now = sessions(&tracker).await;
+        assert_eq!(sessions_now.len(), 3);
+        assert!(sessions_now[..2]
+            .iter()
+            .all(|session| session.left_at == Some(at(10))));
+        assert_eq!(sessions_now[2].player_id, john.id);
+        assert_eq!(sessions_now[2].joined_at, at(30));
+        assert_eq!(sessions_now[2].left_at, None);
+        assert!(tracker.connected.contains_key(&(server, 4)));
+
+        // Delta's identity is learnt once the logs show it.
+        handle(
+            &mut tracker,
+            server,
+            35,
+            EventKind::PlayerIdentity {
+                player_id: 4,
+                name: String::from("Delta"),
+                identity_id: String::from("identity-Delta"),
+                platform: None,
+            },
+        )
+        .await;
+        let delta = player(&tracker, "Delta").await;
+        assert_eq!(
+            delta.guid.as_deref(),
+            Some("fedcba9876543210fedcba9876543210")
+        );
+
+        // Both leave without it being logged.
+        tracker
+            .reconcile_server(server, Vec::new(), at(40))
+            .await
+            .unwrap();
+        assert!(sessions(&tracker)
+            .await
+            .iter()
+            .all(|session| session.left_at.is_some()));
+        assert_eq!(player(&tracker, "Delta").await.last_seen, at(40));
+        assert!(tracker.connected.is_empty());
+    }
+}
@@ -0,0 +1,48 @@
+use harm_entity::config::Model as ConfigModel;
+use harm_entity::mod_preset::{self, Entity as ModPresetEntity, Model as ModPresetModel};
+use harm_schemas::ModConfig;
+use sea_orm::{prelude::*, DatabaseConnection};
+
+/// Works out the mods a server loads: the mods of each of its presets in
+/// turn, without the ones it excludes, followed by its own mods. A mod is
+/// only loaded once; the server's own entry for a mod replaces a preset's,
+/// e.g. to pin a different version.
+pub fn effective_mods(cfg: &ConfigModel, presets: &[ModPresetModel]) -> Vec<ModConfig> {
+    let links = &cfg.mod_presets;
+    let mut mods: Vec<ModConfig> = Vec::new();
+
+    for id in &links.presets {
+        let Some(preset) = presets.iter().find(|preset| preset.id == *id) else {
+            continue;
+        };
+        for game_mod in &preset.mods.0 {
+            let excluded = links.excluded_mods.iter().any(|mod_id| game_mod.is(mod_id));
+            if !excluded && !mods.iter().any(|m| m.is(&game_mod.mod_id)) {
+                mods.push(game_mod.clone());
+            }
+        }
+    }
+
+    for game_mod in &cfg.config.game.mods {
+        match mods.iter_mut().find(|m| m.is(&game_mod.mod_id)) {
+            Some(existing) => *existing = game_mod.clone(),
+            None => mods.push(game_mod.clone()),
+        }
+    }
+
+    mods
+}
+
+/// Loads the presets a server links to.
+pub async fn linked_presets(
+    db: &DatabaseConnection,
+    cfg: &ConfigModel,
+) -> Result<Vec<ModPresetModel>, DbErr> {
+    if cfg.mod_presets.presets.is_empty() {
+        return Ok(Vec::new());
+    }
+    ModPresetEntity::find()
+        .filter(mod_preset::Column::Id.is_in(cfg.mod_presets.presets.clone()))
+        .all(db)
+        .await
+}
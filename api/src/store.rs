@@ -0,0 +1,66 @@
+use async_trait::async_trait;
+use harm_entity::process::{self, Entity as ProcessEntity};
+use harm_pm::store::{ProcessRecord, ProcessStore};
+use sea_orm::{prelude::*, sea_query::OnConflict};
+use uuid::Uuid;
+
+/// DbProcessStore persists the process manager's running processes to the
+/// `process` table.
+pub struct DbProcessStore {
+    db: DatabaseConnection,
+}
+
+impl DbProcessStore {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl ProcessStore for DbProcessStore {
+    async fn save(&self, record: ProcessRecord) -> anyhow::Result<()> {
+        ProcessEntity::insert(process::ActiveModel {
+            server_id: sea_orm::ActiveValue::Set(record.server_id),
+            pid: sea_orm::ActiveValue::Set(record.pid.into()),
+            start_time: sea_orm::ActiveValue::Set(record.start_time.map(|t| t as i64)),
+            started_at: sea_orm::ActiveValue::Set(record.started_at),
+            command_line: sea_orm::ActiveValue::Set(serde_json::to_value(record.command_line)?),
+        })
+        .on_conflict(
+            OnConflict::column(process::Column::ServerId)
+                .update_columns([
+                    process::Column::Pid,
+                    process::Column::StartTime,
+                    process::Column::StartedAt,
+                    process::Column::CommandLine,
+                ])
+                .to_owned(),
+        )
+        .exec(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn remove(&self, server_id: Uuid) -> anyhow::Result<()> {
+        ProcessEntity::delete_by_id(server_id)
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn load(&self) -> anyhow::Result<Vec<ProcessRecord>> {
+        let records = ProcessEntity::find().all(&self.db).await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| ProcessRecord {
+                server_id: record.server_id,
+                pid: record.pid as u32,
+                start_time: record.start_time.map(|t| t as u64),
+                started_at: record.started_at,
+                command_line: serde_json::from_value(record.command_line).unwrap_or_default(),
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,61 @@
+use std::{path::PathBuf, time::Duration};
+
+use chrono::Utc;
+use harm_entity::trash::{self, Entity as TrashEntity, Model as TrashModel};
+use harm_pm::manager::ProcessManager;
+use sea_orm::{prelude::*, DatabaseConnection};
+use slog::{info, warn, Logger};
+use tokio::task::JoinHandle;
+
+/// How long deleted servers stay in the trash unless told otherwise.
+pub const DEFAULT_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How often servers past their retention window are purged.
+pub const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Deletes a server from the trash for good, along with its data directory,
+/// and returns where its files were archived to, if they were.
+pub async fn purge(
+    db: &DatabaseConnection,
+    pm: &ProcessManager,
+    entry: &TrashModel,
+) -> anyhow::Result<Option<PathBuf>> {
+    // The files go first, so a failure leaves the entry to retry later.
+    let archived = pm.remove_data_dir(entry.id, entry.archive).await?;
+    TrashEntity::delete_by_id(entry.id).exec(db).await?;
+    Ok(archived)
+}
+
+/// Purges servers from the trash once their retention window has passed,
+/// checking every `interval`.
+pub fn spawn_purger(
+    db: DatabaseConnection,
+    pm: ProcessManager,
+    log: Logger,
+    interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+
+            let expired = match TrashEntity::find()
+                .filter(trash::Column::PurgeAt.lte(Utc::now()))
+                .all(&db)
+                .await
+            {
+                Ok(expired) => expired,
+                Err(e) => {
+                    warn!(log, "Could not load the trash: {}", e);
+                    continue;
+                }
+            };
+            for entry in expired {
+                match purge(&db, &pm, &entry).await {
+                    Ok(_) => info!(log, "Purged server {} from the trash", entry.id),
+                    Err(e) => warn!(log, "Could not purge server {}: {}", entry.id, e),
+                }
+            }
+        }
+    })
+}
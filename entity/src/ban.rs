@@ -0,0 +1,36 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "schemars")]
+extern crate schemars;
+
+/// A player banned from every server HARM manages.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "ban")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: uuid::Uuid,
+
+    /// The player's Reforger identity ID, which bans are enforced by.
+    #[sea_orm(unique)]
+    pub identity_id: String,
+
+    /// The player's name when they were banned.
+    pub name: Option<String>,
+
+    pub reason: Option<String>,
+
+    /// Who issued the ban.
+    pub issuer: Option<String>,
+
+    pub created_at: ChronoDateTimeUtc,
+
+    /// When the ban is lifted, or never if unset.
+    pub expires_at: Option<ChronoDateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -15,6 +15,27 @@ pub struct Model {
 
     #[sea_orm(json)]
     pub config: harm_schemas::ServerConfig,
+
+    #[sea_orm(json)]
+    pub restart_policy: harm_schemas::RestartPolicy,
+
+    #[sea_orm(json)]
+    pub launch_options: harm_schemas::LaunchOptions,
+
+    #[sea_orm(json)]
+    pub runtime: harm_schemas::RuntimeConfig,
+
+    #[sea_orm(json)]
+    pub resource_limits: harm_schemas::ResourceLimits,
+
+    #[sea_orm(json)]
+    pub readiness: harm_schemas::ReadinessConfig,
+
+    /// The presets the server loads mods from. Servers trashed before
+    /// presets existed have none.
+    #[sea_orm(json)]
+    #[serde(default)]
+    pub mod_presets: harm_schemas::ModPresetLinks,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -1 +1,7 @@
+pub mod ban;
 pub mod config;
+pub mod mod_preset;
+pub mod player;
+pub mod process;
+pub mod session;
+pub mod trash;
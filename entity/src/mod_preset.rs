@@ -0,0 +1,32 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "schemars")]
+extern crate schemars;
+
+/// A named, ordered list of mods which servers can load together.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "mod_preset")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: uuid::Uuid,
+
+    #[sea_orm(unique)]
+    pub name: String,
+
+    pub description: Option<String>,
+
+    /// The preset's mods, in load order.
+    #[sea_orm(json)]
+    pub mods: harm_schemas::ModList,
+
+    pub created_at: ChronoDateTimeUtc,
+
+    pub updated_at: ChronoDateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
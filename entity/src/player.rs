@@ -0,0 +1,48 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "schemars")]
+extern crate schemars;
+
+/// A player seen on any server HARM manages, identified by their Reforger
+/// identity.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "player")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: uuid::Uuid,
+
+    #[sea_orm(unique)]
+    pub identity_id: String,
+
+    /// The name the player was last seen with.
+    pub name: String,
+
+    /// Every name the player has been seen with, oldest first.
+    pub names: Json,
+
+    /// The player's BattlEye GUID, if BattlEye reported it.
+    pub guid: Option<String>,
+
+    /// The platform the player was last seen on, e.g. `PLATFORM_PC`.
+    pub platform: Option<String>,
+
+    pub first_seen: ChronoDateTimeUtc,
+
+    pub last_seen: ChronoDateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::session::Entity")]
+    Session,
+}
+
+impl Related<super::session::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Session.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,28 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "schemars")]
+extern crate schemars;
+
+/// A server process HARM has spawned and not yet seen exit, used to re-adopt
+/// it after HARM restarts.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "process")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub server_id: uuid::Uuid,
+
+    pub pid: i64,
+
+    pub start_time: Option<i64>,
+
+    pub started_at: ChronoDateTimeUtc,
+
+    pub command_line: Json,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
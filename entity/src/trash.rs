@@ -0,0 +1,35 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "schemars")]
+extern crate schemars;
+
+/// A deleted server, kept until `purge_at` so it can be restored. Its files
+/// stay in its data directory until it is purged.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "trash")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Model {
+    /// The deleted server's ID, which it keeps if restored.
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: uuid::Uuid,
+
+    pub title: String,
+
+    /// The server's `config` row, as it was when it was deleted.
+    pub server: Json,
+
+    /// Whether the server's files are archived rather than deleted when it
+    /// is purged.
+    pub archive: bool,
+
+    pub deleted_at: ChronoDateTimeUtc,
+
+    /// When the server is deleted for good.
+    pub purge_at: ChronoDateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
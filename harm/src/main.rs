@@ -1,4 +1,8 @@
+use std::{path::PathBuf, time::Duration};
+
 use clap::{Parser, Subcommand};
+use harm_pm::ports::PortPool;
+use harm_schemas::{ServerConfig, Severity};
 use serde_json::Value;
 use uuid::Uuid;
 
@@ -17,12 +21,39 @@ enum Command {
         #[clap(long, short = 'r')]
         /// Where Arma Reforger Server is installed
         reforger: String,
+
+        #[clap(long)]
+        /// Where HARM should keep each server's config, profile and logs.
+        /// Defaults to the platform's data directory.
+        data_dir: Option<PathBuf>,
+
+        #[clap(default_value = "5", long)]
+        /// How often to sample each running server's CPU and memory usage,
+        /// in seconds.
+        sample_interval: u64,
+
+        #[clap(long)]
+        /// A cgroup v2 directory delegated to HARM, under which locally run
+        /// servers with cgroup limits get their own group.
+        cgroup_root: Option<PathBuf>,
+
+        #[clap(default_value = "2001-2300", long)]
+        /// The range of ports new servers are given their game, A2S and RCON
+        /// ports from.
+        port_range: PortPool,
     },
 
     ExportConfig {
         #[clap(long, short)]
         id: Uuid,
     },
+
+    /// Check a server config file, as written by `export-config`, for
+    /// errors and warnings.
+    ValidateConfig {
+        /// The JSON file to check.
+        file: PathBuf,
+    },
 }
 
 #[derive(Parser)]
@@ -46,7 +77,22 @@ async fn main() -> Result<(), String> {
             port,
             database_url,
             reforger,
-        } => harm_api::start(*port, database_url.clone(), reforger.clone()).await,
+            data_dir,
+            sample_interval,
+            cgroup_root,
+            port_range,
+        } => {
+            harm_api::start(
+                *port,
+                database_url.clone(),
+                reforger.clone(),
+                data_dir.clone(),
+                Duration::from_secs(*sample_interval),
+                cgroup_root.clone(),
+                port_range.clone(),
+            )
+            .await
+        }
 
         Command::ExportConfig { id } => {
             let resp = reqwest::get(format!("http://localhost:10658/servers/{}", id))
@@ -62,5 +108,26 @@ async fn main() -> Result<(), String> {
 
             Ok(())
         }
+
+        Command::ValidateConfig { file } => {
+            let contents = std::fs::read_to_string(file)
+                .map_err(|e| format!("failed to read {}: {}", file.display(), e))?;
+            let config: ServerConfig = serde_json::from_str(&contents)
+                .map_err(|e| format!("failed to parse {}: {}", file.display(), e))?;
+
+            let report = config.validate();
+            for issue in &report.issues {
+                let severity = match issue.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                };
+                println!("{}: {}: {}", severity, issue.pointer, issue.message);
+            }
+
+            if !report.is_valid() {
+                return Err(format!("{} is invalid", file.display()));
+            }
+            Ok(())
+        }
     }
 }
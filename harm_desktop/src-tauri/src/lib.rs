@@ -1,4 +1,6 @@
 use config::AppConfig;
+use harm_pm::{ports::PortPool, resources::DEFAULT_SAMPLE_INTERVAL};
+use harm_schemas::{ServerConfig, ValidationReport};
 use tauri::{async_runtime::JoinHandle, AppHandle, Manager, State};
 
 mod config;
@@ -15,6 +17,11 @@ fn update_config(config: AppConfig) -> Result<(), String> {
         .map_err(|e| format!("Failed to update config: {}", e))
 }
 
+#[tauri::command]
+fn validate_server_config(config: ServerConfig) -> ValidationReport {
+    config.validate()
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -23,7 +30,8 @@ pub fn run() {
             start_api,
             stop_api,
             get_config,
-            update_config
+            update_config,
+            validate_server_config
         ])
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_store::Builder::default().build())
@@ -55,5 +63,14 @@ async fn stop_api(state: State<'_, JoinHandle<Result<(), String>>>) -> Result<()
 async fn _start_api(port: u16, reforger_path: String) -> Result<(), String> {
     let db_path = config::config_path();
     let db_url = format!("sqlite://{:?}?mode=rwc", db_path);
-    harm_api::start(port, db_url, reforger_path).await
+    harm_api::start(
+        port,
+        db_url,
+        reforger_path,
+        None,
+        DEFAULT_SAMPLE_INTERVAL,
+        None,
+        PortPool::default(),
+    )
+    .await
 }
@@ -1,12 +1,34 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
+mod m20250210_000001_add_restart_policy;
+mod m20250215_000001_create_process_table;
+mod m20250222_000001_add_launch_options;
+mod m20250301_000001_add_runtime;
+mod m20250308_000001_add_resource_limits;
+mod m20250315_000001_add_readiness;
+mod m20250322_000001_create_ban_table;
+mod m20250329_000001_create_player_tables;
+mod m20250405_000001_create_trash_table;
+mod m20250412_000001_add_mod_presets;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20250210_000001_add_restart_policy::Migration),
+            Box::new(m20250215_000001_create_process_table::Migration),
+            Box::new(m20250222_000001_add_launch_options::Migration),
+            Box::new(m20250301_000001_add_runtime::Migration),
+            Box::new(m20250308_000001_add_resource_limits::Migration),
+            Box::new(m20250315_000001_add_readiness::Migration),
+            Box::new(m20250322_000001_create_ban_table::Migration),
+            Box::new(m20250329_000001_create_player_tables::Migration),
+            Box::new(m20250405_000001_create_trash_table::Migration),
+            Box::new(m20250412_000001_add_mod_presets::Migration),
+        ]
     }
 }
@@ -0,0 +1,39 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Process::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(Process::ServerId))
+                    .col(big_integer(Process::Pid))
+                    .col(big_integer_null(Process::StartTime))
+                    .col(timestamp_with_time_zone(Process::StartedAt))
+                    .col(json(Process::CommandLine))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Process::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Process {
+    Table,
+    ServerId,
+    Pid,
+    StartTime,
+    StartedAt,
+    CommandLine,
+}
@@ -0,0 +1,43 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Ban::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(Ban::Id))
+                    .col(string_uniq(Ban::IdentityId))
+                    .col(string_null(Ban::Name))
+                    .col(string_null(Ban::Reason))
+                    .col(string_null(Ban::Issuer))
+                    .col(timestamp_with_time_zone(Ban::CreatedAt))
+                    .col(timestamp_with_time_zone_null(Ban::ExpiresAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Ban::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Ban {
+    Table,
+    Id,
+    IdentityId,
+    Name,
+    Reason,
+    Issuer,
+    CreatedAt,
+    ExpiresAt,
+}
@@ -0,0 +1,92 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Player::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(Player::Id))
+                    .col(string_uniq(Player::IdentityId))
+                    .col(string(Player::Name))
+                    .col(json(Player::Names))
+                    .col(string_null(Player::Guid))
+                    .col(string_null(Player::Platform))
+                    .col(timestamp_with_time_zone(Player::FirstSeen))
+                    .col(timestamp_with_time_zone(Player::LastSeen))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Session::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(Session::Id))
+                    .col(uuid(Session::PlayerId))
+                    .col(uuid(Session::ServerId))
+                    .col(string(Session::Name))
+                    .col(string_null(Session::Address))
+                    .col(timestamp_with_time_zone(Session::JoinedAt))
+                    .col(timestamp_with_time_zone_null(Session::LeftAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Session::Table, Session::PlayerId)
+                            .to(Player::Table, Player::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_session_player_id")
+                    .table(Session::Table)
+                    .col(Session::PlayerId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Session::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Player::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Player {
+    Table,
+    Id,
+    IdentityId,
+    Name,
+    Names,
+    Guid,
+    Platform,
+    FirstSeen,
+    LastSeen,
+}
+
+#[derive(DeriveIden)]
+enum Session {
+    Table,
+    Id,
+    PlayerId,
+    ServerId,
+    Name,
+    Address,
+    JoinedAt,
+    LeftAt,
+}
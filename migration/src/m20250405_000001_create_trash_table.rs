@@ -0,0 +1,41 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Trash::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(Trash::Id))
+                    .col(string(Trash::Title))
+                    .col(json(Trash::Server))
+                    .col(boolean(Trash::Archive))
+                    .col(timestamp_with_time_zone(Trash::DeletedAt))
+                    .col(timestamp_with_time_zone(Trash::PurgeAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Trash::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Trash {
+    Table,
+    Id,
+    Title,
+    Server,
+    Archive,
+    DeletedAt,
+    PurgeAt,
+}
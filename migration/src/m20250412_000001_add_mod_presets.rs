@@ -0,0 +1,67 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ModPreset::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(ModPreset::Id))
+                    .col(string_uniq(ModPreset::Name))
+                    .col(string_null(ModPreset::Description))
+                    .col(json(ModPreset::Mods))
+                    .col(timestamp_with_time_zone(ModPreset::CreatedAt))
+                    .col(timestamp_with_time_zone(ModPreset::UpdatedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Config::Table)
+                    .add_column(
+                        json(Config::ModPresets).default(r#"{"presets":[],"excludedMods":[]}"#),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Config::Table)
+                    .drop_column(Config::ModPresets)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ModPreset::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ModPreset {
+    Table,
+    Id,
+    Name,
+    Description,
+    Mods,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Config {
+    Table,
+    ModPresets,
+}
@@ -0,0 +1,569 @@
+use std::{collections::BTreeMap, net::SocketAddr, time::Duration};
+
+use anyhow::{Error, Result};
+use chrono::{DateTime, Utc};
+use harm_schemas::A2SConfig;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+
+use crate::ports;
+
+/// How often running servers are queried by default.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a query waits for each packet of a reply by default.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How many challenges a server may answer a query with before giving up.
+const MAX_CHALLENGES: usize = 3;
+
+/// The largest datagram a server may send.
+const MAX_PACKET_SIZE: usize = 1400;
+
+/// The header of a reply which fits in a single packet.
+const SINGLE_PACKET: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+
+/// The header of each packet of a reply split over several packets.
+const MULTI_PACKET: [u8; 4] = [0xFE, 0xFF, 0xFF, 0xFF];
+
+const A2S_INFO: u8 = b'T';
+const A2S_PLAYER: u8 = b'U';
+const A2S_RULES: u8 = b'V';
+const S2A_INFO: u8 = b'I';
+const S2A_PLAYER: u8 = b'D';
+const S2A_RULES: u8 = b'E';
+const S2C_CHALLENGE: u8 = b'A';
+
+/// A server's reply to A2S_INFO.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerInfo {
+    pub protocol: u8,
+    pub name: String,
+    pub map: String,
+    pub folder: String,
+    pub game: String,
+    pub app_id: u16,
+    pub players: u8,
+    pub max_players: u8,
+    pub bots: u8,
+    /// `d` for a dedicated server, `l` for a listen server.
+    pub server_type: char,
+    /// `l` for Linux, `w` for Windows, `m` for macOS.
+    pub environment: char,
+    /// Whether the server needs a password to join.
+    pub password: bool,
+    pub vac: bool,
+    pub version: String,
+    /// The game port, if the server reports it.
+    pub port: Option<u16>,
+    pub steam_id: Option<u64>,
+    pub keywords: Option<String>,
+    pub game_id: Option<u64>,
+}
+
+/// A player in a server's reply to A2S_PLAYER.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Player {
+    pub index: u8,
+    pub name: String,
+    pub score: i32,
+    /// How long the player has been connected, in seconds.
+    pub duration: f32,
+}
+
+/// The result of the latest poll of a running server.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryStatus {
+    /// When the server was queried.
+    pub at: DateTime<Utc>,
+    pub info: Option<ServerInfo>,
+    pub players: Vec<Player>,
+    /// The server's rules, if it answered A2S_RULES.
+    pub rules: Option<BTreeMap<String, String>>,
+    /// Why the server couldn't be queried, if it couldn't.
+    pub error: Option<String>,
+}
+
+/// A client for Valve's A2S server query protocol, which Reforger answers
+/// on its A2S port.
+#[derive(Clone, Debug)]
+pub struct A2sClient {
+    address: SocketAddr,
+    timeout: Duration,
+}
+
+impl A2sClient {
+    pub fn new(address: SocketAddr) -> Self {
+        Self {
+            address,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Creates a client for the A2S endpoint in a server config. Servers
+    /// bound to every address are queried over loopback.
+    pub fn for_config(config: &A2SConfig) -> Self {
+        Self::new(SocketAddr::new(
+            ports::local_address(&config.address),
+            config.port,
+        ))
+    }
+
+    /// Sets how long to wait for each packet of a reply.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn info(&self) -> Result<ServerInfo> {
+        let reply = self
+            .query(A2S_INFO, b"Source Engine Query\0", None, S2A_INFO)
+            .await?;
+        parse_info(&mut Reader::new(&reply))
+    }
+
+    pub async fn players(&self) -> Result<Vec<Player>> {
+        let reply = self
+            .query(A2S_PLAYER, &[], Some(SINGLE_PACKET), S2A_PLAYER)
+            .await?;
+        let mut reader = Reader::new(&reply);
+        let count = reader.u8()?;
+        (0..count)
+            .map(|_| {
+                Ok(Player {
+                    index: reader.u8()?,
+                    name: reader.string()?,
+                    score: reader.i32()?,
+                    duration: reader.f32()?,
+                })
+            })
+            .collect()
+    }
+
+    /// Queries the server's rules: the key-value pairs it publishes about
+    /// itself, such as its mods or game mode.
+    pub async fn rules(&self) -> Result<BTreeMap<String, String>> {
+        let reply = self
+            .query(A2S_RULES, &[], Some(SINGLE_PACKET), S2A_RULES)
+            .await?;
+        let mut reader = Reader::new(&reply);
+        let count = reader.u16()?;
+        (0..count)
+            .map(|_| Ok((reader.string()?, reader.string()?)))
+            .collect()
+    }
+
+    /// Queries the server and returns the body of its reply, without the
+    /// `expected` header byte. If the server answers with a challenge, the
+    /// query is sent again with the challenge appended. `challenge` is the
+    /// challenge sent with the first attempt, for queries which need one.
+    async fn query(
+        &self,
+        kind: u8,
+        body: &[u8],
+        challenge: Option<[u8; 4]>,
+        expected: u8,
+    ) -> Result<Vec<u8>> {
+        let socket = UdpSocket::bind(match self.address {
+            SocketAddr::V4(_) => "0.0.0.0:0",
+            SocketAddr::V6(_) => "[::]:0",
+        })
+        .await?;
+        socket.connect(self.address).await?;
+
+        let mut request = SINGLE_PACKET.to_vec();
+        request.push(kind);
+        request.extend_from_slice(body);
+        let mut packet = request.clone();
+        packet.extend(challenge.iter().flatten());
+
+        for _ in 0..MAX_CHALLENGES {
+            socket.send(&packet).await?;
+            let reply = self.receive(&socket).await?;
+            match reply.first() {
+                Some(header) if *header == expected => return Ok(reply[1..].to_vec()),
+                Some(&S2C_CHALLENGE) if reply.len() >= 5 => {
+                    packet = request.clone();
+                    packet.extend_from_slice(&reply[1..5]);
+                }
+                _ => return Err(Error::msg("The server sent an unexpected reply.")),
+            }
+        }
+
+        Err(Error::msg("The server kept answering with challenges."))
+    }
+
+    /// Receives a reply, reassembling it if it is split over several
+    /// packets, and returns it without its header.
+    async fn receive(&self, socket: &UdpSocket) -> Result<Vec<u8>> {
+        let mut parts = BTreeMap::new();
+        let mut reply_id = None;
+        let mut buf = vec![0; MAX_PACKET_SIZE];
+
+        loop {
+            let len = tokio::time::timeout(self.timeout, socket.recv(&mut buf))
+                .await
+                .map_err(|_| Error::msg("The server did not answer in time."))??;
+            let packet = &buf[..len];
+
+            if let Some(reply) = packet.strip_prefix(&SINGLE_PACKET) {
+                return Ok(reply.to_vec());
+            }
+            let Some(packet) = packet.strip_prefix(&MULTI_PACKET) else {
+                return Err(Error::msg("The server sent a malformed packet."));
+            };
+
+            let mut reader = Reader::new(packet);
+            let id = reader.u32()?;
+            if id & 0x8000_0000 != 0 {
+                return Err(Error::msg("Compressed replies are not supported."));
+            }
+            // Every packet of a reply carries its ID, so packets of another
+            // reply, e.g. a late one to an earlier query, aren't mixed in.
+            if *reply_id.get_or_insert(id) != id {
+                return Err(Error::msg(
+                    "The server sent packets from more than one reply.",
+                ));
+            }
+            let total = reader.u8()?;
+            let number = reader.u8()?;
+            if number >= total {
+                return Err(Error::msg("The server sent a malformed packet."));
+            }
+            let _size = reader.u16()?;
+            parts.insert(number, reader.rest().to_vec());
+
+            if parts.len() >= total as usize {
+                let reply = parts.into_values().flatten().collect::<Vec<_>>();
+                return match reply.strip_prefix(&SINGLE_PACKET) {
+                    Some(reply) => Ok(reply.to_vec()),
+                    None => Err(Error::msg("The server sent a malformed packet.")),
+                };
+            }
+        }
+    }
+}
+
+fn parse_info(reader: &mut Reader) -> Result<ServerInfo> {
+    let mut info = ServerInfo {
+        protocol: reader.u8()?,
+        name: reader.string()?,
+        map: reader.string()?,
+        folder: reader.string()?,
+        game: reader.string()?,
+        app_id: reader.u16()?,
+        players: reader.u8()?,
+        max_players: reader.u8()?,
+        bots: reader.u8()?,
+        server_type: reader.u8()? as char,
+        environment: reader.u8()? as char,
+        password: reader.u8()? != 0,
+        vac: reader.u8()? != 0,
+        version: reader.string()?,
+        port: None,
+        steam_id: None,
+        keywords: None,
+        game_id: None,
+    };
+
+    // The extra data flag says which optional fields follow.
+    let Ok(flags) = reader.u8() else {
+        return Ok(info);
+    };
+    if flags & 0x80 != 0 {
+        info.port = Some(reader.u16()?);
+    }
+    if flags & 0x10 != 0 {
+        info.steam_id = Some(reader.u64()?);
+    }
+    if flags & 0x40 != 0 {
+        // The SourceTV port and name.
+        reader.u16()?;
+        reader.string()?;
+    }
+    if flags & 0x20 != 0 {
+        info.keywords = Some(reader.string()?);
+    }
+    if flags & 0x01 != 0 {
+        info.game_id = Some(reader.u64()?);
+    }
+
+    Ok(info)
+}
+
+/// Reads the little-endian values A2S replies are made of.
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.buf.len() < N {
+            return Err(Error::msg("The server's reply ended early."));
+        }
+        let (value, rest) = self.buf.split_at(N);
+        self.buf = rest;
+        Ok(value.try_into()?)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.take()?))
+    }
+
+    /// Reads a null-terminated string, replacing invalid UTF-8.
+    fn string(&mut self) -> Result<String> {
+        let Some(end) = self.buf.iter().position(|b| *b == 0) else {
+            return Err(Error::msg("The server's reply ended early."));
+        };
+        let value = String::from_utf8_lossy(&self.buf[..end]).into_owned();
+        self.buf = &self.buf[end + 1..];
+        Ok(value)
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    const CHALLENGE: [u8; 4] = [0x12, 0x34, 0x56, 0x78];
+
+    /// How a FakeResponder sends its replies.
+    #[derive(Clone, Copy, Default)]
+    struct Behaviour {
+        /// Split replies into packets with this many bytes of payload, sent
+        /// in reverse order.
+        split: Option<usize>,
+        /// Slip a packet from another reply in between the packets of a
+        /// split reply.
+        interleave: bool,
+    }
+
+    /// A local A2S server, which answers every query with a challenge first,
+    /// as Reforger does.
+    struct FakeResponder {
+        address: SocketAddr,
+    }
+
+    impl FakeResponder {
+        async fn spawn(behaviour: Behaviour) -> Self {
+            let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+            let address = socket.local_addr().unwrap();
+            tokio::spawn(async move {
+                let mut buf = [0; MAX_PACKET_SIZE];
+                while let Ok((len, from)) = socket.recv_from(&mut buf).await {
+                    for packet in respond(&buf[..len], behaviour) {
+                        socket.send_to(&packet, from).await.unwrap();
+                    }
+                }
+            });
+            Self { address }
+        }
+
+        fn client(&self) -> A2sClient {
+            A2sClient::new(self.address).with_timeout(Duration::from_millis(500))
+        }
+    }
+
+    /// The packets sent in answer to `request`.
+    fn respond(request: &[u8], behaviour: Behaviour) -> Vec<Vec<u8>> {
+        let Some(request) = request.strip_prefix(&SINGLE_PACKET) else {
+            return Vec::new();
+        };
+        let (kind, rest) = request.split_first().unwrap();
+        let (body, challenge) = match *kind {
+            A2S_INFO => {
+                let rest = rest.strip_prefix(b"Source Engine Query\0").unwrap();
+                (info_reply(), rest)
+            }
+            A2S_PLAYER => (players_reply(), rest),
+            A2S_RULES => (rules_reply(), rest),
+            _ => return Vec::new(),
+        };
+        if challenge != CHALLENGE {
+            let mut packet = SINGLE_PACKET.to_vec();
+            packet.push(S2C_CHALLENGE);
+            packet.extend_from_slice(&CHALLENGE);
+            return vec![packet];
+        }
+
+        let mut reply = SINGLE_PACKET.to_vec();
+        reply.extend(body);
+        let Some(size) = behaviour.split else {
+            return vec![reply];
+        };
+        let mut packets = split(&reply, size, 7);
+        packets.reverse();
+        if behaviour.interleave {
+            let stray = split(&reply, size, 8).remove(0);
+            packets.insert(1, stray);
+        }
+        packets
+    }
+
+    /// Splits a reply into multi-packet packets with reply ID `id`.
+    fn split(reply: &[u8], size: usize, id: u32) -> Vec<Vec<u8>> {
+        let chunks: Vec<_> = reply.chunks(size).collect();
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(number, chunk)| {
+                let mut packet = MULTI_PACKET.to_vec();
+                packet.extend(id.to_le_bytes());
+                packet.push(chunks.len() as u8);
+                packet.push(number as u8);
+                packet.extend((MAX_PACKET_SIZE as u16).to_le_bytes());
+                packet.extend_from_slice(chunk);
+                packet
+            })
+            .collect()
+    }
+
+    fn string(buf: &mut Vec<u8>, value: &str) {
+        buf.extend_from_slice(value.as_bytes());
+        buf.push(0);
+    }
+
+    fn info_reply() -> Vec<u8> {
+        let mut buf = vec![S2A_INFO, 17];
+        string(&mut buf, "Test server");
+        string(&mut buf, "Everon");
+        string(&mut buf, "arma_reforger");
+        string(&mut buf, "Arma Reforger");
+        buf.extend(0u16.to_le_bytes());
+        buf.extend([3, 64, 0, b'd', b'l', 1, 0]);
+        string(&mut buf, "1.2.0.102");
+        buf.push(0x80 | 0x20);
+        buf.extend(2001u16.to_le_bytes());
+        string(&mut buf, "keywords");
+        buf
+    }
+
+    fn players_reply() -> Vec<u8> {
+        let mut buf = vec![S2A_PLAYER, 2];
+        for (index, name) in [(0u8, "Alpha"), (1, "Bravo")] {
+            buf.push(index);
+            string(&mut buf, name);
+            buf.extend(10i32.to_le_bytes());
+            buf.extend(42.5f32.to_le_bytes());
+        }
+        buf
+    }
+
+    fn rules_reply() -> Vec<u8> {
+        let mut buf = vec![S2A_RULES];
+        buf.extend(2u16.to_le_bytes());
+        for (key, value) in [("gameMode", "Conflict"), ("mods", "2")] {
+            string(&mut buf, key);
+            string(&mut buf, value);
+        }
+        buf
+    }
+
+    #[tokio::test]
+    async fn queries_info_after_a_challenge() {
+        let responder = FakeResponder::spawn(Behaviour::default()).await;
+        let info = responder.client().info().await.unwrap();
+
+        assert_eq!(info.name, "Test server");
+        assert_eq!(info.map, "Everon");
+        assert_eq!(info.players, 3);
+        assert_eq!(info.max_players, 64);
+        assert_eq!(info.server_type, 'd');
+        assert!(info.password);
+        assert_eq!(info.version, "1.2.0.102");
+        assert_eq!(info.port, Some(2001));
+        assert_eq!(info.keywords.as_deref(), Some("keywords"));
+        assert_eq!(info.steam_id, None);
+    }
+
+    #[tokio::test]
+    async fn queries_players() {
+        let responder = FakeResponder::spawn(Behaviour::default()).await;
+        let players = responder.client().players().await.unwrap();
+
+        let names: Vec<_> = players.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Alpha", "Bravo"]);
+        assert_eq!(players[1].index, 1);
+        assert_eq!(players[1].score, 10);
+        assert_eq!(players[1].duration, 42.5);
+    }
+
+    #[tokio::test]
+    async fn queries_rules() {
+        let responder = FakeResponder::spawn(Behaviour::default()).await;
+        let rules = responder.client().rules().await.unwrap();
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules["gameMode"], "Conflict");
+        assert_eq!(rules["mods"], "2");
+    }
+
+    #[tokio::test]
+    async fn reassembles_split_replies() {
+        let responder = FakeResponder::spawn(Behaviour {
+            split: Some(16),
+            interleave: false,
+        })
+        .await;
+        let client = responder.client();
+
+        assert_eq!(client.info().await.unwrap().name, "Test server");
+        assert_eq!(client.players().await.unwrap().len(), 2);
+        assert_eq!(client.rules().await.unwrap()["gameMode"], "Conflict");
+    }
+
+    #[tokio::test]
+    async fn rejects_packets_from_another_reply() {
+        let responder = FakeResponder::spawn(Behaviour {
+            split: Some(16),
+            interleave: true,
+        })
+        .await;
+        let error = responder.client().info().await.unwrap_err();
+
+        assert!(error.to_string().contains("more than one reply"));
+    }
+
+    #[tokio::test]
+    async fn times_out_without_an_answer() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client =
+            A2sClient::new(socket.local_addr().unwrap()).with_timeout(Duration::from_millis(50));
+
+        assert!(client.info().await.is_err());
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use tokio::{
+    fs,
+    sync::{broadcast::error::RecvError, mpsc},
+    task::JoinHandle,
+};
+use uuid::Uuid;
+
+use crate::{
+    events::{EventBus, EventKind},
+    logs::{self, LogBuffer, LogStream},
+};
+
+/// How often the profile is checked for the log directory of a new run.
+const PROFILE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How much older than the start of a run its log directory may appear.
+const CLOCK_SLACK: Duration = Duration::from_secs(1);
+
+/// How many recent lines are remembered to drop lines seen in both the
+/// console output and the profile's log file.
+const DEDUP_WINDOW: usize = 256;
+
+/// Where a line of Reforger's console log was read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Source {
+    /// The process's stdout or stderr.
+    Output,
+    /// The `console.log` file Reforger writes in its profile.
+    Profile,
+}
+
+/// Parses a line of Reforger's console log into an event, if it describes
+/// one. Lines look like `12:34:56.789 CATEGORY (E): message`, where the
+/// timestamp and severity are optional.
+pub fn parse(line: &str) -> Option<EventKind> {
+    let line = strip_clock(line.trim());
+    let (category, severity, message) = match line.split_once(':') {
+        Some((header, message)) if is_header(header) => {
+            let header = header.trim();
+            let (category, severity) = match header.split_once('(') {
+                Some((category, severity)) => {
+                    (category.trim(), severity.trim_end_matches(')').trim())
+                }
+                None => (header, ""),
+            };
+            (category, severity, message.trim())
+        }
+        _ => ("", "", line),
+    };
+
+    if let Some(event) = parse_battleye(message) {
+        return Some(event);
+    }
+    if let Some(rest) = message.strip_prefix("### Updating player:") {
+        let fields = key_values(rest);
+        return Some(EventKind::PlayerIdentity {
+            player_id: fields.get("PlayerId")?.parse().ok()?,
+            name: fields.get("Name")?.to_string(),
+            identity_id: fields.get("IdentityId")?.to_string(),
+            platform: fields.get("Platform").map(|platform| platform.to_string()),
+        });
+    }
+    if let Some(address) = message.strip_prefix("Server registered with address:") {
+        return Some(EventKind::ServerRegistered {
+            address: address.trim().to_string(),
+        });
+    }
+    if message.starts_with("Game successfully created") {
+        return Some(EventKind::GameCreated);
+    }
+    if message.starts_with("Loading") {
+        if let Some(scenario_id) = find_scenario(message) {
+            return Some(EventKind::ScenarioLoading { scenario_id });
+        }
+    }
+    if [
+        "Game destroyed",
+        "Application shutdown",
+        "Server shutting down",
+    ]
+    .iter()
+    .any(|prefix| message.starts_with(prefix))
+    {
+        return Some(EventKind::Shutdown {
+            message: message.to_string(),
+        });
+    }
+    if matches!(severity, "E" | "F") {
+        return Some(EventKind::Error {
+            category: category.to_string(),
+            message: message.to_string(),
+            fatal: severity == "F",
+        });
+    }
+
+    None
+}
+
+/// Strips a leading `HH:MM:SS.mmm` timestamp.
+fn strip_clock(line: &str) -> &str {
+    let bytes = line.as_bytes();
+    let is_clock = bytes.len() >= 12
+        && bytes[..12].iter().enumerate().all(|(i, b)| match i {
+            2 | 5 => *b == b':',
+            8 => *b == b'.',
+            _ => b.is_ascii_digit(),
+        });
+    match is_clock {
+        true => line[12..].trim_start(),
+        false => line,
+    }
+}
+
+/// Whether `header` looks like a category and optional severity, e.g.
+/// `SCRIPT    (E)`.
+fn is_header(header: &str) -> bool {
+    let header = header.trim();
+    !header.is_empty()
+        && header.len() <= 24
+        && header.chars().all(|c| {
+            c.is_ascii_uppercase() || c.is_ascii_digit() || matches!(c, '_' | ' ' | '(' | ')')
+        })
+}
+
+/// Parses BattlEye's notices, e.g. `BattlEye Server: 'Player #0 Name
+/// (1.2.3.4:5678) connected'`.
+fn parse_battleye(message: &str) -> Option<EventKind> {
+    let notice = message
+        .strip_prefix("BattlEye Server:")?
+        .trim()
+        .trim_matches('\'');
+    let rest = notice.strip_prefix("Player #")?;
+    let (player_id, rest) = rest.split_once(' ')?;
+    let player_id = player_id.parse().ok()?;
+
+    if let Some((name, guid)) = rest.split_once(" - BE GUID: ") {
+        return Some(EventKind::PlayerGuid {
+            player_id,
+            name: name.to_string(),
+            guid: guid.trim().to_string(),
+        });
+    }
+    if let Some((name, reason)) = rest.split_once(" has been kicked by BattlEye: ") {
+        return Some(EventKind::PlayerKicked {
+            player_id,
+            name: name.to_string(),
+            reason: reason.trim().to_string(),
+        });
+    }
+    if let Some(name) = rest.strip_suffix(" disconnected") {
+        return Some(EventKind::PlayerDisconnected {
+            player_id,
+            name: name.to_string(),
+        });
+    }
+    if let Some(rest) = rest.strip_suffix(" connected") {
+        // The address is in brackets after the name, which may itself
+        // contain brackets.
+        let (name, address) = match rest.rsplit_once(" (") {
+            Some((name, address)) if address.ends_with(')') => {
+                (name, Some(address.trim_end_matches(')').to_string()))
+            }
+            _ => (rest, None),
+        };
+        return Some(EventKind::PlayerConnected {
+            player_id,
+            name: name.to_string(),
+            address,
+        });
+    }
+
+    None
+}
+
+/// Parses `Key=value, Key=value` pairs.
+fn key_values(fields: &str) -> HashMap<&str, &str> {
+    fields
+        .split(',')
+        .filter_map(|field| field.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect()
+}
+
+/// Finds a scenario resource like `{ECC61978EDCC2B5A}Missions/23_Campaign.conf`.
+fn find_scenario(message: &str) -> Option<String> {
+    let start = message.find('{')?;
+    let resource = message[start..]
+        .split(|c: char| c.is_whitespace() || c == '\'' || c == '"')
+        .next()?;
+    resource.ends_with(".conf").then(|| resource.to_string())
+}
+
+/// The console log parser of a single server. It parses every line of the
+/// server's console output, and of the `console.log` Reforger writes in its
+/// profile, and publishes the events found on the event bus. Every line of
+/// console output is published as well.
+///
+/// Reforger writes most lines to both, so a line seen in one is dropped when
+/// it is seen in the other soon after.
+#[derive(Debug)]
+pub struct ConsoleParser {
+    profile_tx: mpsc::UnboundedSender<String>,
+    task: JoinHandle<()>,
+    follower: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ConsoleParser {
+    /// Starts parsing the output captured in `logs` for server `id`.
+    pub fn spawn(id: Uuid, logs: &LogBuffer, events: EventBus) -> Self {
+        let mut output_rx = logs.subscribe(0).receiver;
+        let (profile_tx, mut profile_rx) = mpsc::unbounded_channel();
+
+        let task = tokio::spawn(async move {
+            let mut recent = VecDeque::with_capacity(DEDUP_WINDOW);
+            loop {
+                let (source, line) = tokio::select! {
+                    line = output_rx.recv() => match line {
+                        Ok(line) => {
+                            events.publish(id, EventKind::Log {
+                                stream: line.stream,
+                                line: line.line.clone(),
+                            });
+                            (Source::Output, line.line)
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return,
+                    },
+                    Some(line) = profile_rx.recv() => (Source::Profile, line),
+                };
+
+                if is_duplicate(&mut recent, source, &line) {
+                    continue;
+                }
+                if let Some(kind) = parse(&line) {
+                    events.publish(id, kind);
+                }
+            }
+        });
+
+        Self {
+            profile_tx,
+            task,
+            follower: Mutex::new(None),
+        }
+    }
+
+    /// Follows the `console.log` of the newest run in a profile's `logs`
+    /// directory, replacing the file followed before. If `from_end` is set,
+    /// the newest run's log is followed from its end, as when re-adopting a
+    /// server. Otherwise a run started after now is waited for.
+    pub fn follow_profile(&self, profile_logs: PathBuf, from_end: bool) {
+        // File timestamps are coarser than the clock, so a directory created
+        // right after this can appear a little older.
+        let since = SystemTime::now() - CLOCK_SLACK;
+        let tx = self.profile_tx.clone();
+        let follower = tokio::spawn(async move {
+            // Reforger creates the run's directory a while before its log.
+            let path = loop {
+                if let Some(dir) = newest_run(&profile_logs, (!from_end).then_some(since)).await {
+                    let path = dir.join("console.log");
+                    if fs::try_exists(&path).await.unwrap_or(false) {
+                        break path;
+                    }
+                }
+                if tx.is_closed() {
+                    return;
+                }
+                tokio::time::sleep(PROFILE_POLL_INTERVAL).await;
+            };
+
+            let offset = match from_end {
+                true => fs::metadata(&path).await.map_or(0, |m| m.len()),
+                false => 0,
+            };
+            let buffer = Arc::new(LogBuffer::new(DEDUP_WINDOW));
+            let mut lines = buffer.subscribe(0).receiver;
+            // Dropping the tailer when this task is aborted stops it.
+            let _tailer = logs::spawn_tailer(buffer, LogStream::Stdout, path, offset);
+
+            loop {
+                match lines.recv().await {
+                    Ok(line) => {
+                        if tx.send(line.line).is_err() {
+                            return;
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
+
+        if let Some(previous) = self.follower.lock().unwrap().replace(follower) {
+            previous.abort();
+        }
+    }
+}
+
+impl Drop for ConsoleParser {
+    fn drop(&mut self) {
+        self.task.abort();
+        if let Some(follower) = self.follower.lock().unwrap().take() {
+            follower.abort();
+        }
+    }
+}
+
+/// Returns the newest run directory in a profile's `logs` directory, e.g.
+/// `logs_2025-03-15_12-00-00`, optionally only if it was created after
+/// `since`.
+async fn newest_run(profile_logs: &Path, since: Option<SystemTime>) -> Option<PathBuf> {
+    let mut entries = fs::read_dir(profile_logs).await.ok()?;
+    let mut newest: Option<(SystemTime, PathBuf)> = None;
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        if !metadata.is_dir() {
+            continue;
+        }
+        let created = metadata
+            .created()
+            .or_else(|_| metadata.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        if since.is_some_and(|since| created < since) {
+            continue;
+        }
+        if newest.as_ref().is_none_or(|(time, _)| created > *time) {
+            newest = Some((created, entry.path()));
+        }
+    }
+
+    newest.map(|(_, path)| path)
+}
+
+/// Whether `line` was recently seen from the other source. Lines are
+/// remembered until they are matched or pushed out of the window.
+fn is_duplicate(recent: &mut VecDeque<(Source, String)>, source: Source, line: &str) -> bool {
+    let line = line.trim_end();
+    if let Some(i) = recent
+        .iter()
+        .position(|(seen, seen_line)| *seen != source && seen_line == line)
+    {
+        recent.remove(i);
+        return true;
+    }
+
+    if recent.len() >= DEDUP_WINDOW {
+        recent.pop_front();
+    }
+    recent.push_back((source, line.to_string()));
+    false
+}
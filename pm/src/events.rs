@@ -0,0 +1,309 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::{logs::LogStream, manager::ServerState, resources::ResourceSample};
+
+/// How many events a subscriber may fall behind before it starts missing
+/// them.
+pub const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+/// How many recent events are kept for subscribers resuming from a cursor.
+pub const DEFAULT_EVENT_HISTORY: usize = 4096;
+
+/// Something which happened on a server, as published on the EventBus.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerEvent {
+    /// Monotonically increasing sequence number, unique across every server.
+    pub seq: u64,
+    pub server_id: Uuid,
+    /// When HARM saw the event happen.
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum EventKind {
+    /// A player joined, as reported by BattlEye.
+    PlayerConnected {
+        /// The player's ID for this session of the server.
+        player_id: u32,
+        name: String,
+        /// The address and port the player connected from.
+        address: Option<String>,
+    },
+
+    /// BattlEye worked out a connected player's GUID.
+    PlayerGuid {
+        player_id: u32,
+        name: String,
+        guid: String,
+    },
+
+    /// Reforger worked out a connected player's platform identity.
+    PlayerIdentity {
+        player_id: u32,
+        name: String,
+        identity_id: String,
+        /// The platform the player plays on, e.g. `PLATFORM_PC`, if the log
+        /// says.
+        platform: Option<String>,
+    },
+
+    /// A player left or lost their connection.
+    PlayerDisconnected { player_id: u32, name: String },
+
+    /// BattlEye kicked a player.
+    PlayerKicked {
+        player_id: u32,
+        name: String,
+        reason: String,
+    },
+
+    /// The server started loading a scenario.
+    ScenarioLoading {
+        /// The scenario's resource, e.g. `{ECC61978EDCC2B5A}Missions/23_Campaign.conf`.
+        scenario_id: String,
+    },
+
+    /// The game world was created.
+    GameCreated,
+
+    /// The server registered itself with the backend and can be joined.
+    ServerRegistered { address: String },
+
+    /// An error was logged, e.g. by a script.
+    Error {
+        /// The subsystem which logged the error, e.g. `SCRIPT`.
+        category: String,
+        message: String,
+        /// Whether the error was fatal to the server.
+        fatal: bool,
+    },
+
+    /// The server started shutting down.
+    Shutdown { message: String },
+
+    /// The server sent a message over RCON, such as chat or a BattlEye
+    /// notice.
+    RconMessage { message: String },
+
+    /// HARM logged in to the server's RCON, e.g. after it started or
+    /// restarted.
+    RconConnected,
+
+    /// HARM lost its RCON connection to the server.
+    RconDisconnected,
+
+    /// The process manager moved the server to a new state.
+    StateChanged { state: ServerState },
+
+    /// The server printed a line of console output.
+    Log { stream: LogStream, line: String },
+
+    /// The server's resource usage was sampled.
+    Resources { sample: ResourceSample },
+}
+
+/// The result of subscribing to events from a cursor: the events published
+/// after the cursor which are still remembered, followed by every event
+/// published afterwards.
+///
+/// The receiver never yields an event already present in `backlog`.
+#[derive(Debug)]
+pub struct EventSubscription {
+    pub backlog: Vec<ServerEvent>,
+    /// How many events published after the cursor were forgotten before the
+    /// subscription was made, and so are in neither `backlog` nor `receiver`.
+    pub missed: u64,
+    /// Whether the cursor was never handed out by this EventBus, e.g.
+    /// because it came from before HARM restarted. The backlog then starts
+    /// from the oldest event remembered.
+    pub reset: bool,
+    pub receiver: broadcast::Receiver<ServerEvent>,
+}
+
+#[derive(Debug)]
+struct History {
+    events: VecDeque<ServerEvent>,
+    capacity: usize,
+    next_seq: u64,
+}
+
+/// EventBus fans events from every server out to any number of subscribers,
+/// and remembers the most recent ones so subscribers can resume from where
+/// they left off. Cloning an EventBus is cheap, and every clone publishes to
+/// the same subscribers.
+#[derive(Clone, Debug)]
+pub struct EventBus {
+    sender: broadcast::Sender<ServerEvent>,
+    history: Arc<Mutex<History>>,
+}
+
+impl EventBus {
+    /// Creates an EventBus which lets subscribers fall `capacity` events
+    /// behind, and remembers the last `history` events.
+    pub fn new(capacity: usize, history: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self {
+            sender,
+            history: Arc::new(Mutex::new(History {
+                events: VecDeque::with_capacity(history),
+                capacity: history,
+                next_seq: 0,
+            })),
+        }
+    }
+
+    /// Publishes an event to every current subscriber and returns it.
+    pub fn publish(&self, server_id: Uuid, kind: EventKind) -> ServerEvent {
+        // Events are numbered, remembered and sent under the lock so every
+        // subscriber sees them in order, and none falls between the history
+        // and the receiver of `subscribe_since`.
+        let mut history = self.history.lock().unwrap();
+        let event = ServerEvent {
+            seq: history.next_seq,
+            server_id,
+            timestamp: Utc::now(),
+            kind,
+        };
+        history.next_seq += 1;
+
+        if history.capacity > 0 {
+            if history.events.len() >= history.capacity {
+                history.events.pop_front();
+            }
+            history.events.push_back(event.clone());
+        }
+
+        // Sending only fails when nobody is subscribed, which is fine.
+        let _ = self.sender.send(event.clone());
+        event
+    }
+
+    /// Subscribes to every event published after the event numbered
+    /// `cursor`, or from now on if there is no cursor. A cursor this
+    /// EventBus hasn't reached yet is treated as unknown, and the
+    /// subscription starts from the oldest event remembered.
+    pub fn subscribe_since(&self, cursor: Option<u64>) -> EventSubscription {
+        let history = self.history.lock().unwrap();
+        let receiver = self.sender.subscribe();
+        let Some(cursor) = cursor else {
+            return EventSubscription {
+                backlog: Vec::new(),
+                missed: 0,
+                reset: false,
+                receiver,
+            };
+        };
+
+        let oldest = history
+            .events
+            .front()
+            .map_or(history.next_seq, |event| event.seq);
+        // Sequence numbers start again from 0 whenever HARM starts, so a
+        // cursor which hasn't been reached yet is from an earlier run.
+        if cursor >= history.next_seq {
+            return EventSubscription {
+                backlog: history.events.iter().cloned().collect(),
+                missed: oldest,
+                reset: true,
+                receiver,
+            };
+        }
+
+        EventSubscription {
+            backlog: history
+                .events
+                .iter()
+                .filter(|event| event.seq > cursor)
+                .cloned()
+                .collect(),
+            missed: oldest.saturating_sub(cursor.saturating_add(1)),
+            reset: false,
+            receiver,
+        }
+    }
+
+    /// Subscribes to every event published from now on. If a subscriber
+    /// falls too far behind, the receiver reports `RecvError::Lagged` and
+    /// skips ahead.
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_CAPACITY, DEFAULT_EVENT_HISTORY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn publish(bus: &EventBus, count: usize) {
+        for _ in 0..count {
+            bus.publish(Uuid::nil(), EventKind::GameCreated);
+        }
+    }
+
+    fn seqs(subscription: &EventSubscription) -> Vec<u64> {
+        subscription.backlog.iter().map(|event| event.seq).collect()
+    }
+
+    #[test]
+    fn resumes_from_a_cursor() {
+        let bus = EventBus::new(16, 4);
+        publish(&bus, 6);
+
+        let subscription = bus.subscribe_since(Some(3));
+        assert_eq!(seqs(&subscription), [4, 5]);
+        assert_eq!(subscription.missed, 0);
+        assert!(!subscription.reset);
+
+        let subscription = bus.subscribe_since(Some(0));
+        assert_eq!(seqs(&subscription), [2, 3, 4, 5]);
+        assert_eq!(subscription.missed, 1);
+        assert!(!subscription.reset);
+
+        let subscription = bus.subscribe_since(None);
+        assert!(subscription.backlog.is_empty());
+        assert_eq!(subscription.missed, 0);
+        assert!(!subscription.reset);
+    }
+
+    #[test]
+    fn treats_cursors_from_an_earlier_run_as_unknown() {
+        let bus = EventBus::new(16, 4);
+        let subscription = bus.subscribe_since(Some(0));
+        assert!(subscription.backlog.is_empty());
+        assert_eq!(subscription.missed, 0);
+        assert!(subscription.reset);
+
+        publish(&bus, 6);
+        let subscription = bus.subscribe_since(Some(6));
+        assert_eq!(seqs(&subscription), [2, 3, 4, 5]);
+        assert_eq!(subscription.missed, 2);
+        assert!(subscription.reset);
+
+        let subscription = bus.subscribe_since(Some(u64::MAX));
+        assert_eq!(seqs(&subscription), [2, 3, 4, 5]);
+        assert!(subscription.reset);
+    }
+}
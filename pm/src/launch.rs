@@ -0,0 +1,75 @@
+use harm_schemas::LaunchOptions;
+
+use crate::paths::ServerPaths;
+
+/// Renders a server's launch options into the arguments passed to the
+/// Reforger executable, with `-config` pointing at the server's config file.
+/// `-profile` and `-addonDownloadDir` default to the server's own data
+/// directory unless set in `options`.
+pub fn render_args(options: &LaunchOptions, paths: &ServerPaths) -> Vec<String> {
+    let mut args = vec![
+        String::from("-config"),
+        paths.config.to_string_lossy().into_owned(),
+    ];
+
+    let mut push = |flag: &str, value: Option<String>| {
+        args.push(String::from(flag));
+        if let Some(value) = value {
+            args.push(value);
+        }
+    };
+
+    if let Some(max_fps) = options.max_fps {
+        push("-maxFPS", Some(max_fps.to_string()));
+    }
+    let profile = match &options.profile {
+        Some(profile) => profile.clone(),
+        None => paths.profile.to_string_lossy().into_owned(),
+    };
+    push("-profile", Some(profile));
+    if !options.addons_dir.is_empty() {
+        push("-addonsDir", Some(options.addons_dir.join(",")));
+    }
+    let addon_download_dir = match &options.addon_download_dir {
+        Some(dir) => dir.clone(),
+        None => paths.addons.to_string_lossy().into_owned(),
+    };
+    push("-addonDownloadDir", Some(addon_download_dir));
+    if let Some(interval) = options.log_stats {
+        push("-logStats", Some(interval.to_string()));
+    }
+    if let Some(level) = options.log_level {
+        push("-logLevel", Some(level.as_arg().to_string()));
+    }
+    if let Some(save) = &options.load_session_save {
+        push(
+            "-loadSessionSave",
+            Some(save.clone()).filter(|s| !s.is_empty()),
+        );
+    }
+    if let Some(secs) = options.autoreload {
+        push("-autoreload", Some(secs.to_string()));
+    }
+    if let Some(secs) = options.freeze_check {
+        push("-freezeCheck", Some(secs.to_string()));
+    }
+
+    args.extend(options.extra_args.iter().cloned());
+    args
+}
+
+/// Checks launch options for arguments HARM manages itself, returning a
+/// description of the first problem found.
+pub fn check(options: &LaunchOptions) -> Result<(), String> {
+    if options
+        .extra_args
+        .iter()
+        .any(|arg| arg.eq_ignore_ascii_case("-config"))
+    {
+        return Err(String::from(
+            "-config is managed by HARM and can't be passed as an extra argument",
+        ));
+    }
+
+    Ok(())
+}
@@ -1 +1,19 @@
+pub mod a2s;
+pub mod console;
+pub mod events;
+pub mod launch;
+pub mod limits;
+pub mod logs;
 pub mod manager;
+pub mod paths;
+pub mod ports;
+pub mod preflight;
+mod procfs;
+pub mod rcon;
+pub mod readiness;
+pub mod resources;
+pub mod runtime;
+pub mod store;
+pub mod supervisor;
+#[cfg(test)]
+mod testing;
@@ -0,0 +1,102 @@
+use harm_schemas::{IoClass, ResourceLimits, RuntimeKind};
+
+/// Checks resource limits for values out of range, or controls the server's
+/// runtime can't apply, returning a description of the first problem found.
+pub fn check(limits: &ResourceLimits, runtime: RuntimeKind) -> Result<(), String> {
+    if let Some(nice) = limits.nice {
+        if !(-20..=19).contains(&nice) {
+            return Err(String::from("nice must be between -20 and 19"));
+        }
+    }
+    if let Some(priority) = &limits.io_priority {
+        match (priority.class, priority.level) {
+            (IoClass::Idle, Some(_)) => {
+                return Err(String::from("the idle I/O class doesn't take a level"))
+            }
+            (_, Some(level)) if level > 7 => {
+                return Err(String::from(
+                    "the I/O priority level must be between 0 and 7",
+                ))
+            }
+            _ => {}
+        }
+    }
+    if let Some(cpu) = limits.cpu_affinity.iter().find(|cpu| **cpu >= cpu_count()) {
+        return Err(format!(
+            "CPU {} doesn't exist; this host has {} CPUs",
+            cpu,
+            cpu_count()
+        ));
+    }
+    if limits.max_open_files == Some(0) {
+        return Err(String::from("maxOpenFiles must be at least 1"));
+    }
+    if let Some(adj) = limits.oom_score_adj {
+        if !(-1000..=1000).contains(&adj) {
+            return Err(String::from("oomScoreAdj must be between -1000 and 1000"));
+        }
+    }
+    if let Some(cgroup) = &limits.cgroup {
+        if cgroup.memory_max == Some(0) || cgroup.cpu_max_percent == Some(0) {
+            return Err(String::from("cgroup limits must be greater than 0"));
+        }
+    }
+
+    match runtime {
+        RuntimeKind::Podman | RuntimeKind::Docker
+            if limits.nice.is_some() || limits.io_priority.is_some() =>
+        {
+            Err(String::from(
+                "nice and ioPriority can't be applied to containers",
+            ))
+        }
+        RuntimeKind::Local if cfg!(not(target_os = "linux")) && *limits != Default::default() => {
+            Err(String::from(
+                "resource limits are only supported on Linux hosts",
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The number of CPUs configured on the host.
+#[cfg(target_os = "linux")]
+fn cpu_count() -> u32 {
+    // SAFETY: sysconf(3) has no memory safety requirements.
+    match unsafe { libc::sysconf(libc::_SC_NPROCESSORS_CONF) } {
+        count if count > 0 => count as u32,
+        _ => 1,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cpu_count() -> u32 {
+    std::thread::available_parallelism().map_or(1, |n| n.get() as u32)
+}
+
+/// The value written to a cgroup's `cpu.max` for a percentage of one core.
+pub(crate) fn cpu_max(percent: Option<u32>) -> String {
+    const PERIOD: u64 = 100_000;
+    match percent {
+        Some(percent) => format!("{} {}", u64::from(percent) * PERIOD / 100, PERIOD),
+        None => format!("max {}", PERIOD),
+    }
+}
+
+/// The ioprio value for ioprio_set(2).
+#[cfg(target_os = "linux")]
+pub(crate) fn ioprio(priority: &harm_schemas::IoPriority) -> libc::c_int {
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    let class = match priority.class {
+        IoClass::Realtime => 1,
+        IoClass::BestEffort => 2,
+        IoClass::Idle => 3,
+    };
+    // 4 is the kernel's default level for the real-time and best-effort
+    // classes.
+    let level = match priority.class {
+        IoClass::Idle => 0,
+        _ => priority.level.unwrap_or(4) as libc::c_int,
+    };
+    (class << IOPRIO_CLASS_SHIFT) | level
+}
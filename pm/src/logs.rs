@@ -0,0 +1,267 @@
+use std::{
+    collections::VecDeque,
+    io::SeekFrom,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, AsyncRead, AsyncSeekExt, BufReader},
+    sync::{broadcast, oneshot},
+    task::JoinHandle,
+};
+
+/// How many lines of console output are retained per server by default.
+pub const DEFAULT_LOG_CAPACITY: usize = 5000;
+
+/// How often a tailed log file is checked for new output.
+const TAIL_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The stream a captured line of console output was read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+/// A single line of console output captured from a server process.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLine {
+    /// Monotonically increasing sequence number, unique per server. Callers
+    /// can use this as a cursor to fetch only lines they have not yet seen.
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub stream: LogStream,
+    pub line: String,
+}
+
+/// The result of subscribing to a server's logs: the most recent lines at the
+/// time of subscribing, followed by every line captured afterwards.
+///
+/// The receiver never yields a line already present in `backlog`. If a
+/// subscriber falls too far behind, the receiver reports
+/// `RecvError::Lagged` and skips ahead; the `seq` of the next line received
+/// can be used to tell how many were missed.
+pub struct LogSubscription {
+    pub backlog: Vec<LogLine>,
+    pub receiver: broadcast::Receiver<LogLine>,
+}
+
+#[derive(Debug)]
+struct LogBufferInner {
+    lines: VecDeque<LogLine>,
+    next_seq: u64,
+}
+
+/// LogBuffer is a bounded ring buffer of console output for a single server,
+/// which also fans new lines out to any live subscribers.
+#[derive(Debug)]
+pub struct LogBuffer {
+    capacity: usize,
+    inner: Mutex<LogBufferInner>,
+    sender: broadcast::Sender<LogLine>,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.clamp(1, 1024));
+        Self {
+            capacity,
+            inner: Mutex::new(LogBufferInner {
+                lines: VecDeque::with_capacity(capacity),
+                next_seq: 0,
+            }),
+            sender,
+        }
+    }
+
+    /// Appends a line to the buffer, evicting the oldest line if the buffer
+    /// is full, and publishes it to subscribers.
+    pub fn push(&self, stream: LogStream, line: String) {
+        let mut inner = self.inner.lock().unwrap();
+        let entry = LogLine {
+            seq: inner.next_seq,
+            timestamp: Utc::now(),
+            stream,
+            line,
+        };
+        inner.next_seq += 1;
+
+        if inner.lines.len() >= self.capacity {
+            inner.lines.pop_front();
+        }
+        inner.lines.push_back(entry.clone());
+
+        // Sending only fails when nobody is subscribed, which is fine.
+        let _ = self.sender.send(entry);
+    }
+
+    /// Returns up to the last `n` lines held in the buffer, oldest first.
+    pub fn tail(&self, n: usize) -> Vec<LogLine> {
+        let inner = self.inner.lock().unwrap();
+        let skip = inner.lines.len().saturating_sub(n);
+        inner.lines.iter().skip(skip).cloned().collect()
+    }
+
+    /// Returns up to `n` lines captured after the line with sequence number
+    /// `seq`, oldest first.
+    pub fn since(&self, seq: u64, n: usize) -> Vec<LogLine> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .lines
+            .iter()
+            .filter(|l| l.seq > seq)
+            .take(n)
+            .cloned()
+            .collect()
+    }
+
+    /// Subscribes to new lines, returning the last `n` lines as a backlog.
+    pub fn subscribe(&self, n: usize) -> LogSubscription {
+        // Holding the lock while subscribing guarantees no line is both in
+        // the backlog and delivered through the receiver, or in neither.
+        let inner = self.inner.lock().unwrap();
+        let skip = inner.lines.len().saturating_sub(n);
+        let backlog = inner.lines.iter().skip(skip).cloned().collect();
+        let receiver = self.sender.subscribe();
+
+        LogSubscription { backlog, receiver }
+    }
+}
+
+/// Spawns a task which reads `reader` line by line until EOF, pushing every
+/// line into `buffer` tagged with `stream`. Output that is not valid UTF-8 is
+/// converted lossily rather than aborting the capture.
+pub fn spawn_reader<R>(buffer: Arc<LogBuffer>, stream: LogStream, reader: R) -> JoinHandle<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(reader);
+        let mut buf = Vec::new();
+
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&buf);
+                    let line = line.trim_end_matches(['\r', '\n']);
+                    buffer.push(stream, line.to_string());
+                }
+            }
+        }
+    })
+}
+
+/// Pushes every complete line available from `reader` into `buffer`. A
+/// trailing partial line is left in `partial` until the rest of it arrives.
+async fn drain_lines<R>(
+    reader: &mut BufReader<R>,
+    partial: &mut Vec<u8>,
+    buffer: &LogBuffer,
+    stream: LogStream,
+) -> std::io::Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    loop {
+        if reader.read_until(b'\n', partial).await? == 0 {
+            return Ok(());
+        }
+
+        if partial.ends_with(b"\n") {
+            let line = String::from_utf8_lossy(partial);
+            buffer.push(stream, line.trim_end_matches(['\r', '\n']).to_string());
+            partial.clear();
+        }
+    }
+}
+
+/// A handle to a task tailing a log file, created with `spawn_tailer`.
+/// Dropping the handle stops the task without a final read.
+#[derive(Debug)]
+pub struct Tailer {
+    stop: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl Tailer {
+    /// Stops tailing once everything written to the file so far has been
+    /// read, including a final line with no trailing newline.
+    pub async fn stop(mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for Tailer {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+/// Spawns a task which follows the file at `path` from byte `offset`, pushing
+/// every line appended to it into `buffer` tagged with `stream`, until the
+/// returned Tailer is stopped.
+///
+/// Server output is written to files rather than pipes so that the process
+/// survives HARM exiting, and its output can be followed again once it is
+/// re-adopted.
+pub fn spawn_tailer(
+    buffer: Arc<LogBuffer>,
+    stream: LogStream,
+    path: PathBuf,
+    offset: u64,
+) -> Tailer {
+    let (stop, mut stop_rx) = oneshot::channel();
+    let task = tokio::spawn(async move {
+        let Ok(mut file) = File::open(&path).await else {
+            return;
+        };
+        if file.seek(SeekFrom::Start(offset)).await.is_err() {
+            return;
+        }
+
+        let mut reader = BufReader::new(file);
+        let mut partial = Vec::new();
+
+        loop {
+            if drain_lines(&mut reader, &mut partial, &buffer, stream)
+                .await
+                .is_err()
+            {
+                return;
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(TAIL_POLL_INTERVAL) => {}
+                _ = &mut stop_rx => break,
+            }
+        }
+
+        let _ = drain_lines(&mut reader, &mut partial, &buffer, stream).await;
+        if !partial.is_empty() {
+            let line = String::from_utf8_lossy(&partial);
+            buffer.push(stream, line.trim_end_matches('\r').to_string());
+        }
+    });
+
+    Tailer {
+        stop: Some(stop),
+        task: Some(task),
+    }
+}
@@ -1,175 +1,1232 @@
-use std::{collections::HashMap, path::PathBuf, process::Stdio, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::{Error, Result};
-use harm_schemas::ServerConfig;
-use slog::{debug, error, info, Logger};
+use chrono::{DateTime, Utc};
+use harm_schemas::{
+    LaunchOptions, RconConfig, ReadinessConfig, ResourceLimits, RestartPolicy, RuntimeConfig,
+    ServerConfig,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use slog::{debug, error, info, warn, Logger};
 use tokio::{
     fs,
-    io::{AsyncReadExt, AsyncWriteExt},
-    process::Child,
-    sync::Mutex,
+    io::AsyncWriteExt,
+    sync::{broadcast::error::RecvError, mpsc, oneshot, Mutex},
+    task::JoinHandle,
+    time::MissedTickBehavior,
 };
 use uuid::Uuid;
 
-#[derive(Debug)]
+use crate::{
+    a2s::{A2sClient, QueryStatus},
+    console::ConsoleParser,
+    events::{EventBus, EventKind},
+    launch, limits,
+    logs::{LogBuffer, LogLine, LogSubscription, DEFAULT_LOG_CAPACITY},
+    paths::ServerPaths,
+    preflight::{self, PreflightFailed, PreflightReport},
+    procfs,
+    rcon::RconClient,
+    resources::{ResourceHistory, ResourceUsage, DEFAULT_HISTORY_CAPACITY},
+    runtime::{self, FakeRuntime, Instance, SpawnRequest},
+    store::{ProcessRecord, ProcessStore},
+    supervisor::{self, Control},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
 pub enum ServerState {
-    Running,
+    /// The process has been spawned and is waiting for its readiness probes
+    /// to pass.
+    Starting,
+
+    /// The process is alive and its readiness probes passed.
+    Ready,
+
+    /// The process was stopped through the process manager.
+    #[default]
     Stopped,
+
+    /// The process exited on its own with a non-zero code or was killed by a
+    /// signal HARM did not send.
+    Crashed,
+
+    /// The process exited on its own with a zero exit code.
+    Exited,
+
+    /// The process has been asked to stop and is within its grace period.
+    Stopping,
+
+    /// The process exited on its own and is waiting out its restart backoff.
+    Restarting,
+
+    /// The process kept exiting and exhausted its restart policy. It will not
+    /// be restarted again until an operator starts it.
+    Quarantined,
+
+    /// The process didn't become ready within its startup timeout and was
+    /// stopped.
+    Failed,
+}
+
+impl ServerState {
+    /// Whether a process is (or is about to be) alive in this state.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            Self::Starting | Self::Ready | Self::Stopping | Self::Restarting
+        )
+    }
+}
+
+/// How and when a server process last exited.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExitInfo {
+    /// The exit code, if the process exited normally.
+    pub code: Option<i32>,
+
+    /// The signal which terminated the process, if any. Always `None` on
+    /// non-Unix hosts.
+    pub signal: Option<i32>,
+
+    pub at: DateTime<Utc>,
+}
+
+impl ExitInfo {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/// The signal sent to a server process to ask it to shut down cleanly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum StopSignal {
+    #[default]
+    Term,
+    Int,
+    Quit,
+    Hup,
+}
+
+/// How a server should be stopped.
+#[derive(Clone, Debug)]
+pub struct StopOptions {
+    /// The signal sent first. Local processes can't be signalled on non-Unix
+    /// hosts, so they are always killed outright.
+    pub signal: StopSignal,
+
+    /// How long the process has to exit after `signal` before it is killed.
+    pub grace_period: Duration,
+
+    /// Skip the signal and kill the process immediately.
+    pub force: bool,
+}
+
+impl Default for StopOptions {
+    fn default() -> Self {
+        Self {
+            signal: StopSignal::default(),
+            grace_period: Duration::from_secs(30),
+            force: false,
+        }
+    }
+}
+
+/// Which path a stop request took.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum StopMethod {
+    /// The process exited within its grace period after being signalled.
+    Graceful,
+
+    /// The process ignored the signal for its whole grace period and was
+    /// killed.
+    Escalated,
+
+    /// The process was killed without being signalled first.
+    Forced,
+
+    /// No process was running, and a pending automatic restart was cancelled.
+    Cancelled,
+}
+
+/// The result of stopping a server.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StopOutcome {
+    pub method: StopMethod,
+    /// How the process exited, if one was running.
+    pub exit: Option<ExitInfo>,
+}
+
+/// Everything the process manager needs to know to run a server.
+#[derive(Clone, Debug, Default)]
+pub struct ServerSpec {
+    pub config: ServerConfig,
+    pub restart_policy: RestartPolicy,
+    pub launch_options: LaunchOptions,
+    pub runtime: RuntimeConfig,
+    pub resource_limits: ResourceLimits,
+    pub readiness: ReadinessConfig,
+}
+
+/// A point-in-time view of a server's runtime state.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStatus {
+    pub id: Uuid,
+    pub state: ServerState,
+    pub pid: Option<u32>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub last_exit: Option<ExitInfo>,
+    /// How many consecutive automatic restarts have been attempted.
+    pub restarts: u32,
+    /// Whether the process was re-adopted from a previous instance of HARM.
+    /// Adopted processes can't report an exit code.
+    pub adopted: bool,
+    /// The latest A2S query of the server, while it is ready.
+    pub query: Option<QueryStatus>,
+    /// Whether HARM is logged in to the server's RCON.
+    pub rcon_connected: bool,
 }
 
 #[derive(Debug)]
 pub struct Server {
     pub id: Uuid,
-    pub process: Option<Child>,
     pub state: ServerState,
+    pub pid: Option<u32>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub last_exit: Option<ExitInfo>,
+    pub restarts: u32,
+    pub adopted: bool,
+    pub logs: Arc<LogBuffer>,
+    pub resources: Arc<ResourceHistory>,
+    pub(crate) console: Arc<ConsoleParser>,
+    /// The client the server is polled with while it runs.
+    pub(crate) a2s: Option<A2sClient>,
+    pub(crate) query: Option<QueryStatus>,
+    /// The RCON connection kept open while the server runs.
+    pub(crate) rcon: Option<RconClient>,
+    pub(crate) control: Option<mpsc::Sender<Control>>,
+}
+
+impl Server {
+    fn new(id: Uuid, logs: Arc<LogBuffer>, events: &EventBus) -> Self {
+        Self {
+            id,
+            state: ServerState::default(),
+            pid: None,
+            started_at: None,
+            last_exit: None,
+            restarts: 0,
+            adopted: false,
+            console: Arc::new(ConsoleParser::spawn(id, &logs, events.clone())),
+            logs,
+            resources: Arc::new(ResourceHistory::new(DEFAULT_HISTORY_CAPACITY)),
+            a2s: None,
+            query: None,
+            rcon: None,
+            control: None,
+        }
+    }
+
+    pub fn status(&self) -> ServerStatus {
+        ServerStatus {
+            id: self.id,
+            state: self.state,
+            pid: self.pid,
+            started_at: self.started_at,
+            last_exit: self.last_exit.clone(),
+            restarts: self.restarts,
+            adopted: self.adopted,
+            query: self
+                .query
+                .clone()
+                .filter(|_| self.state == ServerState::Ready),
+            rcon_connected: self.rcon.as_ref().is_some_and(RconClient::is_connected),
+        }
+    }
 }
 
 /// ProcessManager is a simple process manager built to track and interact with
 /// multiple servers (datatypes defined by the harm_entity crate).
+///
+/// Cloning a ProcessManager is cheap, and every clone shares the same set of
+/// tracked servers.
+#[derive(Clone)]
 pub struct ProcessManager {
     pub arma_reforger_path: String,
-    logger: Logger,
+    /// The directory every server's data directory is created under.
+    pub data_dir: PathBuf,
+    pub(crate) logger: Logger,
     servers: Arc<Mutex<HashMap<Uuid, Server>>>,
+    store: Option<Arc<dyn ProcessStore>>,
+    fake_runtime: Arc<FakeRuntime>,
+    cgroup_root: Option<PathBuf>,
+    events: EventBus,
 }
 
 impl ProcessManager {
-    pub fn new(arma_reforger_path: String, logger: Logger) -> Self {
+    pub fn new(arma_reforger_path: String, data_dir: PathBuf, logger: Logger) -> Self {
         Self {
             arma_reforger_path,
+            data_dir,
             logger,
             servers: Arc::new(Mutex::new(HashMap::new())),
+            store: None,
+            fake_runtime: Arc::new(FakeRuntime::default()),
+            cgroup_root: None,
+            events: EventBus::default(),
         }
     }
 
-    /// Gets three paths for a given server and returns them in a tuple in the
-    /// following order:
-    /// - The Arma Reforger Path
-    /// - The Arma Reforger _directory_ path
-    /// - The path for this server's config file
-    fn get_paths(&self, id: Uuid) -> (PathBuf, PathBuf, PathBuf) {
-        let path = PathBuf::from(self.arma_reforger_path.clone());
-        let parent_path = path.parent().unwrap().to_path_buf();
-        let config_path = parent_path.join(format!("{}.json", id.clone()));
+    /// Persists running processes to `store`, so they can be re-adopted with
+    /// `reattach` after HARM restarts.
+    pub fn with_store(mut self, store: Arc<dyn ProcessStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Creates a cgroup under `cgroup_root` for each locally run server with
+    /// cgroup limits. HARM must have been delegated the directory, e.g. with
+    /// systemd's `Delegate=yes`.
+    pub fn with_cgroup_root(mut self, cgroup_root: PathBuf) -> Self {
+        self.cgroup_root = Some(cgroup_root);
+        self
+    }
+
+    /// The runtime used by servers configured with the fake runtime kind.
+    /// Shared by every clone of the process manager.
+    pub fn fake_runtime(&self) -> Arc<FakeRuntime> {
+        self.fake_runtime.clone()
+    }
+
+    /// The bus events from every server are published on, e.g. those parsed
+    /// from their console output.
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Gets the paths of a server's data directory. See ServerPaths for the
+    /// layout.
+    pub fn server_paths(&self, id: Uuid) -> ServerPaths {
+        ServerPaths::new(&self.data_dir, id)
+    }
+
+    /// Creates a server's data directory layout, if it doesn't already exist.
+    pub async fn create_data_dir(&self, id: Uuid) -> Result<ServerPaths> {
+        let paths = self.server_paths(id);
+        paths.create().await?;
 
         debug!(
             self.logger,
-            "AR: {:?}, RP: {:?}, CP: {:?}", path, parent_path, config_path
+            "Data directory for server {}: {:?}", id, paths.root
         );
 
-        (path, parent_path, config_path)
+        Ok(paths)
+    }
+
+    /// Deletes a server's data directory. If `archive` is set, the directory
+    /// is moved under `<data dir>/archive` instead, and the path it was moved
+    /// to is returned.
+    pub async fn remove_data_dir(&self, id: Uuid, archive: bool) -> Result<Option<PathBuf>> {
+        let paths = self.server_paths(id);
+        if archive {
+            if !fs::try_exists(&paths.root).await? {
+                return Ok(None);
+            }
+            return Ok(Some(paths.archive(&self.data_dir).await?));
+        }
+
+        paths.remove().await?;
+        Ok(None)
     }
 
     /// Writes a server's configuration (via the ServerConfig struct) to the
     /// relevant file on the host's filesystem.
     pub async fn write_config(&self, id: Uuid, config: ServerConfig) -> Result<()> {
-        let (_, _, config_path) = self.get_paths(id);
+        let paths = self.create_data_dir(id).await?;
         let config_str = serde_json::to_string(&config)?;
-        let mut file = fs::File::create(config_path.clone()).await?;
+        let mut file = fs::File::create(paths.config).await?;
         file.write_all(config_str.as_bytes()).await?;
 
         Ok(())
     }
 
-    /// Starts a server using tokio::process and returns the Child handle back
-    /// to the caller.
-    async fn _start_server(&self, id: Uuid, config: ServerConfig) -> Result<Child> {
-        let (exec_path, parent_path, config_path) = self.get_paths(id);
-        self.write_config(id, config).await?;
+    /// Runs the preflight checks for a server without starting it. Servers
+    /// which are running skip the port check, as they hold their own ports.
+    pub async fn preflight(&self, id: Uuid, spec: &ServerSpec) -> PreflightReport {
+        let running = self.status(id).await.state.is_active();
+        self.run_preflight(&self.server_paths(id), spec, running)
+            .await
+    }
+
+    async fn run_preflight(
+        &self,
+        paths: &ServerPaths,
+        spec: &ServerSpec,
+        running: bool,
+    ) -> PreflightReport {
+        preflight::run(
+            Path::new(&self.arma_reforger_path),
+            &self.data_dir,
+            paths,
+            spec,
+            running,
+        )
+        .await
+    }
+
+    /// Checks that a server can be started with `spec` without starting it:
+    /// its resource limits and runtime config must be usable, and every
+    /// preflight check must pass, or a `PreflightFailed` error is returned.
+    async fn check_startable(&self, id: Uuid, spec: &ServerSpec) -> Result<()> {
+        limits::check(&spec.resource_limits, spec.runtime.kind).map_err(Error::msg)?;
+        runtime::check(&spec.runtime).map_err(Error::msg)?;
+        let report = self
+            .run_preflight(&self.server_paths(id), spec, false)
+            .await;
+        if !report.passed() {
+            return Err(PreflightFailed(report).into());
+        }
+        Ok(())
+    }
+
+    /// Checks and starts a server with the runtime its spec selects, and
+    /// returns the running instance back to the caller. See `spawn`.
+    pub(crate) async fn _start_server(
+        &self,
+        id: Uuid,
+        spec: &ServerSpec,
+        logs: Arc<LogBuffer>,
+        console: &ConsoleParser,
+    ) -> Result<Box<dyn Instance>> {
+        self.check_startable(id, spec).await?;
+        self.spawn(id, spec, logs, console).await
+    }
+
+    /// Starts a server with the runtime its spec selects, without checking
+    /// it first. The server's console output is fed into `logs`, and
+    /// `console` follows the log Reforger writes in its profile.
+    ///
+    /// The process is recorded in the process store so it can be re-adopted,
+    /// if the runtime gives it a pid.
+    async fn spawn(
+        &self,
+        id: Uuid,
+        spec: &ServerSpec,
+        logs: Arc<LogBuffer>,
+        console: &ConsoleParser,
+    ) -> Result<Box<dyn Instance>> {
+        let runtime = runtime::from_config(
+            &spec.runtime,
+            &self.fake_runtime,
+            self.cgroup_root.as_deref(),
+        )?;
+        let paths = self.server_paths(id);
+        self.write_config(id, spec.config.clone()).await?;
+        let options = &spec.launch_options;
+        for dir in [&options.profile, &options.addon_download_dir]
+            .into_iter()
+            .flatten()
+        {
+            fs::create_dir_all(dir).await?;
+        }
 
         info!(
             self.logger,
-            "Spawning AR process for server {}",
-            id.to_string()
+            "Spawning AR process for server {} ({:?} runtime)",
+            id.to_string(),
+            spec.runtime.kind
         );
 
-        let child = tokio::process::Command::new(exec_path.clone())
-            .current_dir(parent_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .arg("-maxFPS")
-            .arg("60")
-            .arg("-config")
-            .arg(config_path.to_str().unwrap())
-            .spawn()?;
+        console.follow_profile(profile_logs(spec, &paths), false);
+        let request = SpawnRequest {
+            id,
+            program: PathBuf::from(&self.arma_reforger_path),
+            args: launch::render_args(&spec.launch_options, &paths),
+            env: spec.launch_options.env.clone(),
+            paths,
+            limits: spec.resource_limits.clone(),
+        };
+        let instance = runtime.spawn(&request, logs).await?;
 
-        Ok(child)
+        if let (Some(store), Some(pid)) = (&self.store, instance.pid()) {
+            let record = ProcessRecord {
+                server_id: id,
+                pid,
+                start_time: procfs::start_time(pid),
+                started_at: Utc::now(),
+                command_line: request.command_line(),
+            };
+            if let Err(e) = store.save(record).await {
+                warn!(
+                    self.logger,
+                    "Could not persist AR process for server {}: {}", id, e
+                );
+            }
+        }
+
+        Ok(instance)
     }
 
     /// Starts a new server by UUID, if it is not running. If it is running,
     /// this function will return an error.
-    pub async fn start_server(&self, id: Uuid, config: ServerConfig) -> Result<()> {
-        let mut servers = self.servers.lock().await;
-        if let Some(server) = servers.get_mut(&id) {
-            if server.process.is_some() {
+    ///
+    /// The server is checked first, and left as it was if it can't be
+    /// started; a `PreflightFailed` error is returned if a preflight check
+    /// failed. Once spawned, the process is handed to a supervisor task
+    /// which keeps the server's state up to date when it exits, and restarts
+    /// it according to the spec's restart policy. Starting a quarantined
+    /// server resets its restart budget.
+    pub async fn start_server(&self, id: Uuid, spec: ServerSpec) -> Result<()> {
+        if self.status(id).await.state.is_active() {
+            return Err(Error::msg("That server is already running."));
+        }
+        self.check_startable(id, &spec).await?;
+
+        // The server is claimed as starting, then the lock is released while
+        // the process is spawned, as some runtimes are slow.
+        let (previous, logs, console) = {
+            let mut servers = self.servers.lock().await;
+            let server = servers
+                .entry(id)
+                .or_insert_with(|| Server::new(id, new_log_buffer(), &self.events));
+            if server.state.is_active() {
                 return Err(Error::msg("That server is already running."));
             }
 
-            let child = self._start_server(id, config.clone()).await?;
-            server.process = Some(child);
-        } else {
-            let child = self._start_server(id, config.clone()).await?;
-            let server = Server {
-                id,
-                process: Some(child),
-                state: ServerState::Running,
-            };
-            servers.insert(id, server);
-        }
+            let previous = server.state;
+            self.set_state(server, ServerState::Starting);
+            (previous, server.logs.clone(), server.console.clone())
+        };
+
+        let process = match self.spawn(id, &spec, logs, &console).await {
+            Ok(process) => process,
+            Err(e) => {
+                // Nothing ran, so the server goes back to how it was.
+                self.update(id, |server| server.state = previous).await;
+                return Err(e);
+            }
+        };
+
+        // RCON is only connected once there is a server to connect to.
+        let (control_tx, control_rx) = mpsc::channel(1);
+        let mut servers = self.servers.lock().await;
+        let server = servers
+            .entry(id)
+            .or_insert_with(|| Server::new(id, new_log_buffer(), &self.events));
+        server.restarts = 0;
+        server.adopted = false;
+        server.a2s = Some(A2sClient::for_config(&spec.config.a2s));
+        server.query = None;
+        server.rcon = Some(self.connect_rcon(id, &spec.config.rcon));
+        server.pid = process.pid();
+        server.started_at = Some(Utc::now());
+        server.control = Some(control_tx);
+
+        tokio::spawn(supervisor::supervise(
+            self.clone(),
+            id,
+            process,
+            spec,
+            control_rx,
+        ));
 
         Ok(())
     }
 
-    /// Stops a server by UUID, if it is running.
-    pub async fn stop_server(&self, id: Uuid) -> Result<()> {
-        let mut servers = self.servers.lock().await;
-        if let Some(server) = servers.get_mut(&id) {
-            if let Some(mut process) = server.process.take() {
-                info!(
-                    self.logger,
-                    "Stopping AR process for server {}",
-                    id.to_string()
-                );
-                process.kill().await?;
-                server.state = ServerState::Stopped;
-                server.process = None;
-            } else {
-                error!(
+    /// Re-adopts server processes started by a previous instance of HARM, as
+    /// recorded in the process store. `specs` holds the spec of every known
+    /// server, used to pick the runtime which finds the process again, and if
+    /// an adopted process needs to be restarted.
+    ///
+    /// Each runtime decides whether a record can still be trusted, so that a
+    /// recycled pid is never mistaken for a server. Stale records are
+    /// removed. Returns the IDs of the servers which were re-adopted.
+    pub async fn reattach(&self, mut specs: HashMap<Uuid, ServerSpec>) -> Result<Vec<Uuid>> {
+        let Some(store) = &self.store else {
+            return Ok(Vec::new());
+        };
+
+        let mut adopted = Vec::new();
+        for record in store.load().await? {
+            let id = record.server_id;
+            let paths = self.server_paths(id);
+
+            // Only the log buffer is taken under the lock, as adopting a
+            // process can mean waiting on podman or systemctl.
+            let logs = match self.servers.lock().await.get(&id) {
+                Some(server) => server.logs.clone(),
+                None => new_log_buffer(),
+            };
+
+            let instance = match specs.remove(&id) {
+                Some(spec) => match runtime::from_config(
+                    &spec.runtime,
+                    &self.fake_runtime,
+                    self.cgroup_root.as_deref(),
+                ) {
+                    Ok(runtime) => match runtime.adopt(&record, &paths, logs.clone()).await {
+                        Ok(instance) => instance.map(|instance| (spec, instance)),
+                        Err(e) => {
+                            warn!(
+                                self.logger,
+                                "Could not re-adopt AR process for server {}: {}", id, e
+                            );
+                            None
+                        }
+                    },
+                    Err(_) => None,
+                },
+                None => None,
+            };
+            let Some((spec, instance)) = instance else {
+                debug!(
                     self.logger,
-                    "AR process for server {} already dead!",
-                    id.to_string()
+                    "Discarding stale AR process record for server {}", id
                 );
-                return Err(Error::msg("That server is already stopped!"));
+                store.remove(id).await?;
+                continue;
+            };
+
+            info!(
+                self.logger,
+                "Re-adopting AR process {} for server {}", record.pid, id
+            );
+
+            let mut servers = self.servers.lock().await;
+            let server = servers
+                .entry(id)
+                .or_insert_with(|| Server::new(id, logs, &self.events));
+            server
+                .console
+                .follow_profile(profile_logs(&spec, &paths), true);
+            // The server was ready before HARM restarted, so it isn't probed
+            // again.
+            let (control_tx, control_rx) = mpsc::channel(1);
+            self.set_state(server, ServerState::Ready);
+            server.pid = Some(record.pid);
+            server.started_at = Some(record.started_at);
+            server.adopted = true;
+            server.a2s = Some(A2sClient::for_config(&spec.config.a2s));
+            server.query = None;
+            server.rcon = Some(self.connect_rcon(id, &spec.config.rcon));
+            server.control = Some(control_tx);
+
+            tokio::spawn(supervisor::supervise(
+                self.clone(),
+                id,
+                instance,
+                spec,
+                control_rx,
+            ));
+            adopted.push(id);
+        }
+
+        Ok(adopted)
+    }
+
+    /// Stops a server by UUID, if it is running, and returns how it was
+    /// stopped. See StopOptions for how the process is asked to exit.
+    pub async fn stop_server(&self, id: Uuid, options: StopOptions) -> Result<StopOutcome> {
+        let control = {
+            let servers = self.servers.lock().await;
+            match servers.get(&id) {
+                Some(server) => server.control.clone(),
+                None => {
+                    error!(
+                        self.logger,
+                        "AR process for server {} already dead!",
+                        id.to_string()
+                    );
+                    return Err(Error::msg("That server was not started!"));
+                }
             }
-        } else {
+        };
+
+        let Some(control) = control else {
             error!(
                 self.logger,
                 "AR process for server {} already dead!",
                 id.to_string()
             );
-            return Err(Error::msg("That server was not started!"));
-        }
+            return Err(Error::msg("That server is already stopped!"));
+        };
+
+        info!(
+            self.logger,
+            "Stopping AR process for server {}",
+            id.to_string()
+        );
+
+        // The supervisor owns the process; ask it to stop and wait for the
+        // result. If it has gone away the process exited in the meantime.
+        let (reply_tx, reply_rx) = oneshot::channel();
+        control
+            .send(Control::Stop(options, reply_tx))
+            .await
+            .map_err(|_| Error::msg("That server is already stopped!"))?;
+        reply_rx
+            .await
+            .map_err(|_| Error::msg("That server is already stopped!"))?
+    }
 
+    /// Stops tracking a server which isn't running, dropping its logs and
+    /// resource history. Used once a server has been deleted.
+    pub async fn forget(&self, id: Uuid) -> Result<()> {
+        let mut servers = self.servers.lock().await;
+        if servers
+            .get(&id)
+            .is_some_and(|server| server.state.is_active())
+        {
+            return Err(Error::msg("That server is still running!"));
+        }
+        servers.remove(&id);
         Ok(())
     }
 
-    /// Returns the logs from a server, provided a UUID of the server logs are
-    /// needed for. Return value is (stdout, stderr)
-    pub async fn get_logs(&self, id: Uuid) -> Result<(String, String)> {
+    /// Applies `f` to a tracked server's runtime state, if it is tracked.
+    pub(crate) async fn update(&self, id: Uuid, f: impl FnOnce(&mut Server)) {
         let mut servers = self.servers.lock().await;
         if let Some(server) = servers.get_mut(&id) {
-            let mut o_stdout = String::new();
-            let mut o_stderr = String::new();
-            if let Some(mut process) = server.process.take() {
-                if let Some(mut stdout) = process.stdout.take() {
-                    stdout.read_to_string(&mut o_stdout).await?;
+            let state = server.state;
+            f(server);
+            if server.state != state {
+                self.publish_state(server);
+            }
+        }
+    }
+
+    /// Moves a server to `state`, publishing the change on the event bus.
+    fn set_state(&self, server: &mut Server, state: ServerState) {
+        if server.state != state {
+            server.state = state;
+            self.publish_state(server);
+        }
+    }
+
+    fn publish_state(&self, server: &Server) {
+        self.events.publish(
+            server.id,
+            EventKind::StateChanged {
+                state: server.state,
+            },
+        );
+    }
+
+    /// Records that a server's supervisor has finished, leaving the server in
+    /// `state` with no process attached.
+    pub(crate) async fn finish(&self, id: Uuid, state: ServerState, exit: ExitInfo) {
+        self.update(id, |server| {
+            server.state = state;
+            server.pid = None;
+            server.last_exit = Some(exit);
+            server.control = None;
+            server.rcon = None;
+        })
+        .await;
+
+        if let Some(store) = &self.store {
+            if let Err(e) = store.remove(id).await {
+                warn!(
+                    self.logger,
+                    "Could not remove AR process record for server {}: {}", id, e
+                );
+            }
+        }
+    }
+
+    /// Returns the runtime status of a server. Servers which have never been
+    /// started report as stopped.
+    pub async fn status(&self, id: Uuid) -> ServerStatus {
+        let servers = self.servers.lock().await;
+        match servers.get(&id) {
+            Some(server) => server.status(),
+            None => ServerStatus {
+                id,
+                state: ServerState::Stopped,
+                pid: None,
+                started_at: None,
+                last_exit: None,
+                restarts: 0,
+                adopted: false,
+                query: None,
+                rcon_connected: false,
+            },
+        }
+    }
+
+    /// Opens an RCON connection to a server, which logs in once the server
+    /// is up. Messages the server sends over it, and the connection logging
+    /// in and out, are published on the event bus.
+    fn connect_rcon(&self, id: Uuid, config: &RconConfig) -> RconClient {
+        let client = RconClient::for_config(config);
+        let mut messages = client.messages();
+        let mut connection = client.connection();
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    message = messages.recv() => match message {
+                        Ok(message) => {
+                            events.publish(id, EventKind::RconMessage { message });
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return,
+                    },
+                    changed = connection.changed() => {
+                        if changed.is_err() {
+                            return;
+                        }
+                        let kind = match *connection.borrow_and_update() {
+                            true => EventKind::RconConnected,
+                            false => EventKind::RconDisconnected,
+                        };
+                        events.publish(id, kind);
+                    }
+                }
+            }
+        });
+        client
+    }
+
+    /// Runs an RCON command, e.g. `#players`, on a running server and returns
+    /// its answer.
+    pub async fn rcon_command(&self, id: Uuid, command: &str) -> Result<String> {
+        let client = {
+            let servers = self.servers.lock().await;
+            servers.get(&id).and_then(|server| server.rcon.clone())
+        };
+        match client {
+            Some(client) => client.command(command).await,
+            None => Err(Error::msg("That server is not running.")),
+        }
+    }
+
+    /// Waits up to `timeout` for a server to become ready, and returns its
+    /// status once it is ready, once it is no longer starting up (e.g. it
+    /// crashed or failed its startup timeout), or once `timeout` passes.
+    pub async fn wait_ready(&self, id: Uuid, timeout: Duration) -> ServerStatus {
+        let mut events = self.events.subscribe();
+        let wait = async {
+            loop {
+                let status = self.status(id).await;
+                if status.state == ServerState::Ready || !status.state.is_active() {
+                    return;
+                }
+
+                // Wait for the server's state to change, or check it again if
+                // some events were missed.
+                loop {
+                    match events.recv().await {
+                        Ok(event)
+                            if event.server_id == id
+                                && matches!(event.kind, EventKind::StateChanged { .. }) =>
+                        {
+                            break
+                        }
+                        Ok(_) => continue,
+                        Err(RecvError::Lagged(_)) => break,
+                        Err(RecvError::Closed) => return,
+                    }
+                }
+            }
+        };
+
+        let _ = tokio::time::timeout(timeout, wait).await;
+        self.status(id).await
+    }
+
+    /// Returns the runtime status of every server the process manager has
+    /// started since it was created.
+    pub async fn list_status(&self) -> Vec<ServerStatus> {
+        let servers = self.servers.lock().await;
+        servers.values().map(Server::status).collect()
+    }
+
+    /// Returns up to the last `lines` lines of console output captured from a
+    /// server, oldest first. Output from previous runs of the same server is
+    /// retained until it is evicted from the buffer.
+    pub async fn get_logs(&self, id: Uuid, lines: usize) -> Result<Vec<LogLine>> {
+        let logs = self.log_buffer(id).await?;
+        Ok(logs.tail(lines))
+    }
+
+    /// Returns up to `lines` lines of console output captured after the line
+    /// with sequence number `seq`, oldest first.
+    pub async fn get_logs_since(&self, id: Uuid, seq: u64, lines: usize) -> Result<Vec<LogLine>> {
+        let logs = self.log_buffer(id).await?;
+        Ok(logs.since(seq, lines))
+    }
+
+    /// Subscribes to a server's console output, returning the last `lines`
+    /// lines alongside a receiver for every line captured from now on.
+    pub async fn subscribe_logs(&self, id: Uuid, lines: usize) -> Result<LogSubscription> {
+        let logs = self.log_buffer(id).await?;
+        Ok(logs.subscribe(lines))
+    }
+
+    /// Spawns a task which samples the resource usage of every running
+    /// server every `interval`, for as long as the process manager is in use,
+    /// and publishes each sample on the event bus. Sampling relies on /proc,
+    /// so nothing is recorded on other platforms.
+    pub fn spawn_sampler(&self, interval: Duration) -> JoinHandle<()> {
+        let servers = Arc::downgrade(&self.servers);
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let Some(servers) = servers.upgrade() else {
+                    return;
+                };
+
+                let running = servers
+                    .lock()
+                    .await
+                    .values()
+                    .filter_map(|server| Some((server.id, server.pid?, server.resources.clone())))
+                    .collect::<Vec<_>>();
+                for (id, pid, resources) in running {
+                    if let Some(sample) = resources.sample(pid) {
+                        events.publish(id, EventKind::Resources { sample });
+                    }
                 }
-                if let Some(mut stderr) = process.stderr.take() {
-                    stderr.read_to_string(&mut o_stderr).await?;
+            }
+        })
+    }
+
+    /// Spawns a task which queries every ready server over A2S each
+    /// `interval`, keeping the result for `status`. The task stops once
+    /// every clone of the ProcessManager is dropped.
+    pub fn spawn_query_poller(&self, interval: Duration) -> JoinHandle<()> {
+        let servers = Arc::downgrade(&self.servers);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let Some(servers) = servers.upgrade() else {
+                    return;
+                };
+
+                let ready = servers
+                    .lock()
+                    .await
+                    .values()
+                    .filter(|server| server.state == ServerState::Ready)
+                    .filter_map(|server| Some((server.id, server.a2s.clone()?)))
+                    .collect::<Vec<_>>();
+                for (id, client) in ready {
+                    let servers = Arc::downgrade(&servers);
+                    tokio::spawn(async move {
+                        let query = query(&client).await;
+                        if let Some(servers) = servers.upgrade() {
+                            if let Some(server) = servers.lock().await.get_mut(&id) {
+                                server.query = Some(query);
+                            }
+                        }
+                    });
                 }
             }
-            return Ok((o_stdout, o_stderr));
+        })
+    }
+
+    /// Returns a server's latest resource sample alongside up to `samples`
+    /// recent ones. See `spawn_sampler`.
+    pub async fn resources(&self, id: Uuid, samples: usize) -> Result<ResourceUsage> {
+        let servers = self.servers.lock().await;
+        let Some(server) = servers.get(&id) else {
+            return Err(Error::msg("No server registered by that ID."));
+        };
+
+        let current = server
+            .resources
+            .latest()
+            .filter(|sample| Some(sample.pid) == server.pid);
+        Ok(ResourceUsage {
+            current,
+            history: server.resources.tail(samples),
+        })
+    }
+
+    pub(crate) async fn log_buffer(&self, id: Uuid) -> Result<Arc<LogBuffer>> {
+        let servers = self.servers.lock().await;
+        if let Some(server) = servers.get(&id) {
+            return Ok(server.logs.clone());
+        }
+
+        Err(Error::msg("No server registered by that ID."))
+    }
+
+    pub(crate) async fn console(&self, id: Uuid) -> Result<Arc<ConsoleParser>> {
+        let servers = self.servers.lock().await;
+        if let Some(server) = servers.get(&id) {
+            return Ok(server.console.clone());
         }
 
         Err(Error::msg("No server registered by that ID."))
     }
 }
+
+/// Queries a server's info, players and rules. Rules are optional, so a
+/// server which doesn't answer A2S_RULES is still queried successfully.
+async fn query(client: &A2sClient) -> QueryStatus {
+    let at = Utc::now();
+    let (info, players, rules) = tokio::join!(client.info(), client.players(), client.rules());
+    let rules = rules.ok();
+    match (info, players) {
+        (Ok(info), Ok(players)) => QueryStatus {
+            at,
+            info: Some(info),
+            players,
+            rules,
+            error: None,
+        },
+        (info, players) => QueryStatus {
+            at,
+            error: info
+                .as_ref()
+                .err()
+                .or(players.as_ref().err())
+                .map(ToString::to_string),
+            info: info.ok(),
+            players: players.unwrap_or_default(),
+            rules,
+        },
+    }
+}
+
+fn new_log_buffer() -> Arc<LogBuffer> {
+    Arc::new(LogBuffer::new(DEFAULT_LOG_CAPACITY))
+}
+
+/// The directory Reforger writes the logs of each run to, under its profile.
+fn profile_logs(spec: &ServerSpec, paths: &ServerPaths) -> PathBuf {
+    match &spec.launch_options.profile {
+        Some(profile) => PathBuf::from(profile),
+        None => paths.profile.clone(),
+    }
+    .join("logs")
+}
+
+#[cfg(test)]
+mod tests {
+    use harm_schemas::ReadinessProbe;
+
+    use super::*;
+    use crate::{
+        logs::LogStream,
+        testing::{fake_spec, TestManager},
+    };
+
+    fn stop_options(grace_period: Duration, force: bool) -> StopOptions {
+        StopOptions {
+            grace_period,
+            force,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn starts_and_stops_gracefully() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        assert_eq!(t.pm.status(id).await.state, ServerState::Stopped);
+
+        t.pm.start_server(id, fake_spec(RestartPolicy::default()))
+            .await
+            .unwrap();
+        let status = t.wait_for(id, ServerState::Ready).await;
+        assert!(status.started_at.is_some());
+        assert!(!status.adopted);
+        let spawned = t.pm.fake_runtime().spawned(id).unwrap();
+        assert_eq!(
+            spawned.program,
+            PathBuf::from("/nonexistent/ArmaReforgerServer")
+        );
+        assert!(t.pm.server_paths(id).config.exists());
+
+        let outcome = t.pm.stop_server(id, StopOptions::default()).await.unwrap();
+        assert_eq!(outcome.method, StopMethod::Graceful);
+        assert_eq!(outcome.exit.unwrap().code, Some(0));
+
+        let status = t.pm.status(id).await;
+        assert_eq!(status.state, ServerState::Stopped);
+        assert_eq!(status.pid, None);
+        assert!(!t.pm.fake_runtime().is_running(id));
+    }
+
+    #[tokio::test]
+    async fn rejects_starting_twice_and_stopping_twice() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        let spec = fake_spec(RestartPolicy::default());
+
+        assert!(t.pm.stop_server(id, StopOptions::default()).await.is_err());
+        t.pm.start_server(id, spec.clone()).await.unwrap();
+        assert!(t.pm.start_server(id, spec).await.is_err());
+
+        t.pm.stop_server(id, StopOptions::default()).await.unwrap();
+        assert!(t.pm.stop_server(id, StopOptions::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn leaves_a_server_stopped_when_preflight_fails() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        let mut spec = fake_spec(RestartPolicy::default());
+        spec.config.game.name = String::new();
+        spec.launch_options.profile = Some(
+            t.pm.data_dir
+                .join("custom-profile")
+                .to_string_lossy()
+                .into_owned(),
+        );
+        let mut events = t.pm.events().subscribe();
+
+        let report = t.pm.preflight(id, &spec).await;
+        assert!(!report.passed());
+        assert!(!t.pm.data_dir.exists(), "preflight wrote to disk");
+
+        let error = t.pm.start_server(id, spec).await.unwrap_err();
+        assert!(error.downcast_ref::<PreflightFailed>().is_some());
+        let status = t.pm.status(id).await;
+        assert_eq!(status.state, ServerState::Stopped);
+        assert!(!status.rcon_connected);
+        assert!(events.try_recv().is_err());
+        assert!(t.pm.fake_runtime().spawned(id).is_none());
+    }
+
+    #[tokio::test]
+    async fn leaves_a_server_stopped_when_spawning_fails() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        t.pm.fake_runtime()
+            .fail_next_spawn(id, "no such executable");
+
+        let error =
+            t.pm.start_server(id, fake_spec(RestartPolicy::default()))
+                .await
+                .unwrap_err();
+        assert_eq!(error.to_string(), "no such executable");
+        let status = t.pm.status(id).await;
+        assert_eq!(status.state, ServerState::Stopped);
+        assert!(status.last_exit.is_none());
+        assert!(status.pid.is_none());
+        assert!(!status.rcon_connected);
+
+        // Only the failed spawn fails.
+        t.pm.start_server(id, fake_spec(RestartPolicy::default()))
+            .await
+            .unwrap();
+        t.wait_for(id, ServerState::Ready).await;
+    }
+
+    #[tokio::test]
+    async fn escalates_when_signals_are_ignored() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        t.pm.start_server(id, fake_spec(RestartPolicy::default()))
+            .await
+            .unwrap();
+        t.wait_for(id, ServerState::Ready).await;
+        t.pm.fake_runtime().ignore_signals(id, true);
+
+        let outcome =
+            t.pm.stop_server(id, stop_options(Duration::from_millis(50), false))
+                .await
+                .unwrap();
+        assert_eq!(outcome.method, StopMethod::Escalated);
+        assert_eq!(outcome.exit.unwrap().signal, Some(9));
+        assert_eq!(t.pm.status(id).await.state, ServerState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn force_stops_without_signalling() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        t.pm.start_server(id, fake_spec(RestartPolicy::default()))
+            .await
+            .unwrap();
+
+        let outcome =
+            t.pm.stop_server(id, stop_options(Duration::from_secs(30), true))
+                .await
+                .unwrap();
+        assert_eq!(outcome.method, StopMethod::Forced);
+        assert_eq!(outcome.exit.unwrap().signal, Some(9));
+    }
+
+    #[tokio::test]
+    async fn becomes_ready_once_its_probes_pass() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        let mut spec = fake_spec(RestartPolicy::default());
+        spec.readiness.probes = vec![ReadinessProbe::LogPattern {
+            pattern: String::from("Game successfully created"),
+        }];
+        t.pm.start_server(id, spec).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(t.pm.status(id).await.state, ServerState::Starting);
+
+        t.pm.fake_runtime()
+            .emit(id, LogStream::Stdout, "ENGINE : Game successfully created.");
+        t.wait_for(id, ServerState::Ready).await;
+    }
+
+    #[tokio::test]
+    async fn fails_a_server_which_never_becomes_ready() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        let mut spec = fake_spec(RestartPolicy::default());
+        spec.readiness.probes = vec![ReadinessProbe::LogPattern {
+            pattern: String::from("never printed"),
+        }];
+        spec.readiness.startup_timeout_secs = 1;
+        t.pm.start_server(id, spec).await.unwrap();
+
+        let status = t.wait_for(id, ServerState::Failed).await;
+        assert_eq!(status.last_exit.unwrap().code, Some(0));
+        assert!(!t.pm.fake_runtime().is_running(id));
+    }
+
+    #[tokio::test]
+    async fn publishes_state_changes() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        let mut events = t.pm.events().subscribe();
+        t.pm.start_server(id, fake_spec(RestartPolicy::default()))
+            .await
+            .unwrap();
+        t.wait_for(id, ServerState::Ready).await;
+        t.pm.stop_server(id, StopOptions::default()).await.unwrap();
+
+        let mut states = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let EventKind::StateChanged { state } = event.kind {
+                states.push(state);
+            }
+        }
+        assert_eq!(
+            states,
+            [
+                ServerState::Starting,
+                ServerState::Ready,
+                ServerState::Stopping,
+                ServerState::Stopped
+            ]
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use tokio::fs;
+use uuid::Uuid;
+
+/// The directories and files HARM keeps for a single server, all under
+/// `<data dir>/servers/<id>`:
+///
+/// - `config.json`: the Reforger config file, written on every start.
+/// - `profile/`: passed as `-profile` unless overridden; Reforger keeps its
+///   own logs and session saves in here.
+/// - `logs/`: the server's console output, as captured by HARM.
+/// - `addons/`: passed as `-addonDownloadDir` unless overridden.
+///
+/// The root is also the working directory of the server process.
+#[derive(Clone, Debug)]
+pub struct ServerPaths {
+    pub root: PathBuf,
+    pub config: PathBuf,
+    pub profile: PathBuf,
+    pub logs: PathBuf,
+    pub addons: PathBuf,
+}
+
+impl ServerPaths {
+    pub fn new(data_dir: &Path, id: Uuid) -> Self {
+        let root = data_dir.join("servers").join(id.to_string());
+        Self {
+            config: root.join("config.json"),
+            profile: root.join("profile"),
+            logs: root.join("logs"),
+            addons: root.join("addons"),
+            root,
+        }
+    }
+
+    /// The file the server's stdout is written to.
+    pub fn stdout_log(&self) -> PathBuf {
+        self.logs.join("console.stdout.log")
+    }
+
+    /// The file the server's stderr is written to.
+    pub fn stderr_log(&self) -> PathBuf {
+        self.logs.join("console.stderr.log")
+    }
+
+    /// Creates the server's directory layout, if it doesn't already exist.
+    pub async fn create(&self) -> std::io::Result<()> {
+        for dir in [&self.profile, &self.logs, &self.addons] {
+            fs::create_dir_all(dir).await?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the server's directory and everything in it.
+    pub async fn remove(&self) -> std::io::Result<()> {
+        match fs::remove_dir_all(&self.root).await {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Moves the server's directory to `<data dir>/archive/<id>-<timestamp>`
+    /// and returns the path it was moved to.
+    pub async fn archive(&self, data_dir: &Path) -> std::io::Result<PathBuf> {
+        let archive_dir = data_dir.join("archive");
+        fs::create_dir_all(&archive_dir).await?;
+
+        let name = format!(
+            "{}-{}",
+            self.root.file_name().unwrap_or_default().to_string_lossy(),
+            Utc::now().format("%Y%m%dT%H%M%SZ")
+        );
+        let target = archive_dir.join(name);
+        fs::rename(&self.root, &target).await?;
+
+        Ok(target)
+    }
+}
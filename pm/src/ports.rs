@@ -0,0 +1,182 @@
+use std::{
+    net::{IpAddr, UdpSocket},
+    ops::RangeInclusive,
+    str::FromStr,
+};
+
+use anyhow::{Error, Result};
+use harm_schemas::ServerConfig;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The ports a server listens on. Reforger and its A2S and RCON endpoints
+/// all use UDP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerPorts {
+    /// The game port: `bindPort` if set, otherwise `publicPort`.
+    pub game: u16,
+    pub a2s: u16,
+    pub rcon: u16,
+}
+
+impl ServerPorts {
+    /// Reads the ports a server config listens on.
+    pub fn of(config: &ServerConfig) -> Self {
+        Self {
+            game: config.bind_port.unwrap_or(config.public_port),
+            a2s: config.a2s.port,
+            rcon: config.rcon.port,
+        }
+    }
+
+    /// Writes the ports into a server config. The public port follows the
+    /// game port, as it is what clients are told to connect to.
+    pub fn apply(&self, config: &mut ServerConfig) {
+        config.public_port = self.game;
+        if config.bind_port.is_some() {
+            config.bind_port = Some(self.game);
+        }
+        config.a2s.port = self.a2s;
+        config.rcon.port = self.rcon;
+    }
+
+    fn named(&self) -> [(&'static str, u16); 3] {
+        [("game", self.game), ("a2s", self.a2s), ("rcon", self.rcon)]
+    }
+
+    fn contains(&self, port: u16) -> bool {
+        self.named().iter().any(|(_, p)| *p == port)
+    }
+}
+
+/// A port one server wants which is already taken.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PortConflict {
+    /// Which of the server's ports collides: `game`, `a2s` or `rcon`.
+    pub field: String,
+    pub port: u16,
+    /// The other server using the port, or `None` if the server uses the
+    /// same port twice.
+    pub server_id: Option<Uuid>,
+}
+
+/// Returns every port in `ports` which is used twice by the same server or
+/// used by one of `others`. Ports are compared regardless of the address they
+/// are bound to.
+pub fn conflicts(ports: &ServerPorts, others: &[(Uuid, ServerPorts)]) -> Vec<PortConflict> {
+    let named = ports.named();
+    let mut conflicts = Vec::new();
+
+    for (i, (field, port)) in named.iter().enumerate() {
+        if named[..i].iter().any(|(_, p)| p == port) {
+            conflicts.push(PortConflict {
+                field: field.to_string(),
+                port: *port,
+                server_id: None,
+            });
+        }
+        for (id, other) in others {
+            if other.contains(*port) {
+                conflicts.push(PortConflict {
+                    field: field.to_string(),
+                    port: *port,
+                    server_id: Some(*id),
+                });
+            }
+        }
+    }
+
+    conflicts
+}
+
+/// The range of ports HARM hands out to new servers, e.g. `2001-2300`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PortPool(RangeInclusive<u16>);
+
+impl PortPool {
+    pub fn new(range: RangeInclusive<u16>) -> Self {
+        Self(range)
+    }
+
+    /// Picks three ports from the pool which no server in `used` listens on
+    /// and which are free to bind on `address` right now.
+    pub fn allocate(&self, address: &str, used: &[ServerPorts]) -> Result<ServerPorts> {
+        let mut free = self
+            .0
+            .clone()
+            .filter(|port| !used.iter().any(|ports| ports.contains(*port)))
+            .filter(|port| bind_check(address, *port).is_ok());
+
+        match (free.next(), free.next(), free.next()) {
+            (Some(game), Some(a2s), Some(rcon)) => Ok(ServerPorts { game, a2s, rcon }),
+            _ => Err(Error::msg(format!(
+                "The port pool {}-{} has no room for another server.",
+                self.0.start(),
+                self.0.end()
+            ))),
+        }
+    }
+}
+
+impl Default for PortPool {
+    fn default() -> Self {
+        Self(2001..=2300)
+    }
+}
+
+impl FromStr for PortPool {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| String::from("expected a range like 2001-2300"))?;
+        let start: u16 = start.trim().parse().map_err(|_| "invalid start port")?;
+        let end: u16 = end.trim().parse().map_err(|_| "invalid end port")?;
+        if start == 0 || start > end {
+            return Err(String::from(
+                "the range must be non-empty and start above 0",
+            ));
+        }
+        Ok(Self(start..=end))
+    }
+}
+
+/// The address to reach a local server's endpoint bound to `address` on.
+/// Endpoints bound to every address are reached over loopback.
+pub fn local_address(address: &str) -> IpAddr {
+    match address.parse::<IpAddr>() {
+        Ok(ip) if !ip.is_unspecified() => ip,
+        _ => IpAddr::from([127, 0, 0, 1]),
+    }
+}
+
+/// Checks that a UDP socket can be bound to `address:port`.
+fn bind_check(address: &str, port: u16) -> std::io::Result<()> {
+    UdpSocket::bind((address, port)).map(|_| ())
+}
+
+/// Checks that every port a server config listens on can be bound, so a
+/// server doesn't start only to fail when it binds.
+pub fn preflight(config: &ServerConfig) -> Result<()> {
+    let addresses = [
+        &config.bind_address,
+        &config.a2s.address,
+        &config.rcon.address,
+    ];
+    let ports = ServerPorts::of(config).named();
+
+    for ((field, port), address) in ports.iter().zip(addresses) {
+        if let Err(e) = bind_check(address, *port) {
+            return Err(Error::msg(format!(
+                "The {} port {} on {} can't be bound: {}",
+                field, port, address, e
+            )));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,382 @@
+use std::path::{Path, PathBuf};
+
+use harm_schemas::{RuntimeKind, ServerConfig, Severity};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::{manager::ServerSpec, paths::ServerPaths, ports};
+
+/// Servers aren't started with less free disk space than this in the data
+/// directory.
+pub const MIN_FREE_DISK_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Below this much free disk space a warning is raised.
+pub const LOW_FREE_DISK_BYTES: u64 = 5 * MIN_FREE_DISK_BYTES;
+
+/// The outcome of a single preflight check. Ordered from best to worst.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum CheckStatus {
+    Pass,
+    /// Worth fixing, but doesn't stop the server from starting.
+    Warn,
+    /// Stops the server from starting.
+    Fail,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckResult {
+    /// Which check this is, e.g. `executable` or `ports`.
+    pub check: String,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+/// The results of every preflight check run before a server is started.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PreflightReport {
+    /// The worst status of any check.
+    pub status: CheckStatus,
+    pub checks: Vec<CheckResult>,
+}
+
+impl PreflightReport {
+    fn new(checks: Vec<CheckResult>) -> Self {
+        let status = checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(CheckStatus::Pass);
+        Self { status, checks }
+    }
+
+    /// Whether the server may be started.
+    pub fn passed(&self) -> bool {
+        self.status != CheckStatus::Fail
+    }
+
+    /// The messages of every failed check.
+    pub fn failures(&self) -> impl Iterator<Item = &str> {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+            .map(|check| check.message.as_str())
+    }
+}
+
+/// The error returned when a server can't be started because its preflight
+/// checks failed. The full report is attached.
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("Preflight checks failed: {}", .0.failures().collect::<Vec<_>>().join("; "))]
+pub struct PreflightFailed(pub PreflightReport);
+
+fn result(check: &str, status: CheckStatus, message: impl Into<String>) -> CheckResult {
+    CheckResult {
+        check: check.to_string(),
+        status,
+        message: message.into(),
+    }
+}
+
+/// Runs every preflight check for a server. `running` is whether the server
+/// is already running, in which case its ports are expected to be in use.
+/// Nothing is written to disk, so the checks can be run at any time.
+pub(crate) async fn run(
+    executable: &Path,
+    data_dir: &Path,
+    paths: &ServerPaths,
+    spec: &ServerSpec,
+    running: bool,
+) -> PreflightReport {
+    let mut checks = vec![
+        check_executable(executable, spec.runtime.kind).await,
+        check_directories(paths, spec).await,
+        check_ports(&spec.config, running),
+        check_disk_space(data_dir),
+    ];
+    checks.extend(check_config(&spec.config));
+    checks.push(check_mods(paths, spec).await);
+
+    PreflightReport::new(checks)
+}
+
+async fn check_executable(executable: &Path, runtime: RuntimeKind) -> CheckResult {
+    const CHECK: &str = "executable";
+
+    if runtime == RuntimeKind::Fake {
+        return result(CHECK, CheckStatus::Pass, "The fake runtime runs nothing");
+    }
+
+    let metadata = match fs::metadata(executable).await {
+        Ok(metadata) if metadata.is_file() => metadata,
+        Ok(_) => {
+            return result(
+                CHECK,
+                CheckStatus::Fail,
+                format!("{} is not a file", executable.display()),
+            )
+        }
+        Err(e) => {
+            return result(
+                CHECK,
+                CheckStatus::Fail,
+                format!("{} can't be read: {}", executable.display(), e),
+            )
+        }
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return result(
+                CHECK,
+                CheckStatus::Fail,
+                format!("{} is not executable", executable.display()),
+            );
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+
+    result(
+        CHECK,
+        CheckStatus::Pass,
+        format!("{} is executable", executable.display()),
+    )
+}
+
+async fn check_directories(paths: &ServerPaths, spec: &ServerSpec) -> CheckResult {
+    const CHECK: &str = "directories";
+
+    let mut dirs = vec![paths.root.clone(), paths.logs.clone()];
+    dirs.push(match &spec.launch_options.profile {
+        Some(profile) => PathBuf::from(profile),
+        None => paths.profile.clone(),
+    });
+    dirs.push(match &spec.launch_options.addon_download_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => paths.addons.clone(),
+    });
+
+    for dir in &dirs {
+        if let Err(e) = check_writable(dir).await {
+            return result(
+                CHECK,
+                CheckStatus::Fail,
+                format!("{} is not writable: {}", dir.display(), e),
+            );
+        }
+    }
+
+    result(
+        CHECK,
+        CheckStatus::Pass,
+        "The server's directories are writable",
+    )
+}
+
+/// Checks that `dir` could be written to, without changing anything: it must
+/// be a writable directory, or not exist yet under one, as the server's
+/// directories are created when it starts.
+async fn check_writable(dir: &Path) -> std::io::Result<()> {
+    let mut existing = dir;
+    loop {
+        match fs::metadata(existing).await {
+            Ok(metadata) if metadata.is_dir() => return writable(existing),
+            Ok(_) => {
+                return Err(std::io::Error::other(format!(
+                    "{} is not a directory",
+                    existing.display()
+                )))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                existing = match existing.parent() {
+                    Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+                    Some(parent) => parent,
+                    None => return Err(e),
+                };
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Whether files can be created in an existing directory.
+#[cfg(unix)]
+fn writable(dir: &Path) -> std::io::Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(dir.as_os_str().as_bytes())?;
+    // SAFETY: path is a valid C string.
+    if unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+fn writable(dir: &Path) -> std::io::Result<()> {
+    if std::fs::metadata(dir)?.permissions().readonly() {
+        return Err(std::io::ErrorKind::PermissionDenied.into());
+    }
+    Ok(())
+}
+
+fn check_ports(config: &ServerConfig, running: bool) -> CheckResult {
+    const CHECK: &str = "ports";
+
+    if running {
+        return result(
+            CHECK,
+            CheckStatus::Warn,
+            "The server is running, so its ports are in use by it and weren't checked",
+        );
+    }
+
+    match ports::preflight(config) {
+        Ok(()) => result(CHECK, CheckStatus::Pass, "Every port is free"),
+        Err(e) => result(CHECK, CheckStatus::Fail, e.to_string()),
+    }
+}
+
+fn check_disk_space(data_dir: &Path) -> CheckResult {
+    const CHECK: &str = "diskSpace";
+    const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+    let Some(free) = free_space(data_dir) else {
+        return result(
+            CHECK,
+            CheckStatus::Warn,
+            format!("Free space in {} couldn't be checked", data_dir.display()),
+        );
+    };
+
+    let message = format!(
+        "{:.1} GiB free in {}",
+        free as f64 / GIB,
+        data_dir.display()
+    );
+    let status = if free < MIN_FREE_DISK_BYTES {
+        CheckStatus::Fail
+    } else if free < LOW_FREE_DISK_BYTES {
+        CheckStatus::Warn
+    } else {
+        CheckStatus::Pass
+    };
+    result(CHECK, status, message)
+}
+
+/// The space available to unprivileged users on the filesystem holding
+/// `path`.
+#[cfg(unix)]
+fn free_space(path: &Path) -> Option<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs is plain data, and all zeroes is a valid value.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: path is a valid C string and stat a valid statvfs.
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Sanity checks on the stored config which Reforger would otherwise only
+/// report once it fails to start.
+fn check_config(config: &ServerConfig) -> Vec<CheckResult> {
+    const CHECK: &str = "config";
+
+    let mut problems: Vec<_> = config
+        .validate()
+        .issues
+        .into_iter()
+        .map(|issue| {
+            let status = match issue.severity {
+                Severity::Error => CheckStatus::Fail,
+                Severity::Warning => CheckStatus::Warn,
+            };
+            result(
+                CHECK,
+                status,
+                format!("{}: {}", issue.pointer, issue.message),
+            )
+        })
+        .collect();
+
+    if problems.is_empty() {
+        problems.push(result(CHECK, CheckStatus::Pass, "The config is valid"));
+    }
+    problems
+}
+
+/// Checks which mods have already been downloaded. Reforger downloads
+/// missing mods itself when it starts, so missing mods are only a warning.
+async fn check_mods(paths: &ServerPaths, spec: &ServerSpec) -> CheckResult {
+    const CHECK: &str = "mods";
+
+    let mods = &spec.config.game.mods;
+    if mods.is_empty() {
+        return result(CHECK, CheckStatus::Pass, "No mods are configured");
+    }
+
+    let mut dirs = vec![match &spec.launch_options.addon_download_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => paths.addons.clone(),
+    }];
+    dirs.extend(spec.launch_options.addons_dir.iter().map(PathBuf::from));
+
+    // Reforger keeps each addon in a directory ending in its ID.
+    let mut present = Vec::new();
+    for dir in dirs {
+        let Ok(mut entries) = fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            present.push(entry.file_name().to_string_lossy().to_uppercase());
+        }
+    }
+
+    let missing: Vec<_> = mods
+        .iter()
+        .filter(|m| {
+            let id = m.mod_id.to_uppercase();
+            !present.iter().any(|name| name.ends_with(&id))
+        })
+        .map(|m| match m.name.is_empty() {
+            true => m.mod_id.clone(),
+            false => format!("{} ({})", m.name, m.mod_id),
+        })
+        .collect();
+
+    if missing.is_empty() {
+        return result(
+            CHECK,
+            CheckStatus::Pass,
+            format!("All {} mods are downloaded", mods.len()),
+        );
+    }
+
+    result(
+        CHECK,
+        CheckStatus::Warn,
+        format!(
+            "{} of {} mods will be downloaded on start: {}",
+            missing.len(),
+            mods.len(),
+            missing.join(", ")
+        ),
+    )
+}
@@ -0,0 +1,138 @@
+//! Helpers for inspecting processes through Linux's /proc filesystem. On
+//! other platforms every lookup returns `None`.
+
+/// Returns the fields of `/proc/<pid>/stat` which follow the process name,
+/// so that index 0 is the process state (field 3 in proc(5)).
+#[cfg(target_os = "linux")]
+fn stat_fields(pid: u32) -> Option<Vec<String>> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The process name is wrapped in parentheses and may itself contain
+    // spaces or parentheses, so split after the last closing one.
+    let rest = &stat[stat.rfind(')')? + 1..];
+    Some(rest.split_whitespace().map(String::from).collect())
+}
+
+/// Returns when the process started, in clock ticks since boot. Together
+/// with the pid this uniquely identifies a process, as pids are reused.
+#[cfg(target_os = "linux")]
+pub fn start_time(pid: u32) -> Option<u64> {
+    // starttime is field 22 in proc(5).
+    stat_fields(pid)?.get(19)?.parse().ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn start_time(_pid: u32) -> Option<u64> {
+    None
+}
+
+/// Returns the command line the process was started with.
+#[cfg(target_os = "linux")]
+pub fn cmdline(pid: u32) -> Option<Vec<String>> {
+    let raw = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    Some(
+        raw.split(|b| *b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect(),
+    )
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn cmdline(_pid: u32) -> Option<Vec<String>> {
+    None
+}
+
+/// Whether the process `pid` is alive, is the same process that had
+/// `start_time` when it was recorded, and has not yet exited (zombies count
+/// as exited).
+#[cfg(target_os = "linux")]
+pub fn is_alive(pid: u32, start_time: u64) -> bool {
+    match stat_fields(pid) {
+        Some(fields) => {
+            fields.first().map(String::as_str) != Some("Z")
+                && fields.get(19).and_then(|f| f.parse().ok()) == Some(start_time)
+        }
+        None => false,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_alive(_pid: u32, _start_time: u64) -> bool {
+    false
+}
+
+/// Raw resource counters for a process, as read from /proc.
+#[derive(Clone, Debug, Default)]
+pub struct ProcStats {
+    /// CPU time spent in user and kernel mode, in clock ticks.
+    pub cpu_ticks: u64,
+    pub rss_bytes: u64,
+    pub threads: u32,
+    /// None if /proc/<pid>/fd can't be read, which needs the same user as
+    /// the process.
+    pub open_fds: Option<u32>,
+    /// Bytes read from and written to storage. None if /proc/<pid>/io can't
+    /// be read, for the same reason as `open_fds`.
+    pub read_bytes: Option<u64>,
+    pub write_bytes: Option<u64>,
+}
+
+/// Reads the resource counters of a process from `/proc/<pid>/stat`,
+/// `status`, `io` and `fd`.
+#[cfg(target_os = "linux")]
+pub fn stats(pid: u32) -> Option<ProcStats> {
+    let fields = stat_fields(pid)?;
+    // utime and stime are fields 14 and 15 in proc(5).
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let status_field = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.split_whitespace().next())
+            .and_then(|value| value.parse::<u64>().ok())
+    };
+
+    let io = std::fs::read_to_string(format!("/proc/{}/io", pid)).ok();
+    let io_field = |name: &str| {
+        io.as_deref()?
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.trim().parse::<u64>().ok())
+    };
+
+    Some(ProcStats {
+        cpu_ticks: utime + stime,
+        // Kernel threads have no VmRSS line at all.
+        rss_bytes: status_field("VmRSS:").unwrap_or(0) * 1024,
+        threads: status_field("Threads:").unwrap_or(0) as u32,
+        open_fds: std::fs::read_dir(format!("/proc/{}/fd", pid))
+            .ok()
+            .map(|entries| entries.count() as u32),
+        read_bytes: io_field("read_bytes:"),
+        write_bytes: io_field("write_bytes:"),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn stats(_pid: u32) -> Option<ProcStats> {
+    None
+}
+
+/// The number of clock ticks per second, which CPU times in /proc are
+/// measured in.
+#[cfg(target_os = "linux")]
+pub fn clock_ticks() -> u64 {
+    // SAFETY: sysconf(3) has no memory safety requirements.
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as u64,
+        _ => 100,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn clock_ticks() -> u64 {
+    100
+}
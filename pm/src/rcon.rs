@@ -0,0 +1,1010 @@
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+use anyhow::{Error, Result};
+use harm_schemas::{RconConfig, RconPermission};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    net::UdpSocket,
+    sync::{broadcast, mpsc, oneshot, watch},
+    time::Instant,
+};
+
+use crate::ports;
+
+/// How long to wait for the server to answer a login.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a command may take to be answered, including resends.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for a command to be answered before sending it again.
+const RESEND_INTERVAL: Duration = Duration::from_secs(2);
+
+/// BattlEye drops clients which send nothing for 45 seconds, so an empty
+/// command is sent when the connection has been idle for this long.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The connection is considered lost when nothing is received for this long,
+/// despite keep-alives.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// How long to wait before logging in again after the connection is lost or
+/// a login fails.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// How often pending commands and the connection's health are checked.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many server messages a subscriber may fall behind before it starts
+/// missing them.
+const MESSAGE_CAPACITY: usize = 256;
+
+/// Commands which only read the server's state, and so may be run with the
+/// monitor permission. Anything else needs the admin permission.
+const MONITOR_COMMANDS: &[&str] = &["#players", "#ban list"];
+
+const LOGIN: u8 = 0x00;
+const COMMAND: u8 = 0x01;
+const SERVER_MESSAGE: u8 = 0x02;
+
+/// Builds a BattlEye RCON packet: `BE`, the CRC32 of the rest of the packet,
+/// then 0xFF, the packet type and its payload.
+fn packet(kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut body = vec![0xFF, kind];
+    body.extend_from_slice(payload);
+
+    let mut packet = b"BE".to_vec();
+    packet.extend_from_slice(&crc32fast::hash(&body).to_le_bytes());
+    packet.extend_from_slice(&body);
+    packet
+}
+
+/// Checks a packet's header and checksum, and returns its type and payload.
+fn parse(packet: &[u8]) -> Option<(u8, &[u8])> {
+    if packet.len() < 8 || &packet[..2] != b"BE" || packet[6] != 0xFF {
+        return None;
+    }
+    let crc = u32::from_le_bytes(packet[2..6].try_into().ok()?);
+    if crc32fast::hash(&packet[6..]) != crc {
+        return None;
+    }
+    Some((packet[7], &packet[8..]))
+}
+
+/// How long a connection waits for the server, and between the things it
+/// does unprompted. Only tests shorten these.
+#[derive(Clone, Copy, Debug)]
+struct Timings {
+    login_timeout: Duration,
+    command_timeout: Duration,
+    resend_interval: Duration,
+    keepalive_interval: Duration,
+    connection_timeout: Duration,
+    reconnect_delay: Duration,
+    tick_interval: Duration,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self {
+            login_timeout: LOGIN_TIMEOUT,
+            command_timeout: COMMAND_TIMEOUT,
+            resend_interval: RESEND_INTERVAL,
+            keepalive_interval: KEEPALIVE_INTERVAL,
+            connection_timeout: CONNECTION_TIMEOUT,
+            reconnect_delay: RECONNECT_DELAY,
+            tick_interval: TICK_INTERVAL,
+        }
+    }
+}
+
+/// Sends a login packet and waits up to `timeout` for the answer. Returns
+/// whether the password was accepted, or an error if the server didn't
+/// answer.
+async fn login(socket: &UdpSocket, password: &str, timeout: Duration) -> Result<bool> {
+    socket.send(&packet(LOGIN, password.as_bytes())).await?;
+
+    let mut buf = vec![0; 64];
+    let deadline = Instant::now() + timeout;
+    loop {
+        let len = tokio::time::timeout_at(deadline, socket.recv(&mut buf))
+            .await
+            .map_err(|_| Error::msg("The server did not answer the RCON login."))??;
+        if let Some((LOGIN, payload)) = parse(&buf[..len]) {
+            return Ok(payload.first() == Some(&0x01));
+        }
+    }
+}
+
+async fn connect(address: SocketAddr) -> Result<UdpSocket> {
+    let socket = UdpSocket::bind(match address {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    })
+    .await?;
+    socket.connect(address).await?;
+    Ok(socket)
+}
+
+/// Logs in once and returns whether the password was accepted, without
+/// keeping the connection open.
+pub async fn check_login(address: SocketAddr, password: &str) -> Result<bool> {
+    login(&connect(address).await?, password, LOGIN_TIMEOUT).await
+}
+
+/// The address of the RCON endpoint in a server config. Servers bound to
+/// every address are reached over loopback.
+pub fn address(config: &RconConfig) -> SocketAddr {
+    SocketAddr::new(ports::local_address(&config.address), config.port)
+}
+
+struct Request {
+    command: String,
+    reply: oneshot::Sender<Result<String>>,
+}
+
+/// A command sent to the server and not yet fully answered.
+struct PendingCommand {
+    packet: Vec<u8>,
+    sent_at: Instant,
+    /// The parts of a multi-part answer received so far.
+    parts: Vec<Option<Vec<u8>>>,
+    /// Keep-alives have nobody waiting for their answer.
+    reply: Option<oneshot::Sender<Result<String>>>,
+}
+
+/// A BattlEye RCON connection to a server, kept alive in the background.
+///
+/// The connection logs in as soon as it is created and logs in again
+/// whenever it is lost, e.g. because the server restarted. Messages the
+/// server sends unprompted, such as chat, are acknowledged and can be
+/// received with `messages`. Cloning an RconClient is cheap, and the
+/// connection is closed once every clone is dropped.
+#[derive(Clone, Debug)]
+pub struct RconClient {
+    requests: mpsc::Sender<Request>,
+    messages: broadcast::Sender<String>,
+    connected: watch::Receiver<bool>,
+    command_timeout: Duration,
+}
+
+impl RconClient {
+    pub fn connect(address: SocketAddr, password: String) -> Self {
+        Self::connect_with(address, password, Timings::default())
+    }
+
+    fn connect_with(address: SocketAddr, password: String, timings: Timings) -> Self {
+        let (requests, requests_rx) = mpsc::channel(16);
+        let (messages, _) = broadcast::channel(MESSAGE_CAPACITY);
+        let (connected_tx, connected) = watch::channel(false);
+
+        tokio::spawn(run(
+            address,
+            password,
+            requests_rx,
+            messages.clone(),
+            connected_tx,
+            timings,
+        ));
+
+        Self {
+            requests,
+            messages,
+            connected,
+            command_timeout: timings.command_timeout,
+        }
+    }
+
+    /// Connects to the RCON endpoint in a server config.
+    pub fn for_config(config: &RconConfig) -> Self {
+        Self::connect(address(config), config.password.clone())
+    }
+
+    /// Whether the client is logged in.
+    pub fn is_connected(&self) -> bool {
+        *self.connected.borrow()
+    }
+
+    /// Watches whether the client is logged in, e.g. to act whenever it logs
+    /// in again after the server restarted.
+    pub fn connection(&self) -> watch::Receiver<bool> {
+        self.connected.clone()
+    }
+
+    /// Runs a command, e.g. `#players`, and returns the server's answer.
+    pub async fn command(&self, command: &str) -> Result<String> {
+        if !self.is_connected() {
+            return Err(Error::msg("Not connected to the server's RCON."));
+        }
+
+        let (reply, reply_rx) = oneshot::channel();
+        self.requests
+            .send(Request {
+                command: command.to_string(),
+                reply,
+            })
+            .await
+            .map_err(|_| Error::msg("The RCON connection was closed."))?;
+
+        tokio::time::timeout(self.command_timeout, reply_rx)
+            .await
+            .map_err(|_| Error::msg("The server did not answer the RCON command."))?
+            .map_err(|_| Error::msg("The RCON connection was closed."))?
+    }
+
+    /// Subscribes to messages the server sends unprompted, such as chat and
+    /// player connections.
+    pub fn messages(&self) -> broadcast::Receiver<String> {
+        self.messages.subscribe()
+    }
+}
+
+/// Why a connection ended.
+enum Disconnect {
+    /// The connection was lost, and should be made again.
+    Lost,
+    /// Every client was dropped.
+    Closed,
+}
+
+/// Keeps a connection to the server until every client is dropped.
+async fn run(
+    address: SocketAddr,
+    password: String,
+    mut requests: mpsc::Receiver<Request>,
+    messages: broadcast::Sender<String>,
+    connected: watch::Sender<bool>,
+    timings: Timings,
+) {
+    loop {
+        if requests.is_closed() {
+            return;
+        }
+
+        let socket = match connect(address).await {
+            Ok(socket) => socket,
+            Err(_) => {
+                tokio::time::sleep(timings.reconnect_delay).await;
+                continue;
+            }
+        };
+        match login(&socket, &password, timings.login_timeout).await {
+            Ok(true) => {}
+            // A rejected password, or a server which isn't up yet.
+            Ok(false) | Err(_) => {
+                tokio::time::sleep(timings.reconnect_delay).await;
+                continue;
+            }
+        }
+
+        connected.send_replace(true);
+        let disconnect = session(&socket, &mut requests, &messages, &timings).await;
+        connected.send_replace(false);
+
+        match disconnect {
+            Disconnect::Closed => return,
+            Disconnect::Lost => tokio::time::sleep(timings.reconnect_delay).await,
+        }
+    }
+}
+
+/// Runs commands over a logged in connection until it is lost.
+async fn session(
+    socket: &UdpSocket,
+    requests: &mut mpsc::Receiver<Request>,
+    messages: &broadcast::Sender<String>,
+    timings: &Timings,
+) -> Disconnect {
+    let mut pending: HashMap<u8, PendingCommand> = HashMap::new();
+    let mut next_seq: u8 = 0;
+    let mut last_sent = Instant::now();
+    let mut last_received = Instant::now();
+    let mut ticker = tokio::time::interval(timings.tick_interval);
+    let mut buf = vec![0; 4096];
+
+    let disconnect = loop {
+        tokio::select! {
+            request = requests.recv() => {
+                let Some(request) = request else {
+                    break Disconnect::Closed;
+                };
+                // Sequence numbers wrap, so one can only be reused once its
+                // command has been answered.
+                if pending.contains_key(&next_seq) {
+                    let _ = request.reply.send(Err(Error::msg("Too many RCON commands are pending.")));
+                    continue;
+                }
+
+                let mut payload = vec![next_seq];
+                payload.extend_from_slice(request.command.as_bytes());
+                let packet = packet(COMMAND, &payload);
+                if socket.send(&packet).await.is_err() {
+                    let _ = request.reply.send(Err(Error::msg("Could not send the RCON command.")));
+                    break Disconnect::Lost;
+                }
+
+                pending.insert(next_seq, PendingCommand {
+                    packet,
+                    sent_at: Instant::now(),
+                    parts: Vec::new(),
+                    reply: Some(request.reply),
+                });
+                next_seq = next_seq.wrapping_add(1);
+                last_sent = Instant::now();
+            }
+            len = socket.recv(&mut buf) => {
+                let Ok(len) = len else {
+                    break Disconnect::Lost;
+                };
+                let Some((kind, payload)) = parse(&buf[..len]) else {
+                    continue;
+                };
+                last_received = Instant::now();
+
+                match (kind, payload) {
+                    (COMMAND, [seq, rest @ ..]) => answer(&mut pending, *seq, rest),
+                    (SERVER_MESSAGE, [seq, message @ ..]) => {
+                        // Unacknowledged messages are sent again, and the
+                        // client is eventually dropped.
+                        let _ = socket.send(&packet(SERVER_MESSAGE, &[*seq])).await;
+                        let _ = messages.send(String::from_utf8_lossy(message).into_owned());
+                    }
+                    _ => {}
+                }
+            }
+            _ = ticker.tick() => {
+                if last_received.elapsed() >= timings.connection_timeout {
+                    break Disconnect::Lost;
+                }
+
+                for command in pending.values_mut() {
+                    if command.sent_at.elapsed() >= timings.resend_interval {
+                        let _ = socket.send(&command.packet).await;
+                        command.sent_at = Instant::now();
+                        last_sent = Instant::now();
+                    }
+                }
+                pending.retain(|_, command| {
+                    command.reply.as_ref().is_none_or(|reply| !reply.is_closed())
+                });
+
+                if last_sent.elapsed() >= timings.keepalive_interval && !pending.contains_key(&next_seq) {
+                    let packet = packet(COMMAND, &[next_seq]);
+                    let _ = socket.send(&packet).await;
+                    pending.insert(next_seq, PendingCommand {
+                        packet,
+                        sent_at: Instant::now(),
+                        parts: Vec::new(),
+                        reply: None,
+                    });
+                    next_seq = next_seq.wrapping_add(1);
+                    last_sent = Instant::now();
+                }
+            }
+        }
+    };
+
+    for (_, command) in pending {
+        if let Some(reply) = command.reply {
+            let _ = reply.send(Err(Error::msg("The RCON connection was lost.")));
+        }
+    }
+    disconnect
+}
+
+/// Records the answer to command `seq`. Long answers are split into parts,
+/// each starting with 0x00, the number of parts, and the part's index.
+fn answer(pending: &mut HashMap<u8, PendingCommand>, seq: u8, answer: &[u8]) {
+    let Some(command) = pending.get_mut(&seq) else {
+        return;
+    };
+
+    let body = match answer {
+        // Text answers never start with 0x00, so this is a broken part.
+        [0x00, total, index, ..] if *total == 0 || index >= total => return,
+        [0x00, total, index, part @ ..] => {
+            if command.parts.len() != *total as usize {
+                command.parts = vec![None; *total as usize];
+            }
+            command.parts[*index as usize] = Some(part.to_vec());
+            if command.parts.iter().any(Option::is_none) {
+                return;
+            }
+            command.parts.drain(..).flatten().flatten().collect()
+        }
+        _ => answer.to_vec(),
+    };
+
+    if let Some(command) = pending.remove(&seq) {
+        if let Some(reply) = command.reply {
+            let _ = reply.send(Ok(String::from_utf8_lossy(&body).into_owned()));
+        }
+    }
+}
+
+/// The permission needed to run a command. Only commands known not to change
+/// the server's state may be run by a monitor.
+pub fn required_permission(command: &str) -> RconPermission {
+    let command = command
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let read_only = MONITOR_COMMANDS.iter().any(|allowed| {
+        command == *allowed
+            || command
+                .strip_prefix(allowed)
+                .is_some_and(|rest| rest.starts_with(' '))
+    });
+    match read_only {
+        true => RconPermission::Monitor,
+        false => RconPermission::Admin,
+    }
+}
+
+/// Whether `granted` is enough to run commands which need `required`.
+pub fn permits(granted: &RconPermission, required: &RconPermission) -> bool {
+    matches!(
+        (granted, required),
+        (RconPermission::Admin, _) | (RconPermission::Monitor, RconPermission::Monitor)
+    )
+}
+
+/// The command which kicks a player, by their ID for this session.
+pub fn kick_command(player_id: u32, reason: &str) -> String {
+    format!("#kick {} {}", player_id, single_line(reason))
+        .trim_end()
+        .to_string()
+}
+
+/// The command which bans a player, by their session ID or identity ID, for
+/// `duration_secs` seconds, or for good if it is 0.
+pub fn ban_command(player: &str, duration_secs: u64, reason: &str) -> String {
+    format!(
+        "#ban create {} {} {}",
+        player,
+        duration_secs,
+        single_line(reason)
+    )
+    .trim_end()
+    .to_string()
+}
+
+/// The command which lifts a player's ban, by their identity ID.
+pub fn unban_command(player: &str) -> String {
+    format!("#ban remove {}", player)
+}
+
+/// The command which sends a message to every player.
+pub fn say_command(message: &str) -> String {
+    format!("say -1 {}", single_line(message))
+}
+
+/// Commands end at the first line break, so free text is kept to one line.
+fn single_line(text: &str) -> String {
+    text.split(['\r', '\n'])
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A player in the answer to `#players`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RconPlayer {
+    /// The player's ID for this session of the server, as used to kick them.
+    pub id: u32,
+    pub name: String,
+    /// The address and port the player connected from.
+    pub address: Option<String>,
+    /// The player's ping, in milliseconds.
+    pub ping: Option<u32>,
+    /// The player's BattlEye GUID.
+    pub guid: Option<String>,
+    /// Whether BattlEye has verified the player's GUID.
+    pub verified: bool,
+    /// Whether the player is still in the lobby.
+    pub lobby: bool,
+}
+
+/// Parses the answer to `#players`. BattlEye lists one player per line, as
+/// `0   1.2.3.4:2304   47   0123abcd(OK) Name`, between a header and a
+/// total. Lines which only hold an ID and a name are accepted too.
+pub fn parse_players(answer: &str) -> Vec<RconPlayer> {
+    answer.lines().filter_map(parse_player).collect()
+}
+
+fn parse_player(line: &str) -> Option<RconPlayer> {
+    let (id, rest) = next_field(line)?;
+    let id = id.parse().ok()?;
+
+    let mut player = RconPlayer {
+        id,
+        name: String::new(),
+        address: None,
+        ping: None,
+        guid: None,
+        verified: false,
+        lobby: false,
+    };
+
+    let mut name = rest;
+    if let Some((address, rest)) = next_field(rest).filter(|(field, _)| is_address(field)) {
+        player.address = Some(address.to_string());
+        name = rest;
+        if let Some((ping, rest)) = next_field(rest) {
+            if let Ok(ping) = ping.parse() {
+                player.ping = Some(ping);
+                name = rest;
+            }
+        }
+        if let Some((guid, rest)) = next_field(name) {
+            let (guid, verified) = match guid.split_once('(') {
+                Some((guid, status)) => (guid, status == "OK)"),
+                None => (guid, false),
+            };
+            if guid.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+                player.guid = Some(guid.to_string());
+                player.verified = verified;
+                name = rest;
+            }
+        }
+    }
+
+    let name = name.trim();
+    let (name, lobby) = match name.strip_suffix("(Lobby)") {
+        Some(name) => (name.trim_end(), true),
+        None => (name, false),
+    };
+    if name.is_empty() {
+        return None;
+    }
+    player.name = name.to_string();
+    player.lobby = lobby;
+    Some(player)
+}
+
+/// Splits the first whitespace-separated field off a line.
+fn next_field(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    if line.is_empty() {
+        return None;
+    }
+    Some(line.split_once(char::is_whitespace).unwrap_or((line, "")))
+}
+
+fn is_address(field: &str) -> bool {
+    field.parse::<SocketAddr>().is_ok()
+        || field
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    const PASSWORD: &str = "secret";
+
+    /// Timings short enough for tests to wait out.
+    fn timings() -> Timings {
+        Timings {
+            login_timeout: Duration::from_millis(200),
+            command_timeout: Duration::from_millis(400),
+            resend_interval: Duration::from_millis(100),
+            keepalive_interval: Duration::from_millis(300),
+            connection_timeout: Duration::from_millis(600),
+            reconnect_delay: Duration::from_millis(50),
+            tick_interval: Duration::from_millis(20),
+        }
+    }
+
+    /// A local BattlEye RCON server. It accepts `PASSWORD`, answers `#players`
+    /// in three parts sent in reverse order, never answers `ignored`, only
+    /// answers `flaky` when it is sent again, and echoes anything else.
+    struct FakeServer {
+        address: SocketAddr,
+        /// Every valid packet received, as its type and payload.
+        received: Arc<Mutex<Vec<(u8, Vec<u8>)>>>,
+        /// Messages to send to the last client which logged in.
+        messages: mpsc::UnboundedSender<(u8, String)>,
+    }
+
+    impl FakeServer {
+        async fn spawn() -> Self {
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let address = socket.local_addr().unwrap();
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let (messages, mut messages_rx) = mpsc::unbounded_channel::<(u8, String)>();
+
+            let log = received.clone();
+            tokio::spawn(async move {
+                let mut buf = [0; 4096];
+                let mut client = None;
+                loop {
+                    tokio::select! {
+                        recv = socket.recv_from(&mut buf) => {
+                            let Ok((len, from)) = recv else { return };
+                            let Some((kind, payload)) = parse(&buf[..len]) else {
+                                continue;
+                            };
+                            log.lock().unwrap().push((kind, payload.to_vec()));
+                            for reply in respond(kind, payload, &log) {
+                                socket.send_to(&reply, from).await.unwrap();
+                            }
+                            if kind == LOGIN {
+                                client = Some(from);
+                            }
+                        }
+                        Some((seq, message)) = messages_rx.recv() => {
+                            let mut payload = vec![seq];
+                            payload.extend_from_slice(message.as_bytes());
+                            if let Some(client) = client {
+                                let packet = packet(SERVER_MESSAGE, &payload);
+                                socket.send_to(&packet, client).await.unwrap();
+                            }
+                        }
+                    }
+                }
+            });
+
+            Self {
+                address,
+                received,
+                messages,
+            }
+        }
+
+        /// A client which is logged in.
+        async fn client(&self) -> RconClient {
+            let client = RconClient::connect_with(self.address, PASSWORD.to_string(), timings());
+            client
+                .connection()
+                .wait_for(|connected| *connected)
+                .await
+                .unwrap();
+            client
+        }
+
+        /// The commands received, as their sequence number and text.
+        fn commands(&self) -> Vec<(u8, String)> {
+            self.received
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(kind, _)| *kind == COMMAND)
+                .map(|(_, payload)| {
+                    (
+                        payload[0],
+                        String::from_utf8_lossy(&payload[1..]).into_owned(),
+                    )
+                })
+                .collect()
+        }
+    }
+
+    /// The packets sent in answer to a packet.
+    fn respond(kind: u8, payload: &[u8], log: &Mutex<Vec<(u8, Vec<u8>)>>) -> Vec<Vec<u8>> {
+        match (kind, payload) {
+            (LOGIN, password) => {
+                let accepted = password == PASSWORD.as_bytes();
+                vec![packet(LOGIN, &[accepted as u8])]
+            }
+            (COMMAND, [seq, command @ ..]) => {
+                let command = String::from_utf8_lossy(command);
+                let sent = log
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|(kind, sent)| *kind == COMMAND && sent == payload)
+                    .count();
+                match command.as_ref() {
+                    "ignored" => Vec::new(),
+                    "flaky" if sent < 2 => Vec::new(),
+                    "#players" => {
+                        let parts = [
+                            "Players on server:\n",
+                            "0   127.0.0.1:2001   ",
+                            "10   Alpha",
+                        ];
+                        let mut packets: Vec<_> = parts
+                            .iter()
+                            .enumerate()
+                            .map(|(index, part)| {
+                                let mut answer = vec![*seq, 0x00, parts.len() as u8, index as u8];
+                                answer.extend_from_slice(part.as_bytes());
+                                packet(COMMAND, &answer)
+                            })
+                            .collect();
+                        packets.reverse();
+                        packets
+                    }
+                    command => {
+                        let mut answer = vec![*seq];
+                        if !command.is_empty() {
+                            answer.extend_from_slice(format!("ok: {}", command).as_bytes());
+                        }
+                        vec![packet(COMMAND, &answer)]
+                    }
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn packets_round_trip() {
+        let built = packet(COMMAND, &[7, b'h', b'i']);
+        assert_eq!(&built[..2], b"BE");
+        assert_eq!(&built[6..8], &[0xFF, COMMAND]);
+        assert_eq!(parse(&built), Some((COMMAND, &[7, b'h', b'i'][..])));
+        assert_eq!(parse(&packet(LOGIN, &[])), Some((LOGIN, &[][..])));
+    }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        let built = packet(COMMAND, &[7, b'h', b'i']);
+
+        let mut corrupted = built.clone();
+        *corrupted.last_mut().unwrap() ^= 0x01;
+        assert_eq!(parse(&corrupted), None);
+
+        let mut bad_crc = built.clone();
+        bad_crc[2] ^= 0x01;
+        assert_eq!(parse(&bad_crc), None);
+
+        let mut bad_header = built.clone();
+        bad_header[0] = b'X';
+        assert_eq!(parse(&bad_header), None);
+
+        let mut bad_marker = built.clone();
+        bad_marker[6] = 0x00;
+        assert_eq!(parse(&bad_marker), None);
+
+        assert_eq!(parse(&built[..7]), None);
+    }
+
+    #[test]
+    fn reassembles_parts_in_any_order() {
+        let (reply, mut reply_rx) = oneshot::channel();
+        let mut pending = HashMap::new();
+        pending.insert(
+            3,
+            PendingCommand {
+                packet: Vec::new(),
+                sent_at: Instant::now(),
+                parts: Vec::new(),
+                reply: Some(reply),
+            },
+        );
+
+        answer(&mut pending, 3, &[0x00, 3, 2, b'c']);
+        answer(&mut pending, 3, &[0x00, 3, 0, b'a']);
+        // Answers to other commands, and parts out of range, are ignored.
+        answer(&mut pending, 4, &[0x00, 3, 1, b'x']);
+        answer(&mut pending, 3, &[0x00, 3, 3, b'x']);
+        assert!(reply_rx.try_recv().is_err());
+
+        answer(&mut pending, 3, &[0x00, 3, 1, b'b']);
+        assert_eq!(reply_rx.try_recv().unwrap().unwrap(), "abc");
+        assert!(pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn checks_logins() {
+        let server = FakeServer::spawn().await;
+        assert!(check_login(server.address, PASSWORD).await.unwrap());
+        assert!(!check_login(server.address, "wrong").await.unwrap());
+
+        // Nothing answers on a port nobody listens on.
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let socket = connect(silent.local_addr().unwrap()).await.unwrap();
+        let error = login(&socket, PASSWORD, timings().login_timeout)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The server did not answer the RCON login."
+        );
+    }
+
+    #[tokio::test]
+    async fn stays_disconnected_with_a_wrong_password() {
+        let server = FakeServer::spawn().await;
+        let client = RconClient::connect_with(server.address, String::from("wrong"), timings());
+
+        tokio::time::sleep(timings().reconnect_delay * 6).await;
+        assert!(!client.is_connected());
+        let error = client.command("#players").await.unwrap_err();
+        assert_eq!(error.to_string(), "Not connected to the server's RCON.");
+        // It kept trying to log in.
+        let logins = server.received.lock().unwrap().len();
+        assert!(logins > 1, "{} logins", logins);
+    }
+
+    #[tokio::test]
+    async fn runs_commands() {
+        let server = FakeServer::spawn().await;
+        let client = server.client().await;
+
+        assert_eq!(client.command("#shutdown").await.unwrap(), "ok: #shutdown");
+        assert_eq!(
+            client.command("#players").await.unwrap(),
+            "Players on server:\n0   127.0.0.1:2001   10   Alpha"
+        );
+        assert_eq!(
+            server.commands(),
+            [
+                (0, String::from("#shutdown")),
+                (1, String::from("#players"))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn wraps_sequence_numbers() {
+        let server = FakeServer::spawn().await;
+        let client = server.client().await;
+
+        for i in 0..300 {
+            let command = format!("say -1 {}", i);
+            let answer = client.command(&command).await.unwrap();
+            assert_eq!(answer, format!("ok: {}", command));
+        }
+        let seqs: Vec<_> = server.commands().iter().map(|(seq, _)| *seq).collect();
+        assert_eq!(&seqs[254..258], &[254, 255, 0, 1]);
+    }
+
+    #[tokio::test]
+    async fn resends_unanswered_commands() {
+        let server = FakeServer::spawn().await;
+        let client = server.client().await;
+
+        assert_eq!(client.command("flaky").await.unwrap(), "ok: flaky");
+        assert_eq!(server.commands().len(), 2);
+
+        let error = client.command("ignored").await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The server did not answer the RCON command."
+        );
+    }
+
+    #[tokio::test]
+    async fn acknowledges_server_messages() {
+        let server = FakeServer::spawn().await;
+        let client = server.client().await;
+        let mut messages = client.messages();
+
+        server
+            .messages
+            .send((9, String::from("Player #0 Alpha connected")))
+            .unwrap();
+        assert_eq!(messages.recv().await.unwrap(), "Player #0 Alpha connected");
+
+        // The acknowledgement is only the message's sequence number.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let received = server.received.lock().unwrap().clone();
+        assert!(received.contains(&(SERVER_MESSAGE, vec![9])));
+    }
+
+    #[tokio::test]
+    async fn keeps_an_idle_connection_alive() {
+        let server = FakeServer::spawn().await;
+        let client = server.client().await;
+
+        tokio::time::sleep(timings().keepalive_interval * 2).await;
+        assert_eq!(server.commands()[0], (0, String::new()));
+
+        // Answered keep-alives keep the connection up.
+        tokio::time::sleep(timings().connection_timeout * 2).await;
+        assert!(client.is_connected());
+        let answer = client.command("#players").await.unwrap();
+        assert!(answer.ends_with("Alpha"));
+    }
+
+    #[test]
+    fn parses_players() {
+        let answer = "Players on server:
+[#] [IP Address]:[Port] [Ping] [GUID] [Name]
+--------------------------------------------------
+0   192.168.1.10:2304     47   0123456789abcdef0123456789abcdef(OK) John Smith
+1   10.0.0.2:2304         120  fedcba9876543210fedcba9876543210(?) [TAG] Bob (Lobby)
+2   10.0.0.3:2304         65   Charlie Brown
+3   Delta [1st] Squad
+(4 players in total)";
+
+        let player = |id, name: &str| RconPlayer {
+            id,
+            name: name.to_string(),
+            address: None,
+            ping: None,
+            guid: None,
+            verified: false,
+            lobby: false,
+        };
+        assert_eq!(
+            parse_players(answer),
+            [
+                RconPlayer {
+                    address: Some(String::from("192.168.1.10:2304")),
+                    ping: Some(47),
+                    guid: Some(String::from("0123456789abcdef0123456789abcdef")),
+                    verified: true,
+                    ..player(0, "John Smith")
+                },
+                RconPlayer {
+                    address: Some(String::from("10.0.0.2:2304")),
+                    ping: Some(120),
+                    guid: Some(String::from("fedcba9876543210fedcba9876543210")),
+                    lobby: true,
+                    ..player(1, "[TAG] Bob")
+                },
+                RconPlayer {
+                    address: Some(String::from("10.0.0.3:2304")),
+                    ping: Some(65),
+                    ..player(2, "Charlie Brown")
+                },
+                player(3, "Delta [1st] Squad"),
+            ]
+        );
+        assert!(parse_players("Players on server:\n(0 players in total)").is_empty());
+    }
+
+    #[test]
+    fn only_lets_monitors_read() {
+        for command in ["#players", "  #PLAYERS ", "#ban list", "#ban   list 2"] {
+            assert_eq!(
+                required_permission(command),
+                RconPermission::Monitor,
+                "{}",
+                command
+            );
+        }
+        for command in [
+            "#shutdown",
+            "#playersx",
+            "#players;#shutdown",
+            "#ban create 1 0",
+            "#ban listing",
+            "#kick 0",
+            "say -1 hello",
+            "",
+        ] {
+            assert_eq!(
+                required_permission(command),
+                RconPermission::Admin,
+                "{}",
+                command
+            );
+        }
+
+        let monitor = RconPermission::Monitor;
+        let admin = RconPermission::Admin;
+        assert!(permits(&admin, &admin));
+        assert!(permits(&admin, &monitor));
+        assert!(permits(&monitor, &monitor));
+        assert!(!permits(&monitor, &admin));
+        assert!(!permits(&monitor, &required_permission("#shutdown")));
+    }
+
+    #[test]
+    fn builds_commands() {
+        assert_eq!(kick_command(3, ""), "#kick 3");
+        assert_eq!(kick_command(3, "Team killing"), "#kick 3 Team killing");
+        assert_eq!(
+            ban_command("0123abcd", 3600, "Cheating\n#shutdown"),
+            "#ban create 0123abcd 3600 Cheating #shutdown"
+        );
+        assert_eq!(ban_command("4", 0, " "), "#ban create 4 0");
+        assert_eq!(unban_command("0123abcd"), "#ban remove 0123abcd");
+        assert_eq!(
+            say_command(" Restart in\r\n5 minutes "),
+            "say -1 Restart in 5 minutes"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use harm_schemas::{ReadinessConfig, ReadinessProbe, ServerConfig};
+use regex::Regex;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    a2s::A2sClient,
+    logs::{LogBuffer, DEFAULT_LOG_CAPACITY},
+    rcon,
+};
+
+/// How long a single A2S probe waits for an answer.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Checks a readiness config: every log pattern must be a valid regular
+/// expression, and the timeout and interval must be above zero.
+pub fn check(readiness: &ReadinessConfig) -> Result<(), String> {
+    if readiness.startup_timeout_secs == 0 {
+        return Err(String::from("startupTimeoutSecs must be above 0"));
+    }
+    if readiness.interval_secs == 0 {
+        return Err(String::from("intervalSecs must be above 0"));
+    }
+    for probe in &readiness.probes {
+        if let ReadinessProbe::LogPattern { pattern } = probe {
+            Regex::new(pattern).map_err(|e| format!("Invalid log pattern: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Waits until every readiness probe has passed. Console output captured in
+/// `logs` from `since` onwards is matched against log patterns.
+pub(crate) async fn wait(
+    readiness: &ReadinessConfig,
+    config: &ServerConfig,
+    logs: &LogBuffer,
+    since: DateTime<Utc>,
+) {
+    let interval = Duration::from_secs(readiness.interval_secs.max(1));
+    for probe in &readiness.probes {
+        match probe {
+            ReadinessProbe::LogPattern { pattern } => match Regex::new(pattern) {
+                Ok(pattern) => wait_for_line(&pattern, logs, since).await,
+                // Patterns are checked when they are saved, so this only
+                // happens to configs stored before that.
+                Err(_) => std::future::pending().await,
+            },
+            ReadinessProbe::A2s => {
+                let client = A2sClient::for_config(&config.a2s).with_timeout(PROBE_TIMEOUT);
+                while client.info().await.is_err() {
+                    tokio::time::sleep(interval).await;
+                }
+            }
+            ReadinessProbe::Rcon => {
+                let address = rcon::address(&config.rcon);
+                while !matches!(
+                    rcon::check_login(address, &config.rcon.password).await,
+                    Ok(true)
+                ) {
+                    tokio::time::sleep(interval).await;
+                }
+            }
+        }
+    }
+}
+
+/// Waits for a line captured after `since` to match `pattern`.
+async fn wait_for_line(pattern: &Regex, logs: &LogBuffer, since: DateTime<Utc>) {
+    let subscription = logs.subscribe(DEFAULT_LOG_CAPACITY);
+    if subscription
+        .backlog
+        .iter()
+        .any(|line| line.timestamp >= since && pattern.is_match(&line.line))
+    {
+        return;
+    }
+
+    let mut receiver = subscription.receiver;
+    loop {
+        match receiver.recv().await {
+            Ok(line) if pattern.is_match(&line.line) => return,
+            Ok(_) | Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => std::future::pending().await,
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::procfs::{self, ProcStats};
+
+/// The default interval between resource samples.
+pub const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How many samples are kept per server. At the default interval this is ten
+/// minutes of history.
+pub const DEFAULT_HISTORY_CAPACITY: usize = 120;
+
+/// A server process's resource usage at a point in time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceSample {
+    pub at: DateTime<Utc>,
+    pub pid: u32,
+    /// CPU usage since the previous sample, as a percentage of one core, so
+    /// it can exceed 100 on multi-core hosts. None for the first sample of a
+    /// process.
+    pub cpu_percent: Option<f64>,
+    pub rss_bytes: u64,
+    pub threads: u32,
+    /// None if HARM isn't allowed to inspect the process's file descriptors.
+    pub open_fds: Option<u32>,
+    /// Total bytes the process has read from storage. None if HARM isn't
+    /// allowed to inspect the process's I/O.
+    pub read_bytes: Option<u64>,
+    /// Total bytes the process has written to storage.
+    pub write_bytes: Option<u64>,
+}
+
+/// A server's current resource usage alongside its recent history.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceUsage {
+    /// The latest sample, if the server is running and has been sampled.
+    pub current: Option<ResourceSample>,
+    /// Recent samples, oldest first, including those of earlier runs.
+    pub history: Vec<ResourceSample>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    samples: VecDeque<ResourceSample>,
+    /// The raw counters behind the latest sample, used to work out CPU usage
+    /// for the next one.
+    last: Option<(u32, DateTime<Utc>, ProcStats)>,
+}
+
+/// ResourceHistory keeps the most recent resource samples taken from a
+/// server, across restarts, discarding the oldest once it is full.
+#[derive(Debug)]
+pub struct ResourceHistory {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+impl ResourceHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Samples the process `pid` and records the result, returning it. Returns
+    /// `None` if the process can't be inspected, e.g. because it has exited.
+    pub fn sample(&self, pid: u32) -> Option<ResourceSample> {
+        let stats = procfs::stats(pid)?;
+        let at = Utc::now();
+
+        let mut inner = self.inner.lock().unwrap();
+        let cpu_percent = match &inner.last {
+            Some((last_pid, last_at, last)) if *last_pid == pid => {
+                let elapsed = (at - *last_at).num_milliseconds() as f64 / 1000.0;
+                let ticks = stats.cpu_ticks.saturating_sub(last.cpu_ticks) as f64;
+                (elapsed > 0.0).then(|| ticks / procfs::clock_ticks() as f64 / elapsed * 100.0)
+            }
+            _ => None,
+        };
+
+        let sample = ResourceSample {
+            at,
+            pid,
+            cpu_percent,
+            rss_bytes: stats.rss_bytes,
+            threads: stats.threads,
+            open_fds: stats.open_fds,
+            read_bytes: stats.read_bytes,
+            write_bytes: stats.write_bytes,
+        };
+
+        if inner.samples.len() == self.capacity {
+            inner.samples.pop_front();
+        }
+        inner.samples.push_back(sample.clone());
+        inner.last = Some((pid, at, stats));
+
+        Some(sample)
+    }
+
+    /// Returns the most recent sample, if there is one.
+    pub fn latest(&self) -> Option<ResourceSample> {
+        self.inner.lock().unwrap().samples.back().cloned()
+    }
+
+    /// Returns up to the last `n` samples, oldest first.
+    pub fn tail(&self, n: usize) -> Vec<ResourceSample> {
+        let inner = self.inner.lock().unwrap();
+        let skip = inner.samples.len().saturating_sub(n);
+        inner.samples.iter().skip(skip).cloned().collect()
+    }
+}
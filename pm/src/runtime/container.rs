@@ -0,0 +1,320 @@
+use std::{process::Stdio, sync::Arc, time::Duration};
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use chrono::{SecondsFormat, Utc};
+use harm_schemas::{ResourceLimits, RuntimeConfig};
+use tokio::{
+    process::{Child, Command},
+    sync::watch,
+    task::JoinHandle,
+};
+
+use super::{signal_name, unit_name, unknown_exit, wait_for_exit, Instance, Runtime, SpawnRequest};
+use crate::{
+    logs::{self, LogBuffer, LogStream},
+    manager::{ExitInfo, StopSignal},
+    paths::ServerPaths,
+    store::ProcessRecord,
+};
+
+/// How long to keep reading a stopped container's output before giving up
+/// on the log follower.
+const LOG_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The container engine CLI a ContainerRuntime drives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContainerEngine {
+    Podman,
+    Docker,
+}
+
+impl ContainerEngine {
+    fn program(&self) -> &'static str {
+        match self {
+            Self::Podman => "podman",
+            Self::Docker => "docker",
+        }
+    }
+}
+
+/// Runs servers as Podman or Docker containers, through the engine's CLI.
+///
+/// Containers are named `harm-<id>` and use the host network, so the ports in
+/// the server's config are used as-is. The Reforger install (read-only) and
+/// the server's data directory are mounted at the same paths as on the host,
+/// so the launch arguments need no rewriting. The image has to provide
+/// whatever the Reforger executable needs to run.
+#[derive(Clone, Debug)]
+pub struct ContainerRuntime {
+    engine: ContainerEngine,
+    image: String,
+    volumes: Vec<String>,
+}
+
+impl ContainerRuntime {
+    pub fn new(engine: ContainerEngine, config: &RuntimeConfig) -> Self {
+        Self {
+            engine,
+            image: config.image.clone().unwrap_or_default(),
+            volumes: config.volumes.clone(),
+        }
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(self.engine.program());
+        command.stdin(Stdio::null());
+        command
+    }
+
+    /// Runs an engine command to completion, returning its trimmed stdout.
+    async fn run(&self, args: &[&str]) -> Result<String> {
+        let output = self.command().args(args).output().await?;
+        if !output.status.success() {
+            return Err(Error::msg(format!(
+                "{} {} failed: {}",
+                self.engine.program(),
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Whether the named container is running, and its host pid if so.
+    async fn inspect(&self, name: &str) -> Result<Option<u32>> {
+        let state = self
+            .run(&[
+                "inspect",
+                "--format",
+                "{{.State.Running}} {{.State.Pid}}",
+                name,
+            ])
+            .await?;
+        Ok(match state.split_once(' ') {
+            Some(("true", pid)) => pid.parse().ok(),
+            _ => None,
+        })
+    }
+
+    /// Starts following a container's output into `logs`. If `since` is set,
+    /// only output from that point on is followed.
+    fn follow(
+        &self,
+        name: &str,
+        logs: Arc<LogBuffer>,
+        since: Option<String>,
+    ) -> Result<(Child, Vec<JoinHandle<()>>)> {
+        let mut command = self.command();
+        command.args(["logs", "--follow"]);
+        if let Some(since) = since {
+            command.args(["--since", &since]);
+        }
+        let mut child = command
+            .arg(name)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let mut readers = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(logs::spawn_reader(logs.clone(), LogStream::Stdout, stdout));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(logs::spawn_reader(logs, LogStream::Stderr, stderr));
+        }
+
+        Ok((child, readers))
+    }
+
+    /// Watches for the named container to exit, in the background.
+    fn watch(&self, name: &str) -> watch::Receiver<Option<ExitInfo>> {
+        let (tx, rx) = watch::channel(None);
+        let runtime = self.clone();
+        let name = name.to_string();
+        tokio::spawn(async move {
+            let exit = match runtime.run(&["wait", &name]).await {
+                Ok(code) => ExitInfo {
+                    code: code.parse().ok(),
+                    signal: None,
+                    at: Utc::now(),
+                },
+                Err(_) => unknown_exit(),
+            };
+            let _ = tx.send(Some(exit));
+        });
+        rx
+    }
+
+    fn instance(
+        &self,
+        name: String,
+        pid: Option<u32>,
+        follower: (Child, Vec<JoinHandle<()>>),
+    ) -> Box<dyn Instance> {
+        let exit = self.watch(&name);
+        Box::new(Container {
+            runtime: self.clone(),
+            name,
+            pid,
+            exit,
+            follower: Some(follower.0),
+            readers: follower.1,
+        })
+    }
+}
+
+#[async_trait]
+impl Runtime for ContainerRuntime {
+    async fn spawn(
+        &self,
+        request: &SpawnRequest,
+        logs: Arc<LogBuffer>,
+    ) -> Result<Box<dyn Instance>> {
+        let name = unit_name(request.id);
+        let root = request.paths.root.to_string_lossy().into_owned();
+        let install_dir = request
+            .program
+            .parent()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .unwrap_or_else(|| root.clone());
+
+        // A container left over from the previous run would hold the name.
+        let _ = self.run(&["rm", "--force", &name]).await;
+
+        let mut command = self.command();
+        command
+            .args(["run", "--detach", "--name", &name, "--network", "host"])
+            .args(["--volume", &format!("{}:{}:ro", install_dir, install_dir)])
+            .args(["--volume", &format!("{}:{}", root, root)])
+            .args(["--workdir", &root]);
+        for volume in &self.volumes {
+            command.args(["--volume", volume]);
+        }
+        for (key, value) in &request.env {
+            command.args(["--env", &format!("{}={}", key, value)]);
+        }
+        command.args(limit_args(&request.limits));
+        command
+            .arg(&self.image)
+            .arg(&request.program)
+            .args(&request.args);
+
+        let output = command.output().await?;
+        if !output.status.success() {
+            return Err(Error::msg(format!(
+                "{} run failed: {}",
+                self.engine.program(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let pid = self.inspect(&name).await.ok().flatten();
+        let follower = self.follow(&name, logs, None)?;
+        Ok(self.instance(name, pid, follower))
+    }
+
+    async fn adopt(
+        &self,
+        record: &ProcessRecord,
+        _paths: &ServerPaths,
+        logs: Arc<LogBuffer>,
+    ) -> Result<Option<Box<dyn Instance>>> {
+        let name = unit_name(record.server_id);
+        let pid = match self.inspect(&name).await {
+            Ok(Some(pid)) if pid == record.pid => pid,
+            _ => return Ok(None),
+        };
+
+        let since = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        let follower = self.follow(&name, logs, Some(since))?;
+        Ok(Some(self.instance(name, Some(pid), follower)))
+    }
+}
+
+/// A server running in a container.
+#[derive(Debug)]
+struct Container {
+    runtime: ContainerRuntime,
+    name: String,
+    pid: Option<u32>,
+    exit: watch::Receiver<Option<ExitInfo>>,
+    follower: Option<Child>,
+    readers: Vec<JoinHandle<()>>,
+}
+
+#[async_trait]
+impl Instance for Container {
+    fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    async fn wait(&mut self) -> Result<ExitInfo> {
+        wait_for_exit(&mut self.exit).await
+    }
+
+    async fn signal(&mut self, signal: StopSignal) -> Result<()> {
+        if self.exit.borrow().is_some() {
+            return Ok(());
+        }
+        self.runtime
+            .run(&["kill", "--signal", signal_name(signal), &self.name])
+            .await
+            .map(|_| ())
+    }
+
+    async fn kill(&mut self) -> Result<ExitInfo> {
+        if self.exit.borrow().is_none() {
+            // This fails if the container exited in the meantime, which the
+            // wait picks up.
+            let _ = self.runtime.run(&["kill", &self.name]).await;
+        }
+        self.wait().await
+    }
+
+    async fn close_output(&mut self) {
+        // `logs --follow` exits by itself once the container has stopped and
+        // its output has been read.
+        let readers = std::mem::take(&mut self.readers);
+        let _ = tokio::time::timeout(LOG_DRAIN_TIMEOUT, join_readers(readers)).await;
+        if let Some(mut follower) = self.follower.take() {
+            let _ = follower.kill().await;
+        }
+    }
+}
+
+/// The `run` arguments applying `limits` to a container. nice and ionice
+/// can't be applied to containers, which `limits::check` rejects.
+fn limit_args(limits: &ResourceLimits) -> Vec<String> {
+    let mut args = Vec::new();
+    if !limits.cpu_affinity.is_empty() {
+        let cpus: Vec<_> = limits.cpu_affinity.iter().map(u32::to_string).collect();
+        args.extend([String::from("--cpuset-cpus"), cpus.join(",")]);
+    }
+    if let Some(limit) = limits.max_open_files {
+        args.extend([String::from("--ulimit"), format!("nofile={0}:{0}", limit)]);
+    }
+    if let Some(limit) = limits.max_core_size {
+        args.extend([String::from("--ulimit"), format!("core={0}:{0}", limit)]);
+    }
+    if let Some(adj) = limits.oom_score_adj {
+        args.extend([String::from("--oom-score-adj"), adj.to_string()]);
+    }
+    if let Some(cgroup) = &limits.cgroup {
+        if let Some(bytes) = cgroup.memory_max {
+            args.extend([String::from("--memory"), format!("{}b", bytes)]);
+        }
+        if let Some(percent) = cgroup.cpu_max_percent {
+            args.extend([String::from("--cpus"), (percent as f64 / 100.0).to_string()]);
+        }
+    }
+    args
+}
+
+/// Waits for every output reader to finish.
+async fn join_readers(handles: Vec<JoinHandle<()>>) {
+    for handle in handles {
+        let _ = handle.await;
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use tokio::sync::watch;
+use uuid::Uuid;
+
+use super::{wait_for_exit, Instance, Runtime, SpawnRequest};
+use crate::{
+    logs::{LogBuffer, LogStream},
+    manager::{ExitInfo, StopSignal},
+    paths::ServerPaths,
+    store::ProcessRecord,
+};
+
+/// The signal number reported for a killed fake server.
+const SIGKILL: i32 = 9;
+
+#[derive(Debug)]
+struct FakeServer {
+    request: SpawnRequest,
+    logs: Arc<LogBuffer>,
+    exit: watch::Sender<Option<ExitInfo>>,
+    /// Whether stop signals are ignored, so that stops have to escalate.
+    ignore_signals: bool,
+}
+
+impl FakeServer {
+    fn exit(&self, code: Option<i32>, signal: Option<i32>) {
+        self.exit.send_if_modified(|exit| {
+            if exit.is_some() {
+                return false;
+            }
+            *exit = Some(ExitInfo {
+                code,
+                signal,
+                at: Utc::now(),
+            });
+            true
+        });
+    }
+}
+
+/// An in-memory runtime which never runs anything, so that the process
+/// manager and API can be exercised without a Reforger install.
+///
+/// A fake server runs until it is told to exit with `exit`, or until it is
+/// stopped, in which case it exits cleanly with code 0 unless it has been
+/// told to ignore signals. Fake servers have no pid, so they are never
+/// recorded in the process store.
+#[derive(Debug, Default)]
+pub struct FakeRuntime {
+    servers: Arc<Mutex<HashMap<Uuid, FakeServer>>>,
+    /// Errors the next spawn of each server fails with.
+    spawn_failures: Mutex<HashMap<Uuid, String>>,
+}
+
+impl FakeRuntime {
+    /// The request the server was last spawned with, if it has been.
+    pub fn spawned(&self, id: Uuid) -> Option<SpawnRequest> {
+        let servers = self.servers.lock().unwrap();
+        servers.get(&id).map(|server| server.request.clone())
+    }
+
+    /// Whether the server is running.
+    pub fn is_running(&self, id: Uuid) -> bool {
+        let servers = self.servers.lock().unwrap();
+        servers
+            .get(&id)
+            .is_some_and(|server| server.exit.borrow().is_none())
+    }
+
+    /// Makes a running server exit with `code`. Returns false if it isn't
+    /// running.
+    pub fn exit(&self, id: Uuid, code: i32) -> bool {
+        let running = self.is_running(id);
+        if let Some(server) = self.servers.lock().unwrap().get(&id) {
+            server.exit(Some(code), None);
+        }
+        running
+    }
+
+    /// Writes a line of console output as a running server. Returns false if
+    /// it isn't running.
+    pub fn emit(&self, id: Uuid, stream: LogStream, line: impl Into<String>) -> bool {
+        if !self.is_running(id) {
+            return false;
+        }
+        if let Some(server) = self.servers.lock().unwrap().get(&id) {
+            server.logs.push(stream, line.into());
+        }
+        true
+    }
+
+    /// Makes the next spawn of a server fail with `error`.
+    pub fn fail_next_spawn(&self, id: Uuid, error: impl Into<String>) {
+        let mut failures = self.spawn_failures.lock().unwrap();
+        failures.insert(id, error.into());
+    }
+
+    /// Sets whether a server ignores stop signals.
+    pub fn ignore_signals(&self, id: Uuid, ignore: bool) {
+        if let Some(server) = self.servers.lock().unwrap().get_mut(&id) {
+            server.ignore_signals = ignore;
+        }
+    }
+}
+
+#[async_trait]
+impl Runtime for FakeRuntime {
+    async fn spawn(
+        &self,
+        request: &SpawnRequest,
+        logs: Arc<LogBuffer>,
+    ) -> Result<Box<dyn Instance>> {
+        if let Some(error) = self.spawn_failures.lock().unwrap().remove(&request.id) {
+            return Err(Error::msg(error));
+        }
+
+        let (exit, exit_rx) = watch::channel(None);
+        let mut servers = self.servers.lock().unwrap();
+        let ignore_signals = servers
+            .get(&request.id)
+            .is_some_and(|server| server.ignore_signals);
+        servers.insert(
+            request.id,
+            FakeServer {
+                request: request.clone(),
+                logs,
+                exit,
+                ignore_signals,
+            },
+        );
+
+        Ok(Box::new(FakeInstance {
+            id: request.id,
+            exit: exit_rx,
+            servers: self.servers.clone(),
+        }))
+    }
+
+    async fn adopt(
+        &self,
+        _record: &ProcessRecord,
+        _paths: &ServerPaths,
+        _logs: Arc<LogBuffer>,
+    ) -> Result<Option<Box<dyn Instance>>> {
+        // Nothing a fake runtime runs survives HARM restarting.
+        Ok(None)
+    }
+}
+
+/// A server run by a FakeRuntime.
+#[derive(Debug)]
+struct FakeInstance {
+    id: Uuid,
+    exit: watch::Receiver<Option<ExitInfo>>,
+    servers: Arc<Mutex<HashMap<Uuid, FakeServer>>>,
+}
+
+#[async_trait]
+impl Instance for FakeInstance {
+    fn pid(&self) -> Option<u32> {
+        None
+    }
+
+    async fn wait(&mut self) -> Result<ExitInfo> {
+        wait_for_exit(&mut self.exit).await
+    }
+
+    async fn signal(&mut self, _signal: StopSignal) -> Result<()> {
+        let servers = self.servers.lock().unwrap();
+        if let Some(server) = servers.get(&self.id).filter(|s| !s.ignore_signals) {
+            server.exit(Some(0), None);
+        }
+        Ok(())
+    }
+
+    async fn kill(&mut self) -> Result<ExitInfo> {
+        if let Some(server) = self.servers.lock().unwrap().get(&self.id) {
+            server.exit(None, Some(SIGKILL));
+        }
+        self.wait().await
+    }
+
+    async fn close_output(&mut self) {}
+}
@@ -0,0 +1,375 @@
+use std::{path::PathBuf, process::Stdio, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use tokio::process::Child;
+#[cfg(target_os = "linux")]
+use {
+    anyhow::Error,
+    harm_schemas::{CgroupLimits, ResourceLimits},
+    std::{ffi::CStr, path::Path},
+    tokio::{fs, process::Command},
+};
+
+use super::{follow_files, Instance, Runtime, SpawnRequest};
+#[cfg(target_os = "linux")]
+use crate::limits;
+use crate::{
+    logs::{LogBuffer, Tailer},
+    manager::{ExitInfo, StopSignal},
+    paths::ServerPaths,
+    procfs,
+    store::ProcessRecord,
+};
+
+/// How often an adopted process is checked for having exited.
+const ADOPTED_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Converts the status of a reaped process into an ExitInfo record.
+pub(crate) fn exit_info(status: std::process::ExitStatus) -> ExitInfo {
+    #[cfg(unix)]
+    let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+    #[cfg(not(unix))]
+    let signal = None;
+
+    ExitInfo {
+        code: status.code(),
+        signal,
+        at: Utc::now(),
+    }
+}
+
+/// An ExitInfo for a process whose exit status is not known, either because it
+/// could not be waited on or because it never started.
+pub(crate) fn unknown_exit() -> ExitInfo {
+    ExitInfo {
+        code: None,
+        signal: None,
+        at: Utc::now(),
+    }
+}
+
+/// Runs servers as child processes of HARM on the local host, using
+/// tokio::process.
+///
+/// Each process is placed in its own process group so it outlives HARM, and
+/// its stdout and stderr are written to files in its data directory, which
+/// are followed into the server's logs.
+#[derive(Clone, Debug, Default)]
+pub struct LocalRuntime {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    cgroup_root: Option<PathBuf>,
+}
+
+impl LocalRuntime {
+    /// Creates a runtime which places servers with cgroup limits in a group
+    /// named `harm-<id>` under `cgroup_root`, a cgroup v2 directory HARM has
+    /// been delegated. Without one, servers with cgroup limits can't be
+    /// started.
+    pub fn new(cgroup_root: Option<PathBuf>) -> Self {
+        Self { cgroup_root }
+    }
+
+    /// Creates (or reuses) the cgroup for a server and writes its limits.
+    #[cfg(target_os = "linux")]
+    async fn prepare_cgroup(&self, id: uuid::Uuid, limits: &CgroupLimits) -> Result<PathBuf> {
+        let root = self.cgroup_root.as_ref().ok_or_else(|| {
+            Error::msg("cgroup limits need a cgroup root; start HARM with --cgroup-root")
+        })?;
+
+        let group = root.join(super::unit_name(id));
+        fs::create_dir_all(&group).await?;
+        // The controllers have to be enabled in the parent for the group to
+        // have their files. This fails if they already are, or if they can't
+        // be, in which case writing the limits below fails instead.
+        let _ = fs::write(root.join("cgroup.subtree_control"), "+memory +cpu").await;
+
+        let memory_max = match limits.memory_max {
+            Some(bytes) => bytes.to_string(),
+            None => String::from("max"),
+        };
+        fs::write(group.join("memory.max"), memory_max).await?;
+        fs::write(
+            group.join("cpu.max"),
+            limits::cpu_max(limits.cpu_max_percent),
+        )
+        .await?;
+
+        Ok(group)
+    }
+}
+
+#[async_trait]
+impl Runtime for LocalRuntime {
+    async fn spawn(
+        &self,
+        request: &SpawnRequest,
+        logs: Arc<LogBuffer>,
+    ) -> Result<Box<dyn Instance>> {
+        let paths = &request.paths;
+
+        let mut command = tokio::process::Command::new(&request.program);
+        command
+            .current_dir(&paths.root)
+            .stdout(Stdio::from(std::fs::File::create(paths.stdout_log())?))
+            .stderr(Stdio::from(std::fs::File::create(paths.stderr_log())?))
+            .args(&request.args)
+            .envs(&request.env);
+        #[cfg(unix)]
+        command.process_group(0);
+        #[cfg(target_os = "linux")]
+        {
+            let cgroup = match &request.limits.cgroup {
+                Some(cgroup) => Some(self.prepare_cgroup(request.id, cgroup).await?),
+                None => None,
+            };
+            apply_limits(&mut command, &request.limits, cgroup.as_deref())?;
+        }
+
+        let child = command.spawn()?;
+        let tailers = follow_files(logs, paths, false).await;
+
+        Ok(Box::new(Process {
+            handle: Handle::Child(child),
+            tailers,
+        }))
+    }
+
+    /// A record is only trusted if its pid is still alive with the same start
+    /// time and command line, so a recycled pid is never mistaken for a
+    /// server. The command line may be prefixed by an interpreter, for
+    /// wrapper scripts.
+    async fn adopt(
+        &self,
+        record: &ProcessRecord,
+        paths: &ServerPaths,
+        logs: Arc<LogBuffer>,
+    ) -> Result<Option<Box<dyn Instance>>> {
+        let Some(start_time) = record.start_time else {
+            return Ok(None);
+        };
+        if !procfs::is_alive(record.pid, start_time)
+            || !procfs::cmdline(record.pid)
+                .is_some_and(|cmdline| cmdline.ends_with(&record.command_line))
+        {
+            return Ok(None);
+        }
+
+        let tailers = follow_files(logs, paths, true).await;
+
+        Ok(Some(Box::new(Process {
+            handle: Handle::Adopted {
+                pid: record.pid,
+                start_time,
+            },
+            tailers,
+        })))
+    }
+}
+
+#[derive(Debug)]
+enum Handle {
+    /// A process spawned by this instance of HARM.
+    Child(Child),
+
+    /// A process spawned by a previous instance of HARM and re-adopted on
+    /// boot. It is not our child, so its exit status can't be collected; it is
+    /// polled through /proc instead.
+    Adopted { pid: u32, start_time: u64 },
+}
+
+/// A local server process, along with the tasks following its output.
+#[derive(Debug)]
+struct Process {
+    handle: Handle,
+    tailers: Vec<Tailer>,
+}
+
+#[async_trait]
+impl Instance for Process {
+    fn pid(&self) -> Option<u32> {
+        match &self.handle {
+            Handle::Child(child) => child.id(),
+            Handle::Adopted { pid, .. } => Some(*pid),
+        }
+    }
+
+    async fn wait(&mut self) -> Result<ExitInfo> {
+        match &mut self.handle {
+            Handle::Child(child) => Ok(exit_info(child.wait().await?)),
+            Handle::Adopted { pid, start_time } => {
+                while procfs::is_alive(*pid, *start_time) {
+                    tokio::time::sleep(ADOPTED_POLL_INTERVAL).await;
+                }
+                Ok(unknown_exit())
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    async fn signal(&mut self, signal: StopSignal) -> Result<()> {
+        let signal = match signal {
+            StopSignal::Term => libc::SIGTERM,
+            StopSignal::Int => libc::SIGINT,
+            StopSignal::Quit => libc::SIGQUIT,
+            StopSignal::Hup => libc::SIGHUP,
+        };
+
+        // If there is no pid the process has already been reaped, and if
+        // sending fails it has already exited; either way, waiting on it
+        // picks that up straight away.
+        if let Some(pid) = self.pid() {
+            let _ = signal_group(pid, signal);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    async fn signal(&mut self, _signal: StopSignal) -> Result<()> {
+        Err(anyhow::Error::msg(
+            "Signals are not supported on this platform",
+        ))
+    }
+
+    async fn kill(&mut self) -> Result<ExitInfo> {
+        match &mut self.handle {
+            Handle::Child(child) => {
+                // Anything a wrapper script started is killed along with it.
+                #[cfg(unix)]
+                if let Some(pid) = child.id() {
+                    let _ = signal_group(pid, libc::SIGKILL);
+                }
+                child.kill().await?;
+                // kill() reaps the process, so this returns immediately.
+                Ok(exit_info(child.wait().await?))
+            }
+            #[cfg(unix)]
+            Handle::Adopted { pid, .. } => {
+                signal_group(*pid, libc::SIGKILL)?;
+                self.wait().await
+            }
+            #[cfg(not(unix))]
+            Handle::Adopted { .. } => self.wait().await,
+        }
+    }
+
+    async fn close_output(&mut self) {
+        for tailer in self.tailers.drain(..) {
+            tailer.stop().await;
+        }
+    }
+}
+
+/// Sends `signal` to every process in the group led by the server process
+/// with the given pid. Servers are spawned as the leader of their own group,
+/// so that anything started by a wrapper script is signalled along with it.
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: libc::c_int) -> std::io::Result<()> {
+    // SAFETY: kill(2) has no memory safety requirements.
+    if unsafe { libc::kill(-(pid as libc::pid_t), signal) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Sets up `command` to apply `limits` to the process between fork and exec,
+/// and to move it into `cgroup` if one is given.
+#[cfg(target_os = "linux")]
+fn apply_limits(
+    command: &mut Command,
+    limits: &ResourceLimits,
+    cgroup: Option<&Path>,
+) -> Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    // Everything the child needs is prepared up front, as it must not
+    // allocate between fork and exec.
+    let procs = match cgroup {
+        Some(cgroup) => Some(CString::new(
+            cgroup.join("cgroup.procs").as_os_str().as_bytes(),
+        )?),
+        None => None,
+    };
+    let affinity = (!limits.cpu_affinity.is_empty()).then(|| {
+        // SAFETY: cpu_set_t is plain data, and all zeroes is an empty set.
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        for cpu in &limits.cpu_affinity {
+            // SAFETY: check() rejects CPUs beyond the host's, which is well
+            // below the size of the set.
+            unsafe { libc::CPU_SET(*cpu as usize, &mut set) };
+        }
+        set
+    });
+    let nice = limits.nice;
+    let ioprio = limits.io_priority.as_ref().map(limits::ioprio);
+    let max_open_files = limits.max_open_files;
+    let max_core_size = limits.max_core_size;
+    let oom_score_adj = limits.oom_score_adj.map(|adj| adj.to_string());
+
+    let rlimit = |limit: u64| libc::rlimit {
+        rlim_cur: limit as libc::rlim_t,
+        rlim_max: limit as libc::rlim_t,
+    };
+
+    // SAFETY: the closure only makes async-signal-safe calls and doesn't
+    // allocate, as required after fork.
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(procs) = &procs {
+                write_file(procs, b"0")?;
+            }
+            if let Some(set) = &affinity {
+                check_os(libc::sched_setaffinity(
+                    0,
+                    std::mem::size_of::<libc::cpu_set_t>(),
+                    set,
+                ))?;
+            }
+            if let Some(nice) = nice {
+                check_os(libc::setpriority(libc::PRIO_PROCESS as _, 0, nice))?;
+            }
+            if let Some(ioprio) = ioprio {
+                const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+                check_os(libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) as _)?;
+            }
+            if let Some(limit) = max_open_files {
+                check_os(libc::setrlimit(libc::RLIMIT_NOFILE, &rlimit(limit)))?;
+            }
+            if let Some(limit) = max_core_size {
+                check_os(libc::setrlimit(libc::RLIMIT_CORE, &rlimit(limit)))?;
+            }
+            if let Some(adj) = &oom_score_adj {
+                write_file(c"/proc/self/oom_score_adj", adj.as_bytes())?;
+            }
+            Ok(())
+        });
+    }
+
+    Ok(())
+}
+
+/// Turns the -1 returned by a failed libc call into the error it set.
+#[cfg(target_os = "linux")]
+fn check_os(result: libc::c_int) -> std::io::Result<()> {
+    match result {
+        -1 => Err(std::io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+/// Writes `contents` to the file at `path` using only async-signal-safe
+/// calls.
+#[cfg(target_os = "linux")]
+fn write_file(path: &CStr, contents: &[u8]) -> std::io::Result<()> {
+    // SAFETY: path is a valid C string and contents a valid buffer.
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        check_os(fd)?;
+        let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+        let result = check_os(written as _);
+        libc::close(fd);
+        result
+    }
+}
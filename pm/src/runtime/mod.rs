@@ -0,0 +1,184 @@
+//! Runtimes are the backends which actually run server processes. The
+//! process manager picks one per server from its RuntimeConfig, and the
+//! supervisor drives whatever Instance it hands back.
+
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use harm_schemas::{ResourceLimits, RuntimeConfig, RuntimeKind};
+use tokio::{fs, sync::watch};
+use uuid::Uuid;
+
+use crate::{
+    logs::{self, LogBuffer, LogStream, Tailer},
+    manager::{ExitInfo, StopSignal},
+    paths::ServerPaths,
+    store::ProcessRecord,
+};
+
+mod container;
+mod fake;
+mod local;
+mod systemd;
+
+pub use container::{ContainerEngine, ContainerRuntime};
+pub use fake::FakeRuntime;
+pub use local::LocalRuntime;
+pub use systemd::SystemdRuntime;
+
+pub(crate) use local::unknown_exit;
+
+/// Everything a runtime needs to launch a server.
+#[derive(Clone, Debug)]
+pub struct SpawnRequest {
+    pub id: Uuid,
+    /// The Reforger executable.
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+    /// The server's data directory, which is also its working directory.
+    pub paths: ServerPaths,
+    /// Checked with `limits::check` before the request is made.
+    pub limits: ResourceLimits,
+}
+
+impl SpawnRequest {
+    /// The full command line, as recorded in the process store.
+    pub fn command_line(&self) -> Vec<String> {
+        let mut command_line = vec![self.program.to_string_lossy().into_owned()];
+        command_line.extend(self.args.iter().cloned());
+        command_line
+    }
+}
+
+/// A backend able to run server processes.
+#[async_trait]
+pub trait Runtime: Send + Sync {
+    /// Launches a server, feeding its console output into `logs`.
+    async fn spawn(
+        &self,
+        request: &SpawnRequest,
+        logs: Arc<LogBuffer>,
+    ) -> Result<Box<dyn Instance>>;
+
+    /// Finds a server launched by a previous instance of HARM again, as
+    /// described by `record`. Returns `None` if it is no longer running, or
+    /// if the record can't be trusted to still describe the same process.
+    /// Only output written from now on is fed into `logs`.
+    async fn adopt(
+        &self,
+        record: &ProcessRecord,
+        paths: &ServerPaths,
+        logs: Arc<LogBuffer>,
+    ) -> Result<Option<Box<dyn Instance>>>;
+}
+
+/// A server launched by a Runtime.
+#[async_trait]
+pub trait Instance: Send + Sync + Debug {
+    /// The host pid of the server process, if the runtime has one.
+    fn pid(&self) -> Option<u32>;
+
+    /// Waits for the server to exit. This is cancel safe.
+    async fn wait(&mut self) -> Result<ExitInfo>;
+
+    /// Sends `signal` to the server. Succeeds without doing anything if the
+    /// server has already exited.
+    async fn signal(&mut self, signal: StopSignal) -> Result<()>;
+
+    /// Kills the server outright and waits for it to exit.
+    async fn kill(&mut self) -> Result<ExitInfo>;
+
+    /// Finishes following the server's output. Call once it has exited.
+    async fn close_output(&mut self);
+}
+
+/// Creates the runtime described by `config`. `fake` is the process
+/// manager's shared fake runtime, so that tests can drive the servers it
+/// runs, and `cgroup_root` is where the local runtime creates cgroups.
+pub(crate) fn from_config(
+    config: &RuntimeConfig,
+    fake: &Arc<FakeRuntime>,
+    cgroup_root: Option<&Path>,
+) -> Result<Arc<dyn Runtime>> {
+    check(config).map_err(Error::msg)?;
+
+    Ok(match config.kind {
+        RuntimeKind::Local => Arc::new(LocalRuntime::new(cgroup_root.map(Path::to_path_buf))),
+        RuntimeKind::Podman => Arc::new(ContainerRuntime::new(ContainerEngine::Podman, config)),
+        RuntimeKind::Docker => Arc::new(ContainerRuntime::new(ContainerEngine::Docker, config)),
+        RuntimeKind::Systemd => Arc::new(SystemdRuntime::new(config.user_unit)),
+        RuntimeKind::Fake => fake.clone(),
+    })
+}
+
+/// Checks a runtime config for settings its runtime can't work with,
+/// returning a description of the first problem found.
+pub fn check(config: &RuntimeConfig) -> Result<(), String> {
+    let container = matches!(config.kind, RuntimeKind::Podman | RuntimeKind::Docker);
+    if container && config.image.as_deref().is_none_or(str::is_empty) {
+        return Err(String::from(
+            "an image is required to run a server in a container",
+        ));
+    }
+    if !container && (config.image.is_some() || !config.volumes.is_empty()) {
+        return Err(String::from(
+            "an image and volumes can only be set for container runtimes",
+        ));
+    }
+    if config.user_unit && config.kind != RuntimeKind::Systemd {
+        return Err(String::from(
+            "userUnit can only be set for the systemd runtime",
+        ));
+    }
+
+    Ok(())
+}
+
+/// The name HARM gives the container or unit running a server.
+fn unit_name(id: Uuid) -> String {
+    format!("harm-{}", id)
+}
+
+/// The name of a StopSignal as understood by `kill`-like commands.
+fn signal_name(signal: StopSignal) -> &'static str {
+    match signal {
+        StopSignal::Term => "SIGTERM",
+        StopSignal::Int => "SIGINT",
+        StopSignal::Quit => "SIGQUIT",
+        StopSignal::Hup => "SIGHUP",
+    }
+}
+
+/// Follows a server's output files into `logs`. If `from_end` is set, only
+/// output written from now on is followed.
+async fn follow_files(logs: Arc<LogBuffer>, paths: &ServerPaths, from_end: bool) -> Vec<Tailer> {
+    let mut tailers = Vec::new();
+    for (stream, path) in [
+        (LogStream::Stdout, paths.stdout_log()),
+        (LogStream::Stderr, paths.stderr_log()),
+    ] {
+        let offset = match from_end {
+            true => fs::metadata(&path).await.map_or(0, |m| m.len()),
+            false => 0,
+        };
+        tailers.push(logs::spawn_tailer(logs.clone(), stream, path, offset));
+    }
+    tailers
+}
+
+/// Waits until `exit` holds an exit, for runtimes which learn about exits
+/// from a background task. This is cancel safe.
+async fn wait_for_exit(exit: &mut watch::Receiver<Option<ExitInfo>>) -> Result<ExitInfo> {
+    let exit = exit
+        .wait_for(Option::is_some)
+        .await
+        .map_err(|_| Error::msg("The runtime stopped watching the server"))?;
+    Ok(exit.clone().unwrap_or_else(unknown_exit))
+}
@@ -0,0 +1,322 @@
+use std::{collections::HashMap, process::Stdio, sync::Arc, time::Duration};
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use harm_schemas::{IoClass, ResourceLimits};
+use tokio::{process::Command, sync::watch};
+
+use super::{
+    follow_files, signal_name, unit_name, unknown_exit, wait_for_exit, Instance, Runtime,
+    SpawnRequest,
+};
+use crate::{
+    logs::{LogBuffer, Tailer},
+    manager::{ExitInfo, StopSignal},
+    paths::ServerPaths,
+    store::ProcessRecord,
+};
+
+/// How often a unit is checked for having exited.
+const UNIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runs servers as transient systemd service units, through `systemd-run`.
+///
+/// Units are named `harm-<id>.service`, and write their stdout and stderr to
+/// the same files in the server's data directory as the local runtime, which
+/// are followed into the server's logs.
+#[derive(Clone, Debug)]
+pub struct SystemdRuntime {
+    user: bool,
+}
+
+impl SystemdRuntime {
+    /// Creates a runtime using the system service manager, or the calling
+    /// user's if `user` is set.
+    pub fn new(user: bool) -> Self {
+        Self { user }
+    }
+
+    fn command(&self, program: &str) -> Command {
+        let mut command = Command::new(program);
+        command.stdin(Stdio::null());
+        if self.user {
+            command.arg("--user");
+        }
+        command
+    }
+
+    /// Runs `systemctl` to completion, returning its stdout.
+    async fn systemctl(&self, args: &[&str]) -> Result<String> {
+        let output = self.command("systemctl").args(args).output().await?;
+        if !output.status.success() {
+            return Err(Error::msg(format!(
+                "systemctl {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Reads the state of a unit's main process.
+    async fn show(&self, unit: &str) -> Result<UnitState> {
+        let output = self
+            .systemctl(&[
+                "show",
+                unit,
+                "--property=ActiveState,MainPID,ExecMainCode,ExecMainStatus",
+            ])
+            .await?;
+        let properties: HashMap<&str, &str> = output
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .collect();
+
+        let active_state = properties.get("ActiveState").copied().unwrap_or_default();
+        let active = matches!(
+            active_state,
+            "active" | "activating" | "deactivating" | "reloading"
+        );
+        let status = properties
+            .get("ExecMainStatus")
+            .and_then(|status| status.parse().ok());
+        // ExecMainCode is a CLD_* code from waitid(2).
+        let (code, signal) = match properties.get("ExecMainCode").copied() {
+            Some("1") => (status, None),
+            Some("2" | "3") => (None, status),
+            // Transient units are unloaded as soon as they exit cleanly, and
+            // an unloaded unit reports no main process at all.
+            _ if active_state == "inactive" => (Some(0), None),
+            _ => (None, None),
+        };
+
+        Ok(UnitState {
+            pid: properties
+                .get("MainPID")
+                .and_then(|pid| pid.parse().ok())
+                .filter(|pid| *pid != 0),
+            exit: (!active).then(|| ExitInfo {
+                code,
+                signal,
+                at: Utc::now(),
+            }),
+        })
+    }
+
+    /// Polls the unit until it exits, in the background.
+    fn watch(&self, unit: &str) -> watch::Receiver<Option<ExitInfo>> {
+        let (tx, rx) = watch::channel(None);
+        let runtime = self.clone();
+        let unit = unit.to_string();
+        tokio::spawn(async move {
+            let exit = loop {
+                match runtime.show(&unit).await {
+                    Ok(UnitState {
+                        exit: Some(exit), ..
+                    }) => break exit,
+                    Ok(_) => {}
+                    Err(_) => break unknown_exit(),
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(UNIT_POLL_INTERVAL) => {}
+                    _ = tx.closed() => return,
+                }
+            };
+            let _ = tx.send(Some(exit));
+        });
+        rx
+    }
+}
+
+struct UnitState {
+    pid: Option<u32>,
+    /// How the main process exited, if the unit is no longer active.
+    exit: Option<ExitInfo>,
+}
+
+#[async_trait]
+impl Runtime for SystemdRuntime {
+    async fn spawn(
+        &self,
+        request: &SpawnRequest,
+        logs: Arc<LogBuffer>,
+    ) -> Result<Box<dyn Instance>> {
+        let unit = format!("{}.service", unit_name(request.id));
+        let paths = &request.paths;
+
+        // A failed unit left over from the previous run would hold the name.
+        let _ = self.systemctl(&["stop", &unit]).await;
+        let _ = self.systemctl(&["reset-failed", &unit]).await;
+
+        // Created here rather than by systemd so they are truncated, and so
+        // they are owned by HARM rather than root.
+        std::fs::File::create(paths.stdout_log())?;
+        std::fs::File::create(paths.stderr_log())?;
+
+        let mut command = self.command("systemd-run");
+        command
+            .arg(format!("--unit={}", unit))
+            .arg(format!(
+                "--property=WorkingDirectory={}",
+                paths.root.display()
+            ))
+            .arg(format!(
+                "--property=StandardOutput=append:{}",
+                paths.stdout_log().display()
+            ))
+            .arg(format!(
+                "--property=StandardError=append:{}",
+                paths.stderr_log().display()
+            ));
+        for (key, value) in &request.env {
+            command.arg(format!("--setenv={}={}", key, value));
+        }
+        for property in limit_properties(&request.limits) {
+            command.arg(format!("--property={}", property));
+        }
+        command.arg("--").arg(&request.program).args(&request.args);
+
+        let output = command.output().await?;
+        if !output.status.success() {
+            return Err(Error::msg(format!(
+                "systemd-run failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let pid = self.show(&unit).await.ok().and_then(|state| state.pid);
+        let tailers = follow_files(logs, paths, false).await;
+        let exit = self.watch(&unit);
+
+        Ok(Box::new(Unit {
+            runtime: self.clone(),
+            unit,
+            pid,
+            exit,
+            tailers,
+        }))
+    }
+
+    async fn adopt(
+        &self,
+        record: &ProcessRecord,
+        paths: &ServerPaths,
+        logs: Arc<LogBuffer>,
+    ) -> Result<Option<Box<dyn Instance>>> {
+        let unit = format!("{}.service", unit_name(record.server_id));
+        let pid = match self.show(&unit).await {
+            Ok(UnitState {
+                pid: Some(pid),
+                exit: None,
+            }) if pid == record.pid => pid,
+            _ => return Ok(None),
+        };
+
+        let tailers = follow_files(logs, paths, true).await;
+        let exit = self.watch(&unit);
+
+        Ok(Some(Box::new(Unit {
+            runtime: self.clone(),
+            unit,
+            pid: Some(pid),
+            exit,
+            tailers,
+        })))
+    }
+}
+
+/// The unit properties applying `limits`, as `Name=value`.
+fn limit_properties(limits: &ResourceLimits) -> Vec<String> {
+    let mut properties = Vec::new();
+    if !limits.cpu_affinity.is_empty() {
+        let cpus: Vec<_> = limits.cpu_affinity.iter().map(u32::to_string).collect();
+        properties.push(format!("CPUAffinity={}", cpus.join(" ")));
+    }
+    if let Some(nice) = limits.nice {
+        properties.push(format!("Nice={}", nice));
+    }
+    if let Some(priority) = &limits.io_priority {
+        let class = match priority.class {
+            IoClass::Realtime => "realtime",
+            IoClass::BestEffort => "best-effort",
+            IoClass::Idle => "idle",
+        };
+        properties.push(format!("IOSchedulingClass={}", class));
+        if let Some(level) = priority.level {
+            properties.push(format!("IOSchedulingPriority={}", level));
+        }
+    }
+    if let Some(limit) = limits.max_open_files {
+        properties.push(format!("LimitNOFILE={}", limit));
+    }
+    if let Some(limit) = limits.max_core_size {
+        properties.push(format!("LimitCORE={}", limit));
+    }
+    if let Some(adj) = limits.oom_score_adj {
+        properties.push(format!("OOMScoreAdjust={}", adj));
+    }
+    if let Some(cgroup) = &limits.cgroup {
+        if let Some(bytes) = cgroup.memory_max {
+            properties.push(format!("MemoryMax={}", bytes));
+        }
+        if let Some(percent) = cgroup.cpu_max_percent {
+            properties.push(format!("CPUQuota={}%", percent));
+        }
+    }
+    properties
+}
+
+/// A server running as a transient systemd unit.
+#[derive(Debug)]
+struct Unit {
+    runtime: SystemdRuntime,
+    unit: String,
+    pid: Option<u32>,
+    exit: watch::Receiver<Option<ExitInfo>>,
+    tailers: Vec<Tailer>,
+}
+
+#[async_trait]
+impl Instance for Unit {
+    fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    async fn wait(&mut self) -> Result<ExitInfo> {
+        wait_for_exit(&mut self.exit).await
+    }
+
+    async fn signal(&mut self, signal: StopSignal) -> Result<()> {
+        if self.exit.borrow().is_some() {
+            return Ok(());
+        }
+        self.runtime
+            .systemctl(&[
+                "kill",
+                "--kill-whom=main",
+                &format!("--signal={}", signal_name(signal)),
+                &self.unit,
+            ])
+            .await
+            .map(|_| ())
+    }
+
+    async fn kill(&mut self) -> Result<ExitInfo> {
+        if self.exit.borrow().is_none() {
+            // This fails if the unit exited in the meantime, which the wait
+            // picks up.
+            let _ = self
+                .runtime
+                .systemctl(&["kill", "--signal=SIGKILL", &self.unit])
+                .await;
+        }
+        self.wait().await
+    }
+
+    async fn close_output(&mut self) {
+        for tailer in self.tailers.drain(..) {
+            tailer.stop().await;
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+/// Everything needed to find a running server process again after HARM
+/// restarts.
+#[derive(Clone, Debug)]
+pub struct ProcessRecord {
+    pub server_id: Uuid,
+    pub pid: u32,
+    /// When the process started, in clock ticks since boot as reported by
+    /// /proc. Used to tell the process apart from a later one reusing its pid.
+    pub start_time: Option<u64>,
+    pub started_at: DateTime<Utc>,
+    pub command_line: Vec<String>,
+}
+
+/// ProcessStore persists the processes a ProcessManager is running, so they
+/// can be re-adopted by the next instance of HARM.
+#[async_trait]
+pub trait ProcessStore: Send + Sync {
+    /// Saves a record, replacing any existing record for the same server.
+    async fn save(&self, record: ProcessRecord) -> Result<()>;
+
+    /// Removes the record for a server, if there is one.
+    async fn remove(&self, server_id: Uuid) -> Result<()>;
+
+    /// Loads every saved record.
+    async fn load(&self) -> Result<Vec<ProcessRecord>>;
+}
@@ -0,0 +1,466 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use harm_schemas::{RestartMode, RestartPolicy};
+use slog::{info, warn};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+use uuid::Uuid;
+
+use crate::{
+    manager::{
+        ExitInfo, ProcessManager, ServerSpec, ServerState, StopMethod, StopOptions, StopOutcome,
+    },
+    readiness,
+    runtime::{unknown_exit, Instance},
+};
+
+/// Commands the process manager can send to a server's supervisor task.
+#[derive(Debug)]
+pub(crate) enum Control {
+    /// Stop the process and report back once it has been reaped.
+    Stop(StopOptions, oneshot::Sender<Result<StopOutcome>>),
+}
+
+/// Whether `policy` calls for a restart after a process exited with `exit`.
+fn should_restart(policy: &RestartPolicy, exit: &ExitInfo) -> bool {
+    match policy.mode {
+        RestartMode::Never => false,
+        RestartMode::OnFailure => !exit.success(),
+        RestartMode::Always => true,
+    }
+}
+
+/// Checks that a restart policy can be followed, e.g. that restarts are
+/// spaced out rather than run in a tight loop.
+pub fn check(policy: &RestartPolicy) -> Result<(), String> {
+    if policy.mode != RestartMode::Never && policy.backoff_secs == 0 {
+        return Err(String::from("backoffSecs must be above 0"));
+    }
+    if policy.max_backoff_secs < policy.backoff_secs {
+        return Err(String::from("maxBackoffSecs must be at least backoffSecs"));
+    }
+    if policy.reset_window_secs == 0 {
+        return Err(String::from("resetWindowSecs must be above 0"));
+    }
+    Ok(())
+}
+
+/// The delay before the `attempt`th consecutive restart (starting at 1).
+fn backoff(policy: &RestartPolicy, attempt: u32) -> Duration {
+    let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+    let secs = policy
+        .backoff_secs
+        .saturating_mul(factor)
+        .min(policy.max_backoff_secs);
+    Duration::from_secs(secs)
+}
+
+/// Stops `process` as described by `options`: unless forced, the process is
+/// sent `options.signal` and given `options.grace_period` to exit before it is
+/// killed outright. If the runtime can't deliver the signal the process is
+/// killed straight away.
+async fn stop_process(
+    process: &mut Box<dyn Instance>,
+    options: &StopOptions,
+) -> Result<StopOutcome> {
+    let signalled = !options.force && process.signal(options.signal).await.is_ok();
+    if signalled {
+        if let Ok(exit) = tokio::time::timeout(options.grace_period, process.wait()).await {
+            return Ok(StopOutcome {
+                method: StopMethod::Graceful,
+                exit: Some(exit?),
+            });
+        }
+    }
+
+    let exit = process.kill().await?;
+    let method = if signalled {
+        StopMethod::Escalated
+    } else {
+        StopMethod::Forced
+    };
+
+    Ok(StopOutcome {
+        method,
+        exit: Some(exit),
+    })
+}
+
+enum Outcome {
+    /// The process exited without being asked to.
+    Exited(ExitInfo),
+    /// The process was stopped through the control channel.
+    Stopped,
+}
+
+/// Waits for `process` to exit, or for a stop to be requested through
+/// `control`, whichever happens first.
+///
+/// Meanwhile a starting server is probed as its spec's readiness config
+/// describes, and moved to ready once every probe passes. If that takes
+/// longer than the startup timeout, the process is stopped and the server
+/// marked as failed.
+async fn wait(
+    pm: &ProcessManager,
+    id: Uuid,
+    process: &mut Box<dyn Instance>,
+    spec: &ServerSpec,
+    control: &mut mpsc::Receiver<Control>,
+) -> Outcome {
+    let status = pm.status(id).await;
+    let mut ready = status.state == ServerState::Ready;
+    let since = status.started_at.unwrap_or_else(Utc::now);
+    let logs = pm.log_buffer(id).await;
+    let startup = async {
+        let Ok(logs) = &logs else {
+            return std::future::pending().await;
+        };
+        tokio::time::timeout(
+            Duration::from_secs(spec.readiness.startup_timeout_secs),
+            readiness::wait(&spec.readiness, &spec.config, logs, since),
+        )
+        .await
+    };
+    tokio::pin!(startup);
+
+    let outcome = loop {
+        tokio::select! {
+            exit = process.wait() => break match exit {
+                Ok(exit) => Outcome::Exited(exit),
+                Err(e) => {
+                    warn!(pm.logger, "Failed to wait on AR process for server {}: {}", id, e);
+                    Outcome::Exited(unknown_exit())
+                }
+            },
+            Some(Control::Stop(options, reply)) = control.recv() => {
+                pm.update(id, |server| server.state = ServerState::Stopping).await;
+                let result = stop_process(process, &options).await;
+
+                let exit = match &result {
+                    Ok(StopOutcome { exit: Some(exit), .. }) => exit.clone(),
+                    _ => unknown_exit(),
+                };
+                pm.finish(id, ServerState::Stopped, exit).await;
+                let _ = reply.send(result);
+                break Outcome::Stopped;
+            }
+            result = &mut startup, if !ready => {
+                ready = true;
+                if result.is_ok() {
+                    info!(pm.logger, "Server {} is ready", id);
+                    pm.update(id, |server| {
+                        if server.state == ServerState::Starting {
+                            server.state = ServerState::Ready;
+                        }
+                    })
+                    .await;
+                    continue;
+                }
+
+                warn!(
+                    pm.logger,
+                    "Server {} did not become ready within {}s, stopping it",
+                    id,
+                    spec.readiness.startup_timeout_secs
+                );
+                pm.update(id, |server| server.state = ServerState::Stopping).await;
+                let exit = match stop_process(process, &StopOptions::default()).await {
+                    Ok(StopOutcome { exit: Some(exit), .. }) => exit,
+                    _ => unknown_exit(),
+                };
+                pm.finish(id, ServerState::Failed, exit).await;
+                break Outcome::Stopped;
+            }
+        }
+    };
+
+    process.close_output().await;
+    outcome
+}
+
+/// Owns a spawned server process until it exits, either on its own or because
+/// a stop was requested through `control`, then records how it exited in the
+/// process manager.
+///
+/// If the process exits without being asked to, the spec's restart policy
+/// decides whether it is brought back up. Consecutive restarts are delayed
+/// with an exponential backoff, and once `max_retries` is exhausted the server
+/// is quarantined until it is started again by hand.
+pub(crate) async fn supervise(
+    pm: ProcessManager,
+    id: Uuid,
+    process: Box<dyn Instance>,
+    spec: ServerSpec,
+    mut control: mpsc::Receiver<Control>,
+) {
+    let policy = spec.restart_policy.clone();
+    let mut process = Some(process);
+    let mut restarts = 0;
+
+    loop {
+        let started = Instant::now();
+        let exit = match process.take() {
+            Some(mut process) => match wait(&pm, id, &mut process, &spec, &mut control).await {
+                Outcome::Exited(exit) => exit,
+                Outcome::Stopped => return,
+            },
+            // The last restart attempt failed to spawn a process at all.
+            None => unknown_exit(),
+        };
+
+        let state = if exit.success() {
+            ServerState::Exited
+        } else {
+            ServerState::Crashed
+        };
+
+        info!(
+            pm.logger,
+            "AR process for server {} is now {:?} (code: {:?}, signal: {:?})",
+            id,
+            state,
+            exit.code,
+            exit.signal
+        );
+
+        if !should_restart(&policy, &exit) {
+            pm.finish(id, state, exit).await;
+            return;
+        }
+
+        if started.elapsed() >= Duration::from_secs(policy.reset_window_secs) {
+            restarts = 0;
+        }
+
+        if restarts >= policy.max_retries {
+            warn!(
+                pm.logger,
+                "Server {} failed {} consecutive restarts, quarantining it", id, restarts
+            );
+            pm.finish(id, ServerState::Quarantined, exit).await;
+            return;
+        }
+
+        restarts += 1;
+        let delay = backoff(&policy, restarts);
+        info!(
+            pm.logger,
+            "Restarting server {} in {}s (attempt {}/{})",
+            id,
+            delay.as_secs(),
+            restarts,
+            policy.max_retries
+        );
+
+        let last_exit = exit.clone();
+        pm.update(id, |server| {
+            server.state = ServerState::Restarting;
+            server.pid = None;
+            server.last_exit = Some(exit);
+            server.restarts = restarts;
+        })
+        .await;
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            Some(Control::Stop(_, reply)) = control.recv() => {
+                pm.finish(id, ServerState::Stopped, last_exit).await;
+                let _ = reply.send(Ok(StopOutcome {
+                    method: StopMethod::Cancelled,
+                    exit: None,
+                }));
+                return;
+            }
+        }
+
+        let (logs, console) = match (pm.log_buffer(id).await, pm.console(id).await) {
+            (Ok(logs), Ok(console)) => (logs, console),
+            _ => return,
+        };
+
+        match pm._start_server(id, &spec, logs, &console).await {
+            Ok(spawned) => {
+                let pid = spawned.pid();
+                pm.update(id, |server| {
+                    server.state = ServerState::Starting;
+                    server.pid = pid;
+                    server.started_at = Some(Utc::now());
+                })
+                .await;
+                process = Some(spawned);
+            }
+            Err(e) => {
+                warn!(
+                    pm.logger,
+                    "Could not respawn AR process for server {}: {}", id, e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fake_spec, TestManager};
+
+    fn policy(mode: RestartMode, max_retries: u32, backoff_secs: u64) -> RestartPolicy {
+        RestartPolicy {
+            mode,
+            max_retries,
+            backoff_secs,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let policy = RestartPolicy {
+            backoff_secs: 5,
+            max_backoff_secs: 30,
+            ..Default::default()
+        };
+        let delays: Vec<_> = (1..=5).map(|i| backoff(&policy, i).as_secs()).collect();
+        assert_eq!(delays, [5, 10, 20, 30, 30]);
+        assert_eq!(backoff(&policy, u32::MAX).as_secs(), 30);
+    }
+
+    #[test]
+    fn checks_policies() {
+        assert_eq!(check(&RestartPolicy::default()), Ok(()));
+        assert_eq!(
+            check(&policy(RestartMode::Always, 5, 0)),
+            Err(String::from("backoffSecs must be above 0"))
+        );
+        assert_eq!(check(&policy(RestartMode::Never, 5, 0)), Ok(()));
+        assert_eq!(
+            check(&RestartPolicy {
+                backoff_secs: 60,
+                max_backoff_secs: 30,
+                ..Default::default()
+            }),
+            Err(String::from("maxBackoffSecs must be at least backoffSecs"))
+        );
+        assert_eq!(
+            check(&RestartPolicy {
+                reset_window_secs: 0,
+                ..Default::default()
+            }),
+            Err(String::from("resetWindowSecs must be above 0"))
+        );
+    }
+
+    #[test]
+    fn restart_modes() {
+        let exit = |code| ExitInfo {
+            code: Some(code),
+            signal: None,
+            at: Utc::now(),
+        };
+        let never = policy(RestartMode::Never, 5, 0);
+        let on_failure = policy(RestartMode::OnFailure, 5, 0);
+        let always = policy(RestartMode::Always, 5, 0);
+
+        assert!(!should_restart(&never, &exit(1)));
+        assert!(!should_restart(&on_failure, &exit(0)));
+        assert!(should_restart(&on_failure, &exit(1)));
+        assert!(should_restart(&always, &exit(0)));
+    }
+
+    #[tokio::test]
+    async fn restarts_a_crashed_server() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        t.pm.start_server(id, fake_spec(policy(RestartMode::OnFailure, 3, 0)))
+            .await
+            .unwrap();
+        t.wait_for(id, ServerState::Ready).await;
+
+        assert!(t.pm.fake_runtime().exit(id, 1));
+        let status = t
+            .wait_until(id, |s| s.restarts == 1 && s.state == ServerState::Ready)
+            .await;
+        assert_eq!(status.last_exit.unwrap().code, Some(1));
+        assert!(t.pm.fake_runtime().is_running(id));
+    }
+
+    #[tokio::test]
+    async fn leaves_a_clean_exit_alone_on_failure() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        t.pm.start_server(id, fake_spec(policy(RestartMode::OnFailure, 3, 0)))
+            .await
+            .unwrap();
+        t.wait_for(id, ServerState::Ready).await;
+
+        t.pm.fake_runtime().exit(id, 0);
+        let status = t.wait_for(id, ServerState::Exited).await;
+        assert_eq!(status.restarts, 0);
+        assert!(!t.pm.fake_runtime().is_running(id));
+    }
+
+    #[tokio::test]
+    async fn never_restarts_with_the_never_mode() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        t.pm.start_server(id, fake_spec(policy(RestartMode::Never, 3, 0)))
+            .await
+            .unwrap();
+        t.wait_for(id, ServerState::Ready).await;
+
+        t.pm.fake_runtime().exit(id, 1);
+        let status = t.wait_for(id, ServerState::Crashed).await;
+        assert_eq!(status.restarts, 0);
+    }
+
+    #[tokio::test]
+    async fn quarantines_once_retries_run_out() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        let spec = fake_spec(policy(RestartMode::Always, 2, 0));
+        t.pm.start_server(id, spec.clone()).await.unwrap();
+        t.wait_for(id, ServerState::Ready).await;
+
+        for attempt in 1..=2 {
+            t.pm.fake_runtime().exit(id, 1);
+            t.wait_until(id, |s| {
+                s.restarts == attempt && s.state == ServerState::Ready
+            })
+            .await;
+        }
+        t.pm.fake_runtime().exit(id, 1);
+        let status = t.wait_for(id, ServerState::Quarantined).await;
+        assert_eq!(status.restarts, 2);
+        assert!(!t.pm.fake_runtime().is_running(id));
+
+        // Starting it by hand resets its restart budget.
+        t.pm.start_server(id, spec).await.unwrap();
+        let status = t.wait_for(id, ServerState::Ready).await;
+        assert_eq!(status.restarts, 0);
+    }
+
+    #[tokio::test]
+    async fn stopping_cancels_a_pending_restart() {
+        let t = TestManager::new();
+        let id = Uuid::new_v4();
+        t.pm.start_server(id, fake_spec(policy(RestartMode::Always, 3, 60)))
+            .await
+            .unwrap();
+        t.wait_for(id, ServerState::Ready).await;
+
+        t.pm.fake_runtime().exit(id, 1);
+        let status = t.wait_for(id, ServerState::Restarting).await;
+        assert_eq!(status.restarts, 1);
+
+        let outcome = t.pm.stop_server(id, StopOptions::default()).await.unwrap();
+        assert_eq!(outcome.method, StopMethod::Cancelled);
+        assert!(outcome.exit.is_none());
+        let status = t.wait_for(id, ServerState::Stopped).await;
+        assert_eq!(status.last_exit.unwrap().code, Some(1));
+        assert!(!t.pm.fake_runtime().is_running(id));
+    }
+}
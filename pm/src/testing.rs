@@ -0,0 +1,97 @@
+//! Helpers for driving a process manager with the fake runtime in tests.
+
+use std::{net::UdpSocket, path::PathBuf, time::Duration};
+
+use harm_schemas::{ReadinessConfig, RestartPolicy, RuntimeConfig, RuntimeKind};
+use slog::{o, Discard, Logger};
+use uuid::Uuid;
+
+use crate::{
+    manager::{ProcessManager, ServerSpec, ServerState, ServerStatus},
+    ports::ServerPorts,
+};
+
+/// How long a test waits for a server to reach a state before failing.
+const STATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A process manager with its own data directory, which is removed once it
+/// is dropped.
+pub(crate) struct TestManager {
+    pub pm: ProcessManager,
+    data_dir: PathBuf,
+}
+
+impl TestManager {
+    pub fn new() -> Self {
+        let data_dir = std::env::temp_dir().join(format!("harm-test-{}", Uuid::new_v4()));
+        let pm = ProcessManager::new(
+            String::from("/nonexistent/ArmaReforgerServer"),
+            data_dir.clone(),
+            Logger::root(Discard, o!()),
+        );
+        Self { pm, data_dir }
+    }
+
+    /// Waits for a server to reach `state`, panicking if it doesn't.
+    pub async fn wait_for(&self, id: Uuid, state: ServerState) -> ServerStatus {
+        self.wait_until(id, |status| status.state == state).await
+    }
+
+    /// Waits for a server's status to satisfy `f`, panicking if it doesn't.
+    pub async fn wait_until(&self, id: Uuid, f: impl Fn(&ServerStatus) -> bool) -> ServerStatus {
+        let wait = async {
+            loop {
+                let status = self.pm.status(id).await;
+                if f(&status) {
+                    return status;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        match tokio::time::timeout(STATE_TIMEOUT, wait).await {
+            Ok(status) => status,
+            Err(_) => panic!("server {} timed out: {:?}", id, self.pm.status(id).await),
+        }
+    }
+}
+
+impl Drop for TestManager {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
+
+/// A spec which runs with the fake runtime on free ports, and which is ready
+/// as soon as it is spawned.
+pub(crate) fn fake_spec(restart_policy: RestartPolicy) -> ServerSpec {
+    let mut spec = ServerSpec {
+        restart_policy,
+        runtime: RuntimeConfig {
+            kind: RuntimeKind::Fake,
+            ..Default::default()
+        },
+        readiness: ReadinessConfig {
+            probes: Vec::new(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    spec.config.game.name = String::from("Test server");
+    spec.config.game.scenario_id = String::from("{ECC61978EDCC2B5A}Missions/23_Campaign.conf");
+    free_ports().apply(&mut spec.config);
+    spec
+}
+
+/// Three distinct UDP ports nothing is bound to, as picked by the OS.
+pub(crate) fn free_ports() -> ServerPorts {
+    // Every socket is held until all three are picked, so none repeat.
+    let sockets: Vec<_> = (0..3)
+        .map(|_| UdpSocket::bind(("0.0.0.0", 0)).expect("no free UDP port"))
+        .collect();
+    let port = |i: usize| sockets[i].local_addr().expect("unbound socket").port();
+    ServerPorts {
+        game: port(0),
+        a2s: port(1),
+        rcon: port(2),
+    }
+}
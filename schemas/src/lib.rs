@@ -1,5 +1,7 @@
 use std::{collections::HashMap, vec};
 
+use uuid::Uuid;
+
 #[cfg(feature = "serde")]
 extern crate serde;
 
@@ -9,6 +11,13 @@ extern crate schemars;
 #[cfg(feature = "sea_orm")]
 extern crate sea_orm;
 
+mod validation;
+
+pub use validation::*;
+
+/// The RCON password new configs start with, which should be changed.
+pub const DEFAULT_RCON_PASSWORD: &str = "changeme_withoutspaces";
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(
     feature = "serde",
@@ -79,7 +88,7 @@ impl Default for RconConfig {
         Self {
             address: String::from("0.0.0.0"),
             port: 19999,
-            password: String::from("changeme_withoutspaces"),
+            password: String::from(DEFAULT_RCON_PASSWORD),
             max_clients: 16,
             permission: RconPermission::default(),
             blacklist: Vec::new(),
@@ -174,9 +183,33 @@ impl Default for GameProperties {
 pub struct ModConfig {
     pub mod_id: String,
     pub name: String,
+    /// The version to load, e.g. `1.0.12`. The latest version is loaded if
+    /// unset.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub version: Option<String>,
     pub required: bool,
 }
 
+impl ModConfig {
+    /// Whether this is the mod with `mod_id`. Workshop IDs are hexadecimal,
+    /// so case is ignored.
+    pub fn is(&self, mod_id: &str) -> bool {
+        self.mod_id.trim().eq_ignore_ascii_case(mod_id.trim())
+    }
+}
+
+/// A list of mods in load order, stored as a single JSON column, e.g. a mod
+/// preset's mods. It is serialized as a plain array.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(transparent))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub struct ModList(pub Vec<ModConfig>);
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(
     feature = "serde",
@@ -201,6 +234,13 @@ pub struct GameConfig {
     pub mods: Vec<ModConfig>,
 }
 
+impl GameConfig {
+    /// Where the mod with `mod_id` is in the load order, if it is loaded.
+    pub fn mod_position(&self, mod_id: &str) -> Option<usize> {
+        self.mods.iter().position(|m| m.is(mod_id))
+    }
+}
+
 impl Default for GameConfig {
     fn default() -> Self {
         Self {
@@ -220,6 +260,23 @@ impl Default for GameConfig {
     }
 }
 
+/// The mod presets a server loads mods from, on top of its own mods.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub struct ModPresetLinks {
+    /// The IDs of the presets, in the order their mods load.
+    pub presets: Vec<Uuid>,
+
+    /// Mods from the presets which this server doesn't load.
+    pub excluded_mods: Vec<String>,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(
     feature = "serde",
@@ -312,3 +369,406 @@ impl Default for ServerConfig {
         }
     }
 }
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub enum RestartMode {
+    /// The server is never restarted automatically.
+    #[default]
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "never"))
+    )]
+    Never,
+
+    /// The server is restarted if it crashes or exits with a non-zero code.
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "onFailure"))
+    )]
+    OnFailure,
+
+    /// The server is restarted whenever it exits without being stopped.
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "always"))
+    )]
+    Always,
+}
+
+/// Controls whether HARM brings a server back up after it exits without being
+/// asked to. This is HARM's own setting and is not written to the Reforger
+/// config file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub struct RestartPolicy {
+    pub mode: RestartMode,
+    /// How many consecutive restarts are attempted before the server is
+    /// quarantined.
+    pub max_retries: u32,
+    /// The delay before the first restart, doubled for every consecutive
+    /// restart after it.
+    pub backoff_secs: u64,
+    /// The upper bound for the delay between restarts.
+    pub max_backoff_secs: u64,
+    /// A server which stays up for at least this long has its consecutive
+    /// restart count reset.
+    pub reset_window_secs: u64,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            mode: RestartMode::default(),
+            max_retries: 5,
+            backoff_secs: 5,
+            max_backoff_secs: 300,
+            reset_window_secs: 600,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub enum LogLevel {
+    #[default]
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "normal"))
+    )]
+    Normal,
+
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "warning"))
+    )]
+    Warning,
+
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "error"))
+    )]
+    Error,
+
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "fatal"))
+    )]
+    Fatal,
+}
+
+impl LogLevel {
+    /// The value Reforger expects for `-logLevel`.
+    pub fn as_arg(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Warning => "warning",
+            Self::Error => "error",
+            Self::Fatal => "fatal",
+        }
+    }
+}
+
+/// Command line options passed to the Reforger server executable. The
+/// `-config` argument is always managed by HARM and can't be set here.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub struct LaunchOptions {
+    /// `-maxFPS`: caps the server's simulation rate.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub max_fps: Option<u16>,
+    /// `-profile`: the directory the server keeps its profile, logs and saves
+    /// in. Defaults to a directory in the server's HARM data directory.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub profile: Option<String>,
+    /// `-addonsDir`: extra directories to load addons from.
+    pub addons_dir: Vec<String>,
+    /// `-addonDownloadDir`: where the server downloads Workshop addons to.
+    /// Defaults to a directory in the server's HARM data directory.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub addon_download_dir: Option<String>,
+    /// `-logStats`: how often, in milliseconds, performance stats are logged.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub log_stats: Option<u32>,
+    /// `-logLevel`
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub log_level: Option<LogLevel>,
+    /// `-loadSessionSave`: the session save to resume. An empty string resumes
+    /// the latest save.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub load_session_save: Option<String>,
+    /// `-autoreload`: how many seconds after a game ends the scenario is
+    /// reloaded.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub autoreload: Option<u32>,
+    /// `-freezeCheck`: how many seconds the server may be unresponsive before
+    /// it is considered frozen.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub freeze_check: Option<u32>,
+    /// Arguments appended verbatim after every other argument.
+    pub extra_args: Vec<String>,
+    /// Environment variables set for the server process.
+    pub env: HashMap<String, String>,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self {
+            max_fps: Some(60),
+            profile: None,
+            addons_dir: Vec::new(),
+            addon_download_dir: None,
+            log_stats: None,
+            log_level: None,
+            load_session_save: None,
+            autoreload: None,
+            freeze_check: None,
+            extra_args: Vec::new(),
+            env: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub enum RuntimeKind {
+    /// A process on the same host as HARM.
+    #[default]
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "local"))
+    )]
+    Local,
+
+    /// A Podman container.
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "podman"))
+    )]
+    Podman,
+
+    /// A Docker container.
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "docker"))
+    )]
+    Docker,
+
+    /// A transient systemd service unit.
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "systemd"))
+    )]
+    Systemd,
+
+    /// An in-memory stand-in which never runs anything, for testing.
+    #[cfg_attr(feature = "serde", cfg_attr(feature = "serde", serde(rename = "fake")))]
+    Fake,
+}
+
+/// Controls how HARM runs a server. This is HARM's own setting and is not
+/// written to the Reforger config file.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub struct RuntimeConfig {
+    pub kind: RuntimeKind,
+    /// The image to run the server in. Required for the Podman and Docker
+    /// runtimes; the image must be able to run the Reforger executable.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub image: Option<String>,
+    /// Extra volumes to mount into the container, in `host:container[:opts]`
+    /// form. The Reforger install and the server's data directory are always
+    /// mounted at the same paths as on the host.
+    pub volumes: Vec<String>,
+    /// Run systemd units in the calling user's service manager rather than
+    /// the system one.
+    pub user_unit: bool,
+}
+
+/// An I/O scheduling class, as set with `ionice`.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub enum IoClass {
+    /// Served first, regardless of other processes. Requires root.
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "realtime"))
+    )]
+    Realtime,
+
+    /// The default class.
+    #[default]
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "bestEffort"))
+    )]
+    BestEffort,
+
+    /// Only served when no other process needs the disk.
+    #[cfg_attr(feature = "serde", cfg_attr(feature = "serde", serde(rename = "idle")))]
+    Idle,
+}
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub struct IoPriority {
+    pub class: IoClass,
+    /// From 0 (highest) to 7 (lowest). Not used by the idle class.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub level: Option<u8>,
+}
+
+/// Limits enforced through a cgroup v2 group created for the server.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub struct CgroupLimits {
+    /// `memory.max`: the memory the server may use, in bytes, before it is
+    /// reclaimed from or OOM-killed.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub memory_max: Option<u64>,
+    /// `cpu.max`: the CPU time the server may use, as a percentage of one
+    /// core. 200 allows two full cores.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub cpu_max_percent: Option<u32>,
+}
+
+/// OS-level resource controls applied to a server's process when it is
+/// started. This is HARM's own setting and is not written to the Reforger
+/// config file.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub struct ResourceLimits {
+    /// The CPUs the server may run on. Empty allows every CPU.
+    pub cpu_affinity: Vec<u32>,
+    /// The scheduling niceness, from -20 (highest priority) to 19.
+    /// Negative values require privileges.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub nice: Option<i32>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub io_priority: Option<IoPriority>,
+    /// `RLIMIT_NOFILE`: the most files the server may have open.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub max_open_files: Option<u64>,
+    /// `RLIMIT_CORE`: the largest core dump the server may write, in bytes.
+    /// 0 disables core dumps.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub max_core_size: Option<u64>,
+    /// The OOM killer score adjustment, from -1000 (never kill) to 1000
+    /// (kill first). Negative values require privileges.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub oom_score_adj: Option<i32>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub cgroup: Option<CgroupLimits>,
+}
+
+/// A check which must pass before a starting server is considered ready.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(tag = "type", rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub enum ReadinessProbe {
+    /// Passes once a line of the server's console output matches `pattern`,
+    /// a regular expression.
+    LogPattern { pattern: String },
+
+    /// Passes once the server answers an A2S query on its A2S port.
+    A2s,
+
+    /// Passes once the server accepts an RCON login with its RCON password.
+    Rcon,
+}
+
+/// How HARM decides a started server is ready to be joined. This is HARM's
+/// own setting and is not written to the Reforger config file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "sea_orm", derive(sea_orm::FromJsonQueryResult))]
+pub struct ReadinessConfig {
+    /// Every probe must pass for the server to become ready. With no probes,
+    /// a server is ready as soon as it is spawned.
+    pub probes: Vec<ReadinessProbe>,
+    /// How long a server has to become ready before it is stopped and
+    /// marked as failed.
+    pub startup_timeout_secs: u64,
+    /// How often the A2S and RCON probes are retried.
+    pub interval_secs: u64,
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        Self {
+            probes: vec![ReadinessProbe::A2s],
+            startup_timeout_secs: 600,
+            interval_secs: 5,
+        }
+    }
+}
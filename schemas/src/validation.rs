@@ -0,0 +1,511 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    net::IpAddr,
+};
+
+use crate::{GamePlatform, ModConfig, ServerConfig, DEFAULT_RCON_PASSWORD};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum Severity {
+    /// Worth fixing, but Reforger will still accept the config.
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "warning"))
+    )]
+    Warning,
+
+    /// Reforger would reject the config, or fail to start with it.
+    #[cfg_attr(
+        feature = "serde",
+        cfg_attr(feature = "serde", serde(rename = "error"))
+    )]
+    Error,
+}
+
+/// A problem with one field of a server config.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ValidationIssue {
+    /// The field's JSON pointer, e.g. `/game/maxPlayers`.
+    pub pointer: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Every problem found in a server config, in field order.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    cfg_attr(feature = "serde", serde(rename_all = "camelCase"))
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Whether the config has no errors. Warnings don't count.
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+    }
+
+    fn error(&mut self, pointer: impl Into<String>, message: impl Into<String>) {
+        self.push(pointer.into(), Severity::Error, message.into());
+    }
+
+    fn warning(&mut self, pointer: impl Into<String>, message: impl Into<String>) {
+        self.push(pointer.into(), Severity::Warning, message.into());
+    }
+
+    fn push(&mut self, pointer: String, severity: Severity, message: String) {
+        self.issues.push(ValidationIssue {
+            pointer,
+            severity,
+            message,
+        });
+    }
+}
+
+/// Finds mods listed more than once, returning the index of each repeat and
+/// of the mod's first listing.
+pub fn duplicate_mods(mods: &[ModConfig]) -> Vec<(usize, usize)> {
+    let mut seen = HashMap::new();
+    let mut duplicates = Vec::new();
+    for (i, game_mod) in mods.iter().enumerate() {
+        let mod_id = game_mod.mod_id.trim();
+        if mod_id.is_empty() {
+            continue;
+        }
+        // Workshop IDs are case-insensitive hexadecimal.
+        match seen.entry(mod_id.to_uppercase()) {
+            Entry::Occupied(first) => duplicates.push((i, *first.get())),
+            Entry::Vacant(entry) => {
+                entry.insert(i);
+            }
+        }
+    }
+    duplicates
+}
+
+impl ServerConfig {
+    /// Checks the config against the rules Reforger applies when it loads
+    /// one. Errors are fields Reforger would reject or which would stop the
+    /// server from starting; warnings are likely mistakes.
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        self.validate_network(&mut report);
+        self.validate_rcon(&mut report);
+        self.validate_game(&mut report);
+        self.validate_operating(&mut report);
+        report
+    }
+
+    fn validate_network(&self, report: &mut ValidationReport) {
+        for (pointer, address) in [
+            ("/bindAddress", Some(&self.bind_address)),
+            ("/publicAddress", self.public_address.as_ref()),
+            ("/a2s/address", Some(&self.a2s.address)),
+            ("/rcon/address", Some(&self.rcon.address)),
+        ] {
+            if address.is_some_and(|address| address.parse::<IpAddr>().is_err()) {
+                report.error(pointer, "must be an IP address");
+            }
+        }
+
+        // Reforger listens on the bind port if set, otherwise the public one.
+        let game_port = match self.bind_port {
+            Some(port) => ("/bindPort", port),
+            None => ("/publicPort", self.public_port),
+        };
+        let ports = [
+            game_port,
+            ("/a2s/port", self.a2s.port),
+            ("/rcon/port", self.rcon.port),
+        ];
+        for (i, (pointer, port)) in ports.iter().enumerate() {
+            if *port == 0 {
+                report.error(*pointer, "must not be 0");
+            } else if ports[..i].iter().any(|(_, other)| other == port) {
+                report.error(
+                    *pointer,
+                    format!("port {} is used by another of this server's ports", port),
+                );
+            }
+        }
+    }
+
+    fn validate_rcon(&self, report: &mut ValidationReport) {
+        let rcon = &self.rcon;
+        if rcon.password.len() < 3 || rcon.password.contains(' ') {
+            report.error(
+                "/rcon/password",
+                "must be at least 3 characters, without spaces",
+            );
+        } else if rcon.password == DEFAULT_RCON_PASSWORD {
+            report.warning("/rcon/password", "is the default password");
+        }
+        if !(1..=16).contains(&rcon.max_clients) {
+            report.error("/rcon/maxClients", "must be between 1 and 16");
+        }
+    }
+
+    fn validate_game(&self, report: &mut ValidationReport) {
+        let game = &self.game;
+        if game.name.trim().is_empty() {
+            report.error("/game/name", "must not be empty");
+        }
+        if game.scenario_id.trim().is_empty() {
+            report.error("/game/scenarioId", "must not be empty");
+        }
+        if !(1..=128).contains(&game.max_players) {
+            report.error("/game/maxPlayers", "must be between 1 and 128");
+        }
+        if game.password_admin.contains(' ') {
+            report.error("/game/passwordAdmin", "must not contain spaces");
+        }
+        if game
+            .password
+            .as_ref()
+            .is_some_and(|password| password.contains(' '))
+        {
+            report.error("/game/password", "must not contain spaces");
+        }
+
+        // Reforger takes `supportedPlatforms` over `crossPlatform` when both
+        // are set, so a mismatch silently drops one of them.
+        let consoles = game
+            .supported_platforms
+            .iter()
+            .any(|platform| *platform != GamePlatform::PC);
+        if game.supported_platforms.is_empty() {
+            report.error(
+                "/game/supportedPlatforms",
+                "must list at least one platform",
+            );
+        } else if game.cross_platform && !consoles {
+            report.warning(
+                "/game/supportedPlatforms",
+                "crossPlatform is enabled, but only PLATFORM_PC is supported",
+            );
+        } else if !game.cross_platform && consoles {
+            report.warning(
+                "/game/crossPlatform",
+                "is disabled, but supportedPlatforms includes consoles",
+            );
+        }
+        for (i, platform) in game.supported_platforms.iter().enumerate() {
+            if game.supported_platforms[..i].contains(platform) {
+                report.warning(
+                    format!("/game/supportedPlatforms/{}", i),
+                    "is listed more than once",
+                );
+            }
+        }
+
+        let properties = &game.game_properties;
+        if !(500..=10000).contains(&properties.server_max_view_distance) {
+            report.error(
+                "/game/gameProperties/serverMaxViewDistance",
+                "must be between 500 and 10000",
+            );
+        }
+        if properties.server_min_grass_distance != 0
+            && !(50..=150).contains(&properties.server_min_grass_distance)
+        {
+            report.error(
+                "/game/gameProperties/serverMinGrassDistance",
+                "must be 0 or between 50 and 150",
+            );
+        }
+        if !(500..=5000).contains(&properties.network_view_distance) {
+            report.error(
+                "/game/gameProperties/networkViewDistance",
+                "must be between 500 and 5000",
+            );
+        }
+        if !properties.battleye {
+            report.warning(
+                "/game/gameProperties/battlEye",
+                "is disabled, so cheaters won't be kicked",
+            );
+        }
+
+        for (i, game_mod) in game.mods.iter().enumerate() {
+            let pointer = format!("/game/mods/{}/modId", i);
+            let mod_id = game_mod.mod_id.trim();
+            if mod_id.is_empty() {
+                report.error(pointer, "must not be empty");
+            } else if mod_id.len() != 16 || !mod_id.chars().all(|c| c.is_ascii_hexdigit()) {
+                report.warning(pointer, "is not a 16-digit Workshop ID");
+            }
+            if game_mod
+                .version
+                .as_ref()
+                .is_some_and(|version| version.trim().is_empty() || version.contains(' '))
+            {
+                report.error(
+                    format!("/game/mods/{}/version", i),
+                    "must not be empty or contain spaces; leave it unset for the latest version",
+                );
+            }
+        }
+        for (i, first) in duplicate_mods(&game.mods) {
+            report.error(
+                format!("/game/mods/{}/modId", i),
+                format!(
+                    "mod {} is already listed at /game/mods/{}",
+                    game.mods[i].mod_id.trim(),
+                    first
+                ),
+            );
+        }
+    }
+
+    fn validate_operating(&self, report: &mut ValidationReport) {
+        let operating = &self.operating;
+        if operating.ai_limit < -1 {
+            report.error("/operating/aiLimit", "must be -1 (no limit) or more");
+        }
+        if operating.player_save_time < 1 {
+            report.error("/operating/playerSaveTime", "must be at least 1 second");
+        }
+        if !(5..=300).contains(&operating.slot_reservation_timeout) {
+            report.error(
+                "/operating/slotReservationTimeout",
+                "must be between 5 and 300 seconds",
+            );
+        }
+        if !(0..=50).contains(&operating.join_queue.max_size) {
+            report.error("/operating/joinQueue/maxSize", "must be between 0 and 50");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use Severity::{Error, Warning};
+
+    /// A config with no issues.
+    fn valid() -> ServerConfig {
+        let mut config = ServerConfig::default();
+        config.game.name = String::from("Test server");
+        config.rcon.password = String::from("secret");
+        config
+    }
+
+    /// The pointer and severity of every issue with `config`.
+    fn issues(config: &ServerConfig) -> Vec<(String, Severity)> {
+        config
+            .validate()
+            .issues
+            .into_iter()
+            .map(|issue| (issue.pointer, issue.severity))
+            .collect()
+    }
+
+    fn issue(pointer: &str, severity: Severity) -> Vec<(String, Severity)> {
+        vec![(pointer.to_string(), severity)]
+    }
+
+    fn game_mod(mod_id: &str) -> ModConfig {
+        ModConfig {
+            mod_id: mod_id.to_string(),
+            name: String::from("Mod"),
+            version: None,
+            required: true,
+        }
+    }
+
+    #[test]
+    fn accepts_a_valid_config() {
+        let report = valid().validate();
+        assert_eq!(report.issues, []);
+        assert!(report.is_valid());
+    }
+
+    #[test]
+    fn checks_max_players() {
+        for (max_players, expected) in [
+            (0, issue("/game/maxPlayers", Error)),
+            (1, vec![]),
+            (128, vec![]),
+            (129, issue("/game/maxPlayers", Error)),
+        ] {
+            let mut config = valid();
+            config.game.max_players = max_players;
+            assert_eq!(issues(&config), expected, "maxPlayers {}", max_players);
+        }
+    }
+
+    #[test]
+    fn checks_the_rcon_password() {
+        for (password, expected) in [
+            ("with space", issue("/rcon/password", Error)),
+            ("ab", issue("/rcon/password", Error)),
+            ("abc", vec![]),
+            (DEFAULT_RCON_PASSWORD, issue("/rcon/password", Warning)),
+        ] {
+            let mut config = valid();
+            config.rcon.password = password.to_string();
+            assert_eq!(issues(&config), expected, "password {:?}", password);
+        }
+
+        let mut config = valid();
+        config.rcon.password = DEFAULT_RCON_PASSWORD.to_string();
+        assert!(config.validate().is_valid());
+    }
+
+    #[test]
+    fn checks_view_distances() {
+        let max_view = "/game/gameProperties/serverMaxViewDistance";
+        for (distance, expected) in [
+            (499, issue(max_view, Error)),
+            (500, vec![]),
+            (10000, vec![]),
+            (10001, issue(max_view, Error)),
+        ] {
+            let mut config = valid();
+            config.game.game_properties.server_max_view_distance = distance;
+            assert_eq!(
+                issues(&config),
+                expected,
+                "serverMaxViewDistance {}",
+                distance
+            );
+        }
+
+        let network_view = "/game/gameProperties/networkViewDistance";
+        for (distance, expected) in [
+            (499, issue(network_view, Error)),
+            (500, vec![]),
+            (5000, vec![]),
+            (5001, issue(network_view, Error)),
+        ] {
+            let mut config = valid();
+            config.game.game_properties.network_view_distance = distance;
+            assert_eq!(
+                issues(&config),
+                expected,
+                "networkViewDistance {}",
+                distance
+            );
+        }
+    }
+
+    #[test]
+    fn checks_grass_distance() {
+        let grass = "/game/gameProperties/serverMinGrassDistance";
+        for (distance, expected) in [
+            (0, vec![]),
+            (49, issue(grass, Error)),
+            (50, vec![]),
+            (150, vec![]),
+            (151, issue(grass, Error)),
+        ] {
+            let mut config = valid();
+            config.game.game_properties.server_min_grass_distance = distance;
+            assert_eq!(
+                issues(&config),
+                expected,
+                "serverMinGrassDistance {}",
+                distance
+            );
+        }
+    }
+
+    #[test]
+    fn checks_platforms() {
+        use GamePlatform::{PC, PSN, XBL};
+
+        for (cross_platform, platforms, expected) in [
+            (false, vec![PC], vec![]),
+            (true, vec![PC, XBL, PSN], vec![]),
+            (true, vec![PC], issue("/game/supportedPlatforms", Warning)),
+            (false, vec![PC, XBL], issue("/game/crossPlatform", Warning)),
+            (false, vec![], issue("/game/supportedPlatforms", Error)),
+            (
+                false,
+                vec![PC, PC],
+                issue("/game/supportedPlatforms/1", Warning),
+            ),
+        ] {
+            let mut config = valid();
+            config.game.cross_platform = cross_platform;
+            config.game.supported_platforms = platforms.clone();
+            assert_eq!(
+                issues(&config),
+                expected,
+                "crossPlatform {} with {:?}",
+                cross_platform,
+                platforms
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_duplicate_mods_in_any_case() {
+        let mut config = valid();
+        config.game.mods = vec![
+            game_mod("ABCDEF0123456789"),
+            game_mod("5965550F24A0C152"),
+            game_mod(" abcdef0123456789"),
+        ];
+
+        let report = config.validate();
+        assert_eq!(
+            report.issues,
+            [ValidationIssue {
+                pointer: String::from("/game/mods/2/modId"),
+                severity: Error,
+                message: String::from("mod abcdef0123456789 is already listed at /game/mods/0"),
+            }]
+        );
+        assert_eq!(duplicate_mods(&config.game.mods), [(2, 0)]);
+    }
+
+    #[test]
+    fn checks_port_clashes() {
+        // The public port is used while no bind port is set.
+        let mut config = valid();
+        config.public_port = config.a2s.port;
+        assert_eq!(issues(&config), issue("/a2s/port", Error));
+
+        // Once a bind port is set, the public port can be anything.
+        config.bind_port = Some(2002);
+        assert_eq!(issues(&config), []);
+        config.public_port = 2002;
+        assert_eq!(issues(&config), []);
+
+        config.bind_port = Some(config.rcon.port);
+        assert_eq!(issues(&config), issue("/rcon/port", Error));
+
+        config.bind_port = Some(0);
+        assert_eq!(issues(&config), issue("/bindPort", Error));
+    }
+}